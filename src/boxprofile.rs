@@ -0,0 +1,633 @@
+// By: Eric MacDonald (eMac)
+
+use std::env;
+use std::fs;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::config::{LauncherConfig, MAMEDocument, MAMEMachineNode};
+use crate::wtv::{
+	buildio::BuildIODataCollation,
+	buildmeta::BuildAddressRanges
+};
+
+const BOOTROM_BASE_ADDRESS: u32 = 0x9fc00000;
+// wtv2 (Plus) boxes will be detected and ran from this launcher but we assume (and can only verify) a flash-based approm 
+const APPROM1_FLASH_BASE_ADDRESS: u32 = 0x9f000000;
+const APPROM2_FLASH_BASE_ADDRESS: u32 = 0x9fe00000;
+const APPROM3_DISK_BASE_ADDRESS_MIN: u32 = 0x80300000;
+const APPROM3_DISK_BASE_ADDRESS_MAX: u32 = 0x84400000;
+
+// Profiles in this file are checked before the built-in ones so a box can be changed or added without a new launcher.
+const BOX_PROFILES_FILE_NAME: &'static str = "mame_launcher_boxes.toml";
+// The profiles last loaded along with when the profiles file was changed so edits to it are picked up.
+static LOADED_BOX_PROFILES: Mutex<Option<(Option<SystemTime>, BoxProfiles)>> = Mutex::new(None);
+// Boxes we know about are listed in this order. Any other WebTV machine MAME has is listed after these.
+const KNOWN_BOX_ORDER: [&'static str; 19] = [
+	"wtv1bf0",
+	"wtv1sony",
+	"wtv1phil",
+	"wtv2lc2",
+	"wtv2sony",
+	"wtv2phil",
+	"wtv2jpp",
+	"wtv2jpc",
+	"wtv2wld",
+	"wtv1bfe",
+	"wtv1pal",
+	"wtv1dev",
+	"wtv1dv2",
+	"wtv2drb",
+	"wtv2npl",
+	"wtv2ncl",
+	"wtv2esr",
+	"wtv2utv",
+	"wtv2uvd"
+];
+// MAME keeps the WebTV drivers together in their own source directory.
+const WEBTV_SOURCE_PATTERN: &'static str = r"(^|/)webtv/";
+// Devices only WebTV boxes use.
+const WEBTV_DEVICE_REFS: [&'static str; 3] = [
+	"spot_asic",
+	"solo_asic",
+	"mdoc_collection"
+];
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BootROMStorage {
+	MaskRom,
+	StrippedFlash,
+	NoBootROM
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AppROMStorage {
+	StrippedFlash,
+	Disk,
+	Flashdisk
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileCollation {
+	Raw,
+	StrippedRoms,
+	QuadStrippedRoms,
+	ByteStrippedRoms,
+	ByteSwapped16,
+	ByteSwapped1632,
+	ByteSwapped32
+}
+
+impl ProfileCollation {
+	pub fn to_collation(&self) -> BuildIODataCollation {
+		match self {
+			ProfileCollation::Raw => BuildIODataCollation::Raw,
+			ProfileCollation::StrippedRoms => BuildIODataCollation::StrippedROMs,
+			ProfileCollation::QuadStrippedRoms => BuildIODataCollation::QuadStrippedROMs,
+			ProfileCollation::ByteStrippedRoms => BuildIODataCollation::ByteStrippedROMs,
+			ProfileCollation::ByteSwapped16 => BuildIODataCollation::ByteSwapped16,
+			ProfileCollation::ByteSwapped1632 => BuildIODataCollation::ByteSwapped1632,
+			ProfileCollation::ByteSwapped32 => BuildIODataCollation::ByteSwapped32
+		}
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct AddressRange {
+	pub min: u32,
+	pub max: u32
+}
+
+impl AddressRange {
+	pub fn contains(&self, address: u32) -> bool {
+		address >= self.min && address <= self.max
+	}
+
+	pub fn any_contains(ranges: &Vec<AddressRange>, address: u32) -> bool {
+		ranges.iter().any(|range| range.contains(address))
+	}
+}
+
+// Everything the launcher needs to know about a box that MAME doesn't tell us.
+// Fields left out of a profile in the TOML file get the same values as the built-in catch-all profile.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BoxProfile {
+	// Matched against MAME's machine name. The first profile that matches is used.
+	// Profiles in the TOML file have to set this. The catch-all pattern would match every box.
+	#[serde(default = "String::new")]
+	pub box_pattern: String,
+	pub bootrom_storage: BootROMStorage,
+	pub bootrom_size: u64,
+	pub bootrom_base_addresses: Vec<AddressRange>,
+	// Worked out from the devices MAME lists for the box if it isn't set.
+	pub approm_storage: Option<AppROMStorage>,
+	pub approm_size: u64,
+	pub flash_approm_base_addresses: Vec<AddressRange>,
+	pub disk_approm_base_addresses: Vec<AddressRange>,
+	pub flashdisk_approm_base_addresses: Vec<AddressRange>,
+	// The byte order the box's HD uses. Images in other orders are still detected.
+	pub disk_collation: ProfileCollation,
+	// The SSID manufacture code the box expects. Any manufacture is fine if it isn't set.
+	pub ssid_manufacture: Option<u16>,
+	pub runs_classic_bootroms: bool,
+	pub runs_wince: bool,
+	pub satellite: bool,
+	// How the bootrom and approm flash is split over its chips.
+	pub flash_collation: ProfileCollation,
+	pub bootrom_flash_prefix: String,
+	pub approm_flash_prefix: String,
+	pub approm2_flash_prefix: String,
+	// Boxes that keep a WinCE image in a second flash bank. Empty if there isn't one.
+	pub wince_flash_prefix: String,
+	pub flashdisk_file: String,
+	// Added to the MAME command line when the box is started.
+	pub mame_options: Vec<String>,
+	// Name of the icon in ui/images/box-icons without the extension. No icon is shown if this is empty.
+	pub icon: String
+}
+
+impl Default for BoxProfile {
+	fn default() -> BoxProfile {
+		BoxProfile {
+			box_pattern: r"^wtv".into(),
+			bootrom_storage: BootROMStorage::MaskRom,
+			bootrom_size: 0x200000,
+			bootrom_base_addresses: vec![AddressRange { min: BOOTROM_BASE_ADDRESS, max: BOOTROM_BASE_ADDRESS }],
+			approm_storage: None,
+			approm_size: 0x200000,
+			flash_approm_base_addresses: vec![
+				AddressRange { min: APPROM1_FLASH_BASE_ADDRESS, max: APPROM1_FLASH_BASE_ADDRESS },
+				AddressRange { min: APPROM2_FLASH_BASE_ADDRESS, max: APPROM2_FLASH_BASE_ADDRESS }
+			],
+			disk_approm_base_addresses: vec![AddressRange { min: APPROM3_DISK_BASE_ADDRESS_MIN, max: APPROM3_DISK_BASE_ADDRESS_MAX }],
+			flashdisk_approm_base_addresses: vec![AddressRange { min: APPROM3_DISK_BASE_ADDRESS_MIN, max: APPROM3_DISK_BASE_ADDRESS_MAX }],
+			disk_collation: ProfileCollation::ByteSwapped16,
+			ssid_manufacture: None,
+			runs_classic_bootroms: false,
+			runs_wince: false,
+			satellite: false,
+			flash_collation: ProfileCollation::StrippedRoms,
+			bootrom_flash_prefix: "bootrom_flash".into(),
+			approm_flash_prefix: "bank0_flash".into(),
+			approm2_flash_prefix: "approm_flash".into(),
+			wince_flash_prefix: "".into(),
+			flashdisk_file: "mdoc_flash0".into(),
+			mame_options: vec![],
+			icon: "wtv2".into()
+		}
+	}
+}
+
+impl BoxProfile {
+	fn new(box_pattern: &str) -> BoxProfile {
+		BoxProfile {
+			box_pattern: box_pattern.into(),
+			..Default::default()
+		}
+	}
+
+	pub fn matches(&self, box_name: &str) -> bool {
+		match Regex::new(&self.box_pattern) {
+			Ok(box_re) => box_re.is_match(box_name),
+			_ => false
+		}
+	}
+
+	// Shortcut for when only one box is looked at.
+	pub fn for_box(box_name: &str) -> BoxProfile {
+		BoxProfiles::load().for_box(box_name)
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+struct BoxProfileDocument {
+	#[serde(default)]
+	profile: Vec<BoxProfile>
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct BoxProfiles {
+	profiles: Vec<BoxProfile>
+}
+
+impl BoxProfiles {
+	// Profiles are looked at on every refresh so they're only read again if the profiles file changed.
+	pub fn load() -> BoxProfiles {
+		let profiles_file_path = BoxProfiles::get_profiles_file_path();
+
+		let profiles_modified = match fs::metadata(&profiles_file_path) {
+			Ok(metadata) => metadata.modified().ok(),
+			_ => None
+		};
+
+		let mut loaded_box_profiles = match LOADED_BOX_PROFILES.lock() {
+			Ok(loaded_box_profiles) => loaded_box_profiles,
+			Err(_e) => return BoxProfiles::read(&profiles_file_path)
+		};
+
+		match loaded_box_profiles.as_ref() {
+			Some((loaded_profiles_modified, box_profiles)) => {
+				if *loaded_profiles_modified == profiles_modified {
+					return box_profiles.clone();
+				}
+			},
+			_ => { }
+		};
+
+		let box_profiles = BoxProfiles::read(&profiles_file_path);
+
+		*loaded_box_profiles = Some((profiles_modified, box_profiles.clone()));
+
+		box_profiles
+	}
+
+	// A profiles file that can't be used is logged and left out so the built-in profiles still work.
+	fn read(profiles_file_path: &String) -> BoxProfiles {
+		let mut profiles = match BoxProfiles::get_user_profiles(profiles_file_path) {
+			Ok(user_profiles) => user_profiles,
+			Err(e) => {
+				let _ = LauncherConfig::log_error(&("Couldn't use the box profiles in ".to_owned() + BOX_PROFILES_FILE_NAME), e.as_ref());
+
+				vec![]
+			}
+		};
+
+		profiles.extend(BoxProfiles::default_profiles());
+
+		BoxProfiles {
+			profiles: profiles
+		}
+	}
+
+	pub fn for_box(&self, box_name: &str) -> BoxProfile {
+		for profile in self.profiles.iter() {
+			if profile.matches(box_name) {
+				return profile.clone();
+			}
+		}
+
+		BoxProfile::default()
+	}
+
+	// Every address any box runs a build from so a build can be classified before a box is picked.
+	pub fn get_build_address_ranges(&self) -> BuildAddressRanges {
+		let mut address_ranges = BuildAddressRanges::default();
+
+		for profile in self.profiles.iter() {
+			address_ranges.bootrom.extend(profile.bootrom_base_addresses.iter().map(|range| (range.min, range.max)));
+			address_ranges.flash_approm.extend(profile.flash_approm_base_addresses.iter().map(|range| (range.min, range.max)));
+			address_ranges.disk_approm.extend(profile.disk_approm_base_addresses.iter().map(|range| (range.min, range.max)));
+			address_ranges.flashdisk_approm.extend(profile.flashdisk_approm_base_addresses.iter().map(|range| (range.min, range.max)));
+		}
+
+		address_ranges
+	}
+
+	fn get_profiles_file_path() -> String {
+		let executable_dir = match env::current_exe() {
+			Ok(executable_path) => LauncherConfig::get_parent_from_pathbuf(executable_path).unwrap_or("".into()),
+			_ => "".into()
+		};
+
+		executable_dir + "/" + BOX_PROFILES_FILE_NAME
+	}
+
+	// Not having a profiles file is fine. One that's there but can't be read is an error.
+	fn get_user_profiles(profiles_file_path: &String) -> Result<Vec<BoxProfile>, Box<dyn std::error::Error>> {
+		if !std::path::Path::new(profiles_file_path).exists() {
+			return Ok(vec![]);
+		}
+
+		let toml_profiles_text: String =
+			fs::read_to_string(profiles_file_path)?;
+
+		let toml_profiles: BoxProfileDocument =
+			toml::from_str(&toml_profiles_text)?;
+
+		for (profile_index, profile) in toml_profiles.profile.iter().enumerate() {
+			if profile.box_pattern == "" {
+				return Err(("Profile ".to_owned() + &(profile_index + 1).to_string() + " doesn't have a box_pattern.").into());
+			}
+
+			match Regex::new(&profile.box_pattern) {
+				Ok(_) => { },
+				Err(e) => return Err(("Profile ".to_owned() + &(profile_index + 1).to_string() + " has a bad box_pattern: " + &e.to_string()).into())
+			};
+		}
+
+		Ok(toml_profiles.profile)
+	}
+
+	// More specific patterns need to come first.
+	fn default_profiles() -> Vec<BoxProfile> {
+		vec![
+			BoxProfile {
+				runs_classic_bootroms: true,
+				bootrom_storage: BootROMStorage::StrippedFlash,
+				approm_size: 0x400000,
+				icon: "wtv1".into(),
+				..BoxProfile::new(r"^wtv1dev$")
+			},
+			BoxProfile {
+				bootrom_storage: BootROMStorage::StrippedFlash,
+				approm_size: 0x400000,
+				..BoxProfile::new(r"^wtv\d+dev$")
+			},
+			BoxProfile {
+				runs_classic_bootroms: true,
+				approm_size: 0x400000,
+				icon: "wtv1".into(),
+				..BoxProfile::new(r"^wtv1pal$")
+			},
+			BoxProfile {
+				approm_size: 0x400000,
+				..BoxProfile::new(r"^wtv\d+pal$")
+			},
+			BoxProfile {
+				bootrom_storage: BootROMStorage::NoBootROM,
+				wince_flash_prefix: "bank1_flash".into(),
+				icon: "wld".into(),
+				..BoxProfile::new(r"^wtv\d+wld$")
+			},
+			BoxProfile {
+				disk_collation: ProfileCollation::ByteSwapped1632,
+				runs_wince: true,
+				satellite: true,
+				icon: "utv".into(),
+				..BoxProfile::new(r"^wtv\d+utv")
+			},
+			BoxProfile {
+				runs_wince: true,
+				satellite: true,
+				icon: "utv".into(),
+				..BoxProfile::new(r"^wtv\d+uvd$")
+			},
+			BoxProfile {
+				satellite: true,
+				..BoxProfile::new(r"^wtv\d+esr$")
+			},
+			BoxProfile {
+				runs_classic_bootroms: true,
+				ssid_manufacture: Some(0x0000),
+				icon: "wtv1".into(),
+				..BoxProfile::new(r"^wtv1sony$")
+			},
+			BoxProfile {
+				ssid_manufacture: Some(0x0000),
+				..BoxProfile::new(r"^wtv\d+sony$")
+			},
+			BoxProfile {
+				runs_classic_bootroms: true,
+				ssid_manufacture: Some(0x1000),
+				icon: "wtv1".into(),
+				..BoxProfile::new(r"^wtv1phil$")
+			},
+			BoxProfile {
+				ssid_manufacture: Some(0x1000),
+				..BoxProfile::new(r"^wtv\d+phil$")
+			},
+			BoxProfile {
+				runs_classic_bootroms: true,
+				icon: "bfe".into(),
+				..BoxProfile::new(r"^wtv1bfe$")
+			},
+			BoxProfile {
+				runs_classic_bootroms: true,
+				icon: "wtv1".into(),
+				..BoxProfile::new(r"^wtv1")
+			},
+			BoxProfile::default()
+		]
+	}
+}
+
+// Picks the WebTV boxes out of everything MAME can run and puts them in the order they're listed in the launcher.
+pub fn discover_boxes(mame_config: &MAMEDocument) -> Vec<MAMEMachineNode> {
+	let mut boxes: Vec<MAMEMachineNode> =
+		mame_config.machine
+		.clone()
+		.unwrap_or(vec![])
+		.into_iter()
+		.filter(|machine| machine.runnable.clone().unwrap_or("".into()) != "no" && is_webtv_machine(machine))
+		.collect();
+
+	boxes.sort_by(
+		| cmp_a, cmp_b | {
+			let a_name = cmp_a.name.clone().unwrap_or("".into());
+			let b_name = cmp_b.name.clone().unwrap_or("".into());
+
+			let a_known_order = KNOWN_BOX_ORDER.iter().position(|&val| val == a_name.as_str()).unwrap_or(usize::MAX);
+			let b_known_order = KNOWN_BOX_ORDER.iter().position(|&val| val == b_name.as_str()).unwrap_or(usize::MAX);
+
+			if a_known_order == b_known_order {
+				let a_sort_value = get_box_sort_value(&a_name);
+				let b_sort_value = get_box_sort_value(&b_name);
+
+				if a_sort_value == b_sort_value {
+					cmp_a.description.cmp(&cmp_b.description)
+				} else {
+					a_sort_value.cmp(&b_sort_value)
+				}
+			} else {
+				a_known_order.cmp(&b_known_order)
+			}
+		}
+	);
+
+	boxes
+}
+
+// Driver names change between MAME versions so we go by where the driver lives and what it's built from.
+pub fn is_webtv_machine(machine: &MAMEMachineNode) -> bool {
+	let device_ref_names: Vec<String> =
+		machine.device_ref
+		.clone()
+		.unwrap_or(vec![])
+		.iter()
+		.map(|device_ref| device_ref.name.clone().unwrap_or("".into()))
+		.collect();
+
+	let rom_regions: Vec<String> =
+		machine.rom
+		.clone()
+		.unwrap_or(vec![])
+		.iter()
+		.map(|rom| rom.region.clone().unwrap_or("".into()))
+		.collect();
+
+	is_webtv_driver(
+		&machine.name.clone().unwrap_or("".into()),
+		&machine.sourcefile.clone().unwrap_or("".into()),
+		&device_ref_names,
+		&rom_regions
+	)
+}
+
+// Split out from is_webtv_machine so machines can be checked while MAME's XML is being read.
+pub fn is_webtv_driver(machine_name: &String, sourcefile: &String, device_ref_names: &Vec<String>, rom_regions: &Vec<String>) -> bool {
+	if Regex::new(WEBTV_SOURCE_PATTERN).unwrap().is_match(sourcefile.as_str()) {
+		return true;
+	}
+
+	for device_ref_name in device_ref_names.iter() {
+		if WEBTV_DEVICE_REFS.contains(&device_ref_name.as_str()) {
+			return true;
+		}
+	}
+
+	// Older MAME builds don't always say where the driver came from. Lots of machines have a serial ID so the name is checked too.
+	if Regex::new(r"^wtv\d+").unwrap().is_match(machine_name.as_str()) {
+		for rom_region in rom_regions.iter() {
+			if rom_region == "serial_id" {
+				return true;
+			}
+		}
+	}
+
+	false
+}
+
+// Sorts the wtv1 boxes first, wtv2 boxes second and sony and phillips boxes before any other box type.
+// Boxes that don't follow MAME's wtv naming go last.
+fn get_box_sort_value(box_name: &String) -> String {
+	let sort_re: Regex = Regex::new(r"^wtv(?<box_iteration>\d+)(?<box_name>.+)").unwrap();
+	let mut sort_value: String = String::new();
+
+	match sort_re.captures(box_name.as_str()) {
+		Some(matches) => {
+			sort_value.push_str("0");
+			sort_value.push_str(&matches["box_iteration"]);
+
+			if matches["box_name"] == *"sony" {
+				sort_value.push_str("0");
+			} else if matches["box_name"] == *"phil" {
+				sort_value.push_str("1");
+			} else {
+				sort_value.push_str("2");
+				sort_value.push_str(&matches["box_name"]);
+			}
+		},
+		None => {
+			sort_value.push_str("1");
+			sort_value.push_str(box_name);
+		}
+	}
+
+	sort_value
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn get_test_machines(machines_xml: &str) -> MAMEDocument {
+		quick_xml::de::from_str(&("<mame build=\"0.270\">".to_owned() + machines_xml + "</mame>")).unwrap()
+	}
+
+	fn get_machine_names(machines: &Vec<MAMEMachineNode>) -> Vec<String> {
+		machines.iter().map(|machine| machine.name.clone().unwrap_or("".into())).collect()
+	}
+
+	#[test]
+	fn webtv_machines_are_found_by_source_device_or_serial_id() {
+		let mame_config = get_test_machines(r#"
+			<machine name="wtv1sony" sourcefile="webtv/wtv1.cpp"><description>Sony INT-W100</description></machine>
+			<machine name="newbox" sourcefile="src/mame/webtv/wtv2.cpp"><description>New box</description></machine>
+			<machine name="spotclone" sourcefile="misc/spotclone.cpp"><device_ref name="spot_asic"/></machine>
+			<machine name="soloclone" sourcefile="misc/soloclone.cpp"><device_ref name="screen"/><device_ref name="solo_asic"/></machine>
+			<machine name="wtv9old"><rom name="serial_id" region="serial_id" size="8"/></machine>
+			<machine name="pacman" sourcefile="pacman/pacman.cpp"><rom name="serial_id" region="serial_id" size="8"/></machine>
+			<machine name="wtv9nossid"><rom name="bootrom" region="bootrom" size="2097152"/></machine>
+			<machine name="notwebtv" sourcefile="notwebtv/box.cpp"><device_ref name="screen"/></machine>
+		"#);
+
+		let webtv_machines: Vec<String> =
+			mame_config.machine
+			.unwrap()
+			.into_iter()
+			.filter(|machine| is_webtv_machine(machine))
+			.map(|machine| machine.name.unwrap())
+			.collect();
+
+		assert_eq!(webtv_machines, vec!["wtv1sony", "newbox", "spotclone", "soloclone", "wtv9old"]);
+	}
+
+	#[test]
+	fn known_boxes_are_listed_first() {
+		let mame_config = get_test_machines(r#"
+			<machine name="wtv3foo" sourcefile="webtv/wtv3.cpp"><description>Foo</description></machine>
+			<machine name="wtv2sony" sourcefile="webtv/wtv2.cpp"><description>Sony INT-W200</description></machine>
+			<machine name="webtvproto" sourcefile="webtv/proto.cpp"><description>Prototype</description></machine>
+			<machine name="wtv3phil" sourcefile="webtv/wtv3.cpp"><description>Philips</description></machine>
+			<machine name="pacman" sourcefile="pacman/pacman.cpp"><description>Pac-Man</description></machine>
+			<machine name="wtv2zzz" sourcefile="webtv/wtv2.cpp"><description>Zzz</description></machine>
+			<machine name="spot_asic" sourcefile="webtv/spot.cpp" runnable="no"><description>SPOT ASIC</description></machine>
+			<machine name="wtv3sony" sourcefile="webtv/wtv3.cpp"><description>Sony</description></machine>
+			<machine name="wtv1bf0" sourcefile="webtv/wtv1.cpp"><description>bf0 box</description></machine>
+		"#);
+
+		assert_eq!(get_machine_names(&discover_boxes(&mame_config)), vec![
+			"wtv1bf0",
+			"wtv2sony",
+			"wtv2zzz",
+			"wtv3sony",
+			"wtv3phil",
+			"wtv3foo",
+			"webtvproto"
+		]);
+	}
+
+	#[test]
+	fn boxes_get_the_first_matching_default_profile() {
+		let box_profiles = BoxProfiles { profiles: BoxProfiles::default_profiles() };
+
+		let profile = box_profiles.for_box("wtv1dev");
+		assert_eq!(profile.box_pattern, r"^wtv1dev$");
+		assert_eq!(profile.bootrom_storage, BootROMStorage::StrippedFlash);
+		assert!(profile.runs_classic_bootroms);
+
+		let profile = box_profiles.for_box("wtv2dev");
+		assert_eq!(profile.box_pattern, r"^wtv\d+dev$");
+		assert!(!profile.runs_classic_bootroms);
+
+		let profile = box_profiles.for_box("wtv2wld");
+		assert_eq!(profile.bootrom_storage, BootROMStorage::NoBootROM);
+		assert_eq!(profile.wince_flash_prefix, "bank1_flash");
+
+		let profile = box_profiles.for_box("wtv2utv");
+		assert_eq!(profile.disk_collation, ProfileCollation::ByteSwapped1632);
+		assert!(profile.runs_wince && profile.satellite);
+
+		let profile = box_profiles.for_box("wtv2esr");
+		assert!(profile.satellite && !profile.runs_wince);
+
+		assert_eq!(box_profiles.for_box("wtv1sony").ssid_manufacture, Some(0x0000));
+		assert_eq!(box_profiles.for_box("wtv2phil").ssid_manufacture, Some(0x1000));
+		assert_eq!(box_profiles.for_box("wtv1bfe").icon, "bfe");
+
+		let profile = box_profiles.for_box("wtv1bf0");
+		assert_eq!(profile.box_pattern, r"^wtv1");
+		assert!(profile.runs_classic_bootroms);
+
+		let profile = box_profiles.for_box("wtv2lc2");
+		assert_eq!(profile.box_pattern, r"^wtv");
+		assert_eq!(profile.bootrom_storage, BootROMStorage::MaskRom);
+		assert_eq!(profile.disk_collation, ProfileCollation::ByteSwapped16);
+		assert!(!profile.runs_classic_bootroms);
+
+		// Anything else gets the catch-all profile.
+		assert_eq!(box_profiles.for_box("pacman").box_pattern, r"^wtv");
+	}
+}
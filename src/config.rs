@@ -6,12 +6,34 @@ use std::path::{Path, PathBuf};
 use toml;
 use quick_xml;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
-use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use quick_xml::events::{Event, BytesStart};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufReader, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use regex::Regex;
+use crate::error::LaunchError;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
 const CONFIG_FILE_NAME: &'static str = "mame_launcher.toml";
+const LOG_FILE_NAME: &'static str = "mame_launcher.log";
+// The WebTV machines from `mame -listxml` so we don't need to run MAME every time the config is loaded.
+const MAME_CACHE_FILE_NAME: &'static str = "mame_launcher_machines.toml";
+// Bumped when we start using more of MAME's XML so older caches are thrown out.
+const MAME_CACHE_VERSION: u32 = 1;
+
+const MAME_INI_FILE_NAME: &'static str = "mame.ini";
+
+// The last MAME config loaded. The config is reloaded after almost everything the user does so this keeps that quick.
+static LOADED_MAME_CONFIG: Mutex<Option<MAMEConfigCacheEntry>> = Mutex::new(None);
+// The last MAME directories resolved along with when mame.ini was changed so edits to mame.ini are picked up.
+static RESOLVED_MAME_DIRECTORIES: Mutex<Option<(MAMEExecutableStamp, Option<SystemTime>, MAMEDirectories)>> = Mutex::new(None);
+// The options the last MAME executable said it has. Only changes if MAME is rebuilt.
+static PROBED_MAME_CAPABILITIES: Mutex<Option<(MAMEExecutableStamp, MAMECapabilities)>> = Mutex::new(None);
+// Builds that MAME executables we haven't listed gave for `-version`. Empty if MAME didn't say.
+static PROBED_MAME_BUILDS: Mutex<Vec<(MAMEExecutableStamp, String)>> = Mutex::new(Vec::new());
 
 ////
 //
@@ -20,7 +42,7 @@ const CONFIG_FILE_NAME: &'static str = "mame_launcher.toml";
 ////
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename = "mame")]
 pub struct MAMEDocument {
 	#[serde(rename = "@build")]
@@ -33,7 +55,7 @@ pub struct MAMEDocument {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -55,7 +77,7 @@ pub struct MAMEMachineNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineBIOSSetNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -64,7 +86,7 @@ pub struct MAMEMachineBIOSSetNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineROMNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -72,6 +94,10 @@ pub struct MAMEMachineROMNode {
 	pub bios: Option<String>,
 	#[serde(rename = "@size")]
 	pub size: Option<u32>,
+	#[serde(rename = "@crc")]
+	pub crc: Option<String>,
+	#[serde(rename = "@sha1")]
+	pub sha1: Option<String>,
 	#[serde(rename = "@status")]
 	pub status: Option<String>,
 	#[serde(rename = "@region")]
@@ -81,7 +107,7 @@ pub struct MAMEMachineROMNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineDeviceNode {
 	#[serde(rename = "@type")]
     pub dtype: Option<String>,
@@ -92,7 +118,7 @@ pub struct MAMEMachineDeviceNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineDiskNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -107,7 +133,7 @@ pub struct MAMEMachineDiskNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineFeatureNode {
 	#[serde(rename = "@type")]
     pub ftype: Option<String>,
@@ -116,7 +142,7 @@ pub struct MAMEMachineFeatureNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineSlotNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -124,7 +150,7 @@ pub struct MAMEMachineSlotNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineSlotOptionNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -133,7 +159,7 @@ pub struct MAMEMachineSlotOptionNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineDeviceInstanceNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>,
@@ -142,21 +168,21 @@ pub struct MAMEMachineDeviceInstanceNode {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineDeviceExcensionNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineDeviceRefNode {
 	#[serde(rename = "@name")]
     pub name: Option<String>
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MAMEMachineChipNode {
 	#[serde(rename = "@type")]
     pub ctype: Option<String>,
@@ -168,6 +194,599 @@ pub struct MAMEMachineChipNode {
     pub clock: Option<u64>
 }
 
+////
+//
+// Cached MAME config
+//
+////
+
+// Tells us if the MAME executable changed since the cache was made.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MAMEExecutableStamp {
+	pub mame_path: String,
+	pub size: u64,
+	pub modified_secs: u64,
+	pub modified_nanos: u32
+}
+
+impl MAMEExecutableStamp {
+	pub fn new(mame_path: &String) -> Result<MAMEExecutableStamp, Box<dyn std::error::Error>> {
+		let metadata = fs::metadata(mame_path)?;
+
+		let modified =
+			metadata
+			.modified()?
+			.duration_since(UNIX_EPOCH)?;
+
+		Ok(MAMEExecutableStamp {
+			mame_path: mame_path.clone(),
+			size: metadata.len(),
+			modified_secs: modified.as_secs(),
+			modified_nanos: modified.subsec_nanos()
+		})
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MAMEConfigCacheEntry {
+	pub stamp: MAMEExecutableStamp,
+	pub mame: MAMEDocument
+}
+
+// One entry for each MAME executable that's been used.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MAMEConfigCache {
+	#[serde(default)]
+	pub version: u32,
+	#[serde(default)]
+	pub entry: Vec<MAMEConfigCacheEntry>
+}
+
+impl MAMEConfigCache {
+	pub fn read(cache_file_path: &String) -> Result<MAMEConfigCache, Box<dyn std::error::Error>> {
+		let toml_cache_text: String =
+			fs::read_to_string(cache_file_path)?;
+
+		let toml_cache: MAMEConfigCache =
+			toml::from_str(&toml_cache_text)?;
+
+		Ok(toml_cache)
+	}
+
+	pub fn write(&self, cache_file_path: &String) -> Result<(), Box<dyn std::error::Error>> {
+		let toml_cache_text: String =
+			toml::to_string(self)?;
+
+		fs::write(cache_file_path, toml_cache_text)?;
+
+		Ok(())
+	}
+
+	// An entry is only used while the executable it was made from hasn't changed.
+	pub fn find_entry(&self, stamp: &MAMEExecutableStamp) -> Option<&MAMEConfigCacheEntry> {
+		self.entry.iter().find(|entry| entry.stamp == *stamp)
+	}
+
+	// Each executable has one entry so the old one goes when MAME is updated in place.
+	pub fn set_entry(&mut self, entry: MAMEConfigCacheEntry) {
+		self.entry.retain(|cached_entry| cached_entry.stamp.mame_path != entry.stamp.mame_path);
+		self.entry.push(entry);
+	}
+}
+
+////
+//
+// Directories MAME is setup to use in mame.ini
+//
+////
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MAMEDirectories {
+	// MAME looks through these in order for a box's ROMs and CHDs.
+	pub rom_paths: Vec<String>,
+	pub nvram_directory: String,
+	pub diff_directory: String
+}
+
+impl MAMEDirectories {
+	// Asks MAME what it's using. mame.ini is read directly if MAME can't tell us.
+	pub fn resolve(mame_executable_path: &String) -> MAMEDirectories {
+		let mame_directory_path = LauncherConfig::get_parent(mame_executable_path.clone()).unwrap_or("".into());
+		let mame_ini_path = mame_directory_path.clone() + "/" + MAME_INI_FILE_NAME;
+
+		let stamp = match MAMEExecutableStamp::new(mame_executable_path) {
+			Ok(stamp) => stamp,
+			Err(_e) => return MAMEDirectories::from_options(&mame_directory_path, &HashMap::new())
+		};
+
+		let mame_ini_modified = match fs::metadata(&mame_ini_path) {
+			Ok(metadata) => metadata.modified().ok(),
+			_ => None
+		};
+
+		let mut resolved_mame_directories = match RESOLVED_MAME_DIRECTORIES.lock() {
+			Ok(resolved_mame_directories) => resolved_mame_directories,
+			Err(_e) => return MAMEDirectories::load(mame_executable_path, &mame_directory_path, &mame_ini_path)
+		};
+
+		match resolved_mame_directories.as_ref() {
+			Some((resolved_stamp, resolved_mame_ini_modified, mame_directories)) => {
+				if *resolved_stamp == stamp && *resolved_mame_ini_modified == mame_ini_modified {
+					return mame_directories.clone();
+				}
+			},
+			_ => { }
+		};
+
+		let mame_directories = MAMEDirectories::load(mame_executable_path, &mame_directory_path, &mame_ini_path);
+
+		*resolved_mame_directories = Some((stamp, mame_ini_modified, mame_directories.clone()));
+
+		mame_directories
+	}
+
+	// The first rom path that has a folder for the box. If none do then the first rom path is where a new folder should go.
+	pub fn get_rom_directory(&self, box_name: &String) -> String {
+		for rom_path in self.rom_paths.iter() {
+			let rom_directory = rom_path.clone() + "/" + box_name;
+
+			if Path::new(&rom_directory).exists() {
+				return rom_directory;
+			}
+		}
+
+		self.rom_paths.get(0).cloned().unwrap_or("roms".into()) + "/" + box_name
+	}
+
+	fn load(mame_executable_path: &String, mame_directory_path: &String, mame_ini_path: &String) -> MAMEDirectories {
+		let mame_options = match MAMEDirectories::get_showconfig(mame_executable_path, mame_directory_path) {
+			Ok(mame_options) => mame_options,
+			Err(_e) => {
+				match fs::read_to_string(mame_ini_path) {
+					Ok(mame_ini_text) => MAMEDirectories::parse_ini(&mame_ini_text),
+					_ => HashMap::new()
+				}
+			}
+		};
+
+		MAMEDirectories::from_options(mame_directory_path, &mame_options)
+	}
+
+	// Uses MAME's defaults for anything that isn't set.
+	fn from_options(mame_directory_path: &String, mame_options: &HashMap<String, String>) -> MAMEDirectories {
+		let get_option = |option_name: &str, default_value: &str| -> String {
+			match mame_options.get(option_name) {
+				Some(value) if value != "" => value.clone(),
+				_ => default_value.into()
+			}
+		};
+
+		let mut rom_paths: Vec<String> =
+			get_option("rompath", "roms")
+			.split(';')
+			.filter(|rom_path| rom_path.trim() != "")
+			.map(|rom_path| MAMEDirectories::resolve_path(mame_directory_path, rom_path))
+			.collect();
+
+		if rom_paths.len() == 0 {
+			rom_paths.push(MAMEDirectories::resolve_path(mame_directory_path, "roms"));
+		}
+
+		MAMEDirectories {
+			rom_paths: rom_paths,
+			nvram_directory: MAMEDirectories::resolve_path(mame_directory_path, &get_option("nvram_directory", "nvram")),
+			diff_directory: MAMEDirectories::resolve_path(mame_directory_path, &get_option("diff_directory", "diff"))
+		}
+	}
+
+	// MAME runs from its own directory so that's where it reads mame.ini from.
+	fn get_showconfig(mame_executable_path: &String, mame_directory_path: &String) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+		let mut command = Command::new(mame_executable_path);
+
+		command.current_dir(mame_directory_path);
+		command.arg("-showconfig");
+		command.stderr(Stdio::null());
+
+		#[cfg(target_os = "windows")]
+		command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+		let mame_showconfig = command.output()?;
+
+		if !mame_showconfig.status.success() {
+			return Err("MAME couldn't show its config.".into());
+		}
+
+		let mame_options = MAMEDirectories::parse_ini(std::str::from_utf8(&mame_showconfig.stdout)?);
+
+		if !mame_options.contains_key("rompath") {
+			return Err("MAME didn't give us a rompath.".into());
+		}
+
+		Ok(mame_options)
+	}
+
+	// mame.ini and `-showconfig` are both "name value" lines with # comments. Values with spaces are quoted.
+	fn parse_ini(ini_text: &str) -> HashMap<String, String> {
+		let mut mame_options = HashMap::new();
+
+		for line in ini_text.lines() {
+			let line = line.trim();
+
+			if line == "" || line.starts_with("#") || line.starts_with("<") {
+				continue;
+			}
+
+			match line.split_once(char::is_whitespace) {
+				Some((option_name, value)) => {
+					let value = value.trim();
+					let value = value.strip_prefix("\"").and_then(|value| value.strip_suffix("\"")).unwrap_or(value);
+
+					mame_options.insert(option_name.to_string(), value.to_string());
+				},
+				_ => {
+					mame_options.insert(line.to_string(), "".into());
+				}
+			};
+		}
+
+		mame_options
+	}
+
+	// MAME expands environment variables like $HOME in paths. Relative paths are from MAME's directory.
+	fn resolve_path(mame_directory_path: &String, path: &str) -> String {
+		let env_re: Regex = Regex::new(r"\$(\{(?<braced_name>\w+)\}|(?<name>\w+))").unwrap();
+
+		let expanded_path = env_re.replace_all(path.trim(), |matches: &regex::Captures| {
+			let env_name = match matches.name("braced_name") {
+				Some(braced_name) => braced_name.as_str(),
+				_ => &matches["name"]
+			};
+
+			env::var(env_name).unwrap_or("".into())
+		}).to_string();
+
+		let expanded_path = match expanded_path.trim_end_matches(|c| c == '/' || c == '\\') {
+			"" => expanded_path.clone(),
+			trimmed_path => trimmed_path.to_string()
+		};
+
+		if expanded_path == "" || Path::new(&expanded_path).is_absolute() || mame_directory_path == "" {
+			expanded_path
+		} else {
+			mame_directory_path.clone() + "/" + &expanded_path
+		}
+	}
+}
+
+////
+//
+// Options MAME supports
+//
+////
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MAMECapabilities {
+	// Option names without the dash. Empty if MAME couldn't tell us, which is treated as MAME supporting everything.
+	pub options: HashSet<String>
+}
+
+impl MAMECapabilities {
+	// Asks MAME for its usage. Its config is used if that doesn't list any options.
+	pub fn probe(mame_executable_path: &String) -> MAMECapabilities {
+		let mame_directory_path = LauncherConfig::get_parent(mame_executable_path.clone()).unwrap_or("".into());
+
+		let stamp = match MAMEExecutableStamp::new(mame_executable_path) {
+			Ok(stamp) => stamp,
+			Err(_e) => return MAMECapabilities::default()
+		};
+
+		let mut probed_mame_capabilities = match PROBED_MAME_CAPABILITIES.lock() {
+			Ok(probed_mame_capabilities) => probed_mame_capabilities,
+			Err(_e) => return MAMECapabilities::load(mame_executable_path, &mame_directory_path)
+		};
+
+		match probed_mame_capabilities.as_ref() {
+			Some((probed_stamp, mame_capabilities)) => {
+				if *probed_stamp == stamp {
+					return mame_capabilities.clone();
+				}
+			},
+			_ => { }
+		};
+
+		let mame_capabilities = MAMECapabilities::load(mame_executable_path, &mame_directory_path);
+
+		*probed_mame_capabilities = Some((stamp, mame_capabilities.clone()));
+
+		mame_capabilities
+	}
+
+	pub fn is_probed(&self) -> bool {
+		self.options.len() > 0
+	}
+
+	// Boolean options can be turned off with a "no" prefix so -nomaximize is supported if -maximize is.
+	pub fn supports(&self, option_name: &str) -> bool {
+		if !self.is_probed() {
+			return true;
+		}
+
+		let option_name = option_name.trim_start_matches('-').to_lowercase();
+
+		if self.options.contains(&option_name) {
+			return true;
+		}
+
+		match option_name.strip_prefix("no") {
+			Some(option_name) => self.options.contains(option_name),
+			_ => false
+		}
+	}
+
+	// Slot and media options depend on the box so they're taken from its machine rather than MAME's usage.
+	pub fn get_unsupported_options(&self, machine: &MAMEMachineNode, mame_arguments: &Vec<String>) -> Vec<String> {
+		let mut machine_options: HashSet<String> = HashSet::new();
+
+		for slot in machine.slot.clone().unwrap_or(vec![]).iter() {
+			match slot.name.clone() {
+				Some(slot_name) => {
+					machine_options.insert(slot_name.to_lowercase());
+				},
+				_ => { }
+			};
+		}
+
+		for device in machine.device.clone().unwrap_or(vec![]).iter() {
+			for instance in device.instance.clone().unwrap_or(vec![]).iter() {
+				for instance_name in [instance.name.clone(), instance.briefname.clone()] {
+					match instance_name {
+						Some(instance_name) => {
+							machine_options.insert(instance_name.to_lowercase());
+						},
+						_ => { }
+					};
+				}
+			}
+		}
+
+		let mut unsupported_options = vec![];
+
+		for mame_argument in mame_arguments.iter() {
+			// Values can't be checked. A lone dash isn't an option either.
+			if !mame_argument.starts_with("-") || mame_argument == "-" {
+				continue;
+			}
+
+			let option_name = mame_argument.trim_start_matches('-').to_lowercase();
+
+			if !self.supports(&option_name) && !machine_options.contains(&option_name) {
+				unsupported_options.push(mame_argument.clone());
+			}
+		}
+
+		unsupported_options
+	}
+
+	// Options the custom options set again after the launcher already set them. MAME uses the last one so the launcher's setting is lost.
+	pub fn get_conflicting_options(&self, mame_arguments: &Vec<String>, custom_arguments: &Vec<String>) -> Vec<String> {
+		let mut option_counts: HashMap<String, i32> = HashMap::new();
+
+		for mame_argument in mame_arguments.iter() {
+			match self.get_option_name(mame_argument) {
+				Some(option_name) => {
+					*option_counts.entry(option_name).or_insert(0) += 1;
+				},
+				_ => { }
+			};
+		}
+
+		// Whatever's left was set by the launcher.
+		for custom_argument in custom_arguments.iter() {
+			match self.get_option_name(custom_argument) {
+				Some(option_name) => {
+					*option_counts.entry(option_name).or_insert(0) -= 1;
+				},
+				_ => { }
+			};
+		}
+
+		let mut conflicting_options = vec![];
+
+		for custom_argument in custom_arguments.iter() {
+			match self.get_option_name(custom_argument) {
+				Some(option_name) => {
+					if option_counts.get(&option_name).cloned().unwrap_or(0) > 0 && !conflicting_options.contains(custom_argument) {
+						conflicting_options.push(custom_argument.clone());
+					}
+				},
+				_ => { }
+			};
+		}
+
+		conflicting_options
+	}
+
+	// -nodrc and -drc are the same option. Values don't have a name.
+	fn get_option_name(&self, mame_argument: &String) -> Option<String> {
+		if !mame_argument.starts_with("-") || mame_argument == "-" {
+			return None;
+		}
+
+		let option_name = mame_argument.trim_start_matches('-').to_lowercase();
+
+		match option_name.strip_prefix("no") {
+			Some(negated_option_name) if !self.options.contains(&option_name) && self.options.contains(negated_option_name) => {
+				Some(negated_option_name.to_string())
+			},
+			_ => {
+				Some(option_name)
+			}
+		}
+	}
+
+	fn load(mame_executable_path: &String, mame_directory_path: &String) -> MAMECapabilities {
+		let options = match MAMECapabilities::get_showusage(mame_executable_path, mame_directory_path) {
+			Ok(options) if options.len() > 0 => options,
+			_ => {
+				match MAMEDirectories::get_showconfig(mame_executable_path, mame_directory_path) {
+					Ok(mame_options) => mame_options.keys().map(|option_name| option_name.to_lowercase()).collect(),
+					_ => HashSet::new()
+				}
+			}
+		};
+
+		MAMECapabilities {
+			options: options
+		}
+	}
+
+	fn get_showusage(mame_executable_path: &String, mame_directory_path: &String) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+		let mut command = Command::new(mame_executable_path);
+
+		command.current_dir(mame_directory_path);
+		command.arg("-showusage");
+		command.stderr(Stdio::null());
+
+		#[cfg(target_os = "windows")]
+		command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+		// Some builds exit with an error after showing their usage so only the output is checked.
+		let mame_showusage = command.output()?;
+
+		Ok(MAMECapabilities::parse_usage(&String::from_utf8_lossy(&mame_showusage.stdout)))
+	}
+
+	// Options are listed like "  -readconfig, -rc    enable loading of configuration files".
+	fn parse_usage(usage_text: &str) -> HashSet<String> {
+		let mut options = HashSet::new();
+
+		for line in usage_text.lines() {
+			for option_name in line.trim().split_whitespace() {
+				if !option_name.starts_with("-") {
+					break;
+				}
+
+				options.insert(option_name.trim_start_matches('-').trim_end_matches(',').to_lowercase());
+
+				// Only aliases are separated by commas.
+				if !option_name.ends_with(",") {
+					break;
+				}
+			}
+		}
+
+		options.remove("");
+
+		options
+	}
+}
+
+////
+//
+// Custom MAME options
+//
+////
+
+// Splits custom options like a shell would. Quotes keep spaces in an argument and a backslash escapes a quote or space.
+// Other backslashes are kept so Windows paths can be typed as they are. A backslash before the quote that ends an argument is kept too.
+pub fn split_custom_options(custom_options: &str) -> Result<Vec<String>, LaunchError> {
+	let mut arguments: Vec<String> = vec![];
+	let mut argument = "".to_string();
+	// An empty pair of quotes is still an argument.
+	let mut in_argument = false;
+	let mut quote: Option<char> = None;
+
+	let mut characters = custom_options.chars().peekable();
+	loop {
+		let character = match characters.next() {
+			Some(character) => character,
+			_ => break
+		};
+
+		match quote {
+			Some('\'') => {
+				if character == '\'' {
+					quote = None;
+				} else {
+					argument.push(character);
+				}
+			},
+			Some(_) => {
+				if character == '"' {
+					quote = None;
+				} else if character == '\\' && characters.peek() == Some(&'"') && !characters.clone().nth(1).map(|next| next.is_whitespace()).unwrap_or(true) {
+					// A quote that ends the argument isn't escaped so paths like "C:\My Roms\" keep their backslash.
+					argument.push('"');
+					characters.next();
+				} else {
+					argument.push(character);
+				}
+			},
+			_ => {
+				if character.is_whitespace() {
+					if in_argument {
+						arguments.push(argument.clone());
+						argument.clear();
+						in_argument = false;
+					}
+				} else if character == '\'' || character == '"' {
+					quote = Some(character);
+					in_argument = true;
+				} else if character == '\\' && characters.peek().map(|next| next.is_whitespace() || *next == '\'' || *next == '"').unwrap_or(false) {
+					argument.push(characters.next().unwrap_or(' '));
+					in_argument = true;
+				} else {
+					argument.push(character);
+					in_argument = true;
+				}
+			}
+		};
+	}
+
+	match quote {
+		Some(quote) => {
+			return Err(LaunchError::BadCustomOptions { message: "there's a ".to_owned() + &quote.to_string() + " without an end quote" });
+		},
+		_ => { }
+	};
+
+	if in_argument {
+		arguments.push(argument);
+	}
+
+	Ok(arguments)
+}
+
+// Quoted so it splits back into the same arguments with split_custom_options.
+pub fn get_command_line(program: &String, arguments: &Vec<String>) -> String {
+	let quote_argument = |argument: &String| -> String {
+		if argument != "" && !argument.chars().any(|character| character.is_whitespace() || character == '\'' || character == '"') {
+			argument.clone()
+		} else if !argument.contains('"') {
+			"\"".to_owned() + argument + "\""
+		} else if !argument.contains('\'') {
+			"'".to_owned() + argument + "'"
+		} else {
+			// Quotes are put in single quotes between the double quoted parts.
+			"\"".to_owned() + &argument.replace("\"", "\"'\"'\"") + "\""
+		}
+	};
+
+	let mut command_line = quote_argument(program);
+
+	for argument in arguments.iter() {
+		command_line += " ";
+		command_line += &quote_argument(argument);
+	}
+
+	command_line
+}
+
 ////
 //
 // Main TOML config
@@ -196,7 +815,17 @@ pub struct Paths {
     pub rommy_path: Option<String>,
     pub last_opened_exe_path: Option<String>,
     pub last_opened_rom_path: Option<String>,
-    pub last_opened_img_path: Option<String>
+    pub last_opened_img_path: Option<String>,
+    pub mame_installations: Option<Vec<MAMEInstallation>>
+}
+
+// A named MAME executable the launcher can switch to. The box selected with it is remembered so switching back picks it again.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MAMEInstallation {
+    pub name: String,
+    pub mame_path: String,
+    pub selected_box: Option<String>
 }
 
 impl Paths {
@@ -250,7 +879,7 @@ impl LauncherConfig {
 
 		if Path::new(&mame_path).exists() {
 			mame_config =
-			LauncherConfig::get_mame_config(&mame_path)
+			LauncherConfig::get_cached_mame_config(&mame_path)
 				.unwrap_or(LauncherConfig::default_mame_config());
 		} else {
 			mame_config = LauncherConfig::default_mame_config();
@@ -279,6 +908,27 @@ impl LauncherConfig {
 		Ok(())
 	}
 
+	// Appends an error to the log next to the config so failures can be looked at after the message is gone.
+	pub fn log_error(context: &str, e: &dyn std::error::Error) -> Result<(), Box<dyn std::error::Error>> {
+		let executable_dir = 
+			LauncherConfig::get_parent_from_pathbuf(env::current_exe()?)
+			.unwrap_or("".into());
+
+		let log_file_path = executable_dir + "/" + LOG_FILE_NAME;
+
+		let timestamp = 
+			SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|duration| duration.as_secs())
+			.unwrap_or(0);
+
+		let mut log_file = fs::OpenOptions::new().append(true).create(true).open(log_file_path)?;
+
+		writeln!(log_file, "[{}] {}: {}", timestamp, context, e)?;
+
+		Ok(())
+	}
+
 	pub fn get_parent_from_pathbuf(path: std::path::PathBuf) -> Result<String, Box<dyn std::error::Error>> {
 		let executable_parent =
 			path
@@ -306,6 +956,288 @@ impl LauncherConfig {
 		}
 	}
 
+	// Uses the config already loaded or the cache file if MAME hasn't changed. MAME is only run if neither can be used.
+	pub fn get_cached_mame_config(mame_path: &String) -> Result<MAMEDocument, Box<dyn std::error::Error>> {
+		let stamp = MAMEExecutableStamp::new(mame_path)?;
+
+		// Held while MAME runs so two reloads don't both run it.
+		let mut loaded_mame_config = LOADED_MAME_CONFIG.lock().map_err(|_| "Couldn't lock the loaded MAME config.")?;
+
+		match loaded_mame_config.as_ref() {
+			Some(loaded) => {
+				if loaded.stamp == stamp {
+					return Ok(loaded.mame.clone());
+				}
+			},
+			_ => { }
+		};
+
+		let mut mame_cache = match LauncherConfig::get_mame_config_cache() {
+			Ok(mame_cache) if mame_cache.version == MAME_CACHE_VERSION => mame_cache,
+			_ => MAMEConfigCache {
+				version: MAME_CACHE_VERSION,
+				entry: vec![]
+			}
+		};
+
+		let cached_entry = mame_cache.find_entry(&stamp).cloned();
+
+		let entry = match cached_entry {
+			Some(entry) => entry,
+			_ => {
+				let mame_config = LauncherConfig::get_webtv_mame_config(mame_path)?;
+
+				let entry = MAMEConfigCacheEntry {
+					stamp: stamp.clone(),
+					mame: mame_config
+				};
+
+				mame_cache.set_entry(entry.clone());
+
+				// Not having the cache only makes things slower so a failed write isn't an error.
+				let _ = LauncherConfig::save_mame_config_cache(&mame_cache);
+
+				entry
+			}
+		};
+
+		let mame_config = entry.mame.clone();
+
+		*loaded_mame_config = Some(entry);
+
+		Ok(mame_config)
+	}
+
+	// The build MAME reported the last time it was listed. MAME isn't run so this is empty for executables we haven't loaded.
+	pub fn get_cached_mame_build(mame_path: &String) -> Option<String> {
+		let stamp = MAMEExecutableStamp::new(mame_path).ok()?;
+
+		let mame_cache = LauncherConfig::get_mame_config_cache().ok()?;
+
+		if mame_cache.version != MAME_CACHE_VERSION {
+			return None;
+		}
+
+		let entry = mame_cache.find_entry(&stamp)?;
+
+		let build = entry.mame.build.clone().unwrap_or("".into());
+
+		if build == "" {
+			None
+		} else if entry.mame.debug.clone().unwrap_or("".into()) == "yes" {
+			Some(build + " (debug)")
+		} else {
+			Some(build)
+		}
+	}
+
+	// MAME is asked for its version if it hasn't been listed yet. That's much quicker than listing it.
+	pub fn get_mame_build(mame_path: &String) -> Option<String> {
+		match LauncherConfig::get_cached_mame_build(mame_path) {
+			Some(build) => return Some(build),
+			_ => { }
+		};
+
+		let stamp = MAMEExecutableStamp::new(mame_path).ok()?;
+
+		let mut probed_mame_builds = PROBED_MAME_BUILDS.lock().ok()?;
+
+		let build = match probed_mame_builds.iter().find(|(probed_stamp, _)| *probed_stamp == stamp) {
+			Some((_, build)) => build.clone(),
+			_ => {
+				let build = LauncherConfig::get_mame_version(mame_path).unwrap_or("".into());
+
+				probed_mame_builds.push((stamp, build.clone()));
+
+				build
+			}
+		};
+
+		if build == "" {
+			None
+		} else {
+			Some(build)
+		}
+	}
+
+	// `-version` gives the build the same way `-listxml` does.
+	fn get_mame_version(mame_path: &String) -> Result<String, Box<dyn std::error::Error>> {
+		let mut command = Command::new(mame_path);
+
+		command.arg("-version");
+		command.stderr(Stdio::null());
+
+		#[cfg(target_os = "windows")]
+		command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+		let mame_version = command.output()?;
+
+		if !mame_version.status.success() {
+			return Err("MAME couldn't give its version.".into());
+		}
+
+		Ok(String::from_utf8_lossy(&mame_version.stdout).lines().next().unwrap_or("").trim().to_string())
+	}
+
+	fn get_mame_config_cache_path() -> Result<String, Box<dyn std::error::Error>> {
+		let executable_dir = 
+			LauncherConfig::get_parent_from_pathbuf(env::current_exe()?)
+			.unwrap_or("".into());
+
+		Ok(executable_dir + "/" + MAME_CACHE_FILE_NAME)
+	}
+
+	fn get_mame_config_cache() -> Result<MAMEConfigCache, Box<dyn std::error::Error>> {
+		MAMEConfigCache::read(&LauncherConfig::get_mame_config_cache_path()?)
+	}
+
+	fn save_mame_config_cache(mame_cache: &MAMEConfigCache) -> Result<(), Box<dyn std::error::Error>> {
+		mame_cache.write(&LauncherConfig::get_mame_config_cache_path()?)
+	}
+
+	// Only the WebTV machines are kept. If MAME's XML can't be streamed then it's all read in then filtered.
+	pub fn get_webtv_mame_config(mame_path: &String) -> Result<MAMEDocument, Box<dyn std::error::Error>> {
+		match LauncherConfig::stream_webtv_mame_config(mame_path) {
+			Ok(mame_config) => Ok(mame_config),
+			Err(_e) => {
+				let mut mame_config = LauncherConfig::get_mame_config(mame_path)?;
+
+				mame_config.machine = Some(
+					mame_config.machine
+					.unwrap_or(vec![])
+					.into_iter()
+					.filter(|machine| crate::boxprofile::is_webtv_machine(machine))
+					.collect()
+				);
+
+				Ok(mame_config)
+			}
+		}
+	}
+
+	// Reads `mame -listxml` as it comes out of MAME. Each machine is held as XML until we know if it's a WebTV box.
+	// Only WebTV boxes are deserialized so we don't end up with every machine MAME has in memory.
+	fn stream_webtv_mame_config(mame_path: &String) -> Result<MAMEDocument, Box<dyn std::error::Error>> {
+		let mut command = Command::new(mame_path);
+
+		command.arg("-listxml");
+		command.stdout(Stdio::piped());
+		command.stderr(Stdio::null());
+
+		#[cfg(target_os = "windows")]
+		command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+		let mut mame_process = command.spawn()?;
+
+		let mame_stdout = mame_process.stdout.take().ok_or("Couldn't read MAME's output.")?;
+
+		let result = LauncherConfig::read_webtv_machines(BufReader::new(mame_stdout));
+
+		match result {
+			Ok(_) => {
+				let _ = mame_process.wait();
+			},
+			Err(_) => {
+				let _ = mame_process.kill();
+				let _ = mame_process.wait();
+			}
+		};
+
+		result
+	}
+
+	fn read_webtv_machines<R: std::io::BufRead>(mame_xml: R) -> Result<MAMEDocument, Box<dyn std::error::Error>> {
+		let mut reader = quick_xml::Reader::from_reader(mame_xml);
+
+		let mut mame_config = LauncherConfig::default_mame_config();
+		let mut machines: Vec<MAMEMachineNode> = vec![];
+		let mut found_mame = false;
+
+		let mut machine_writer: Option<quick_xml::Writer<Vec<u8>>> = None;
+		let mut machine_name = String::new();
+		let mut sourcefile = String::new();
+		let mut device_ref_names: Vec<String> = vec![];
+		let mut rom_regions: Vec<String> = vec![];
+
+		let mut buf = Vec::new();
+		loop {
+			let event = reader.read_event_into(&mut buf)?;
+
+			match &event {
+				Event::Start(e) if e.name().as_ref() == b"mame" => {
+					found_mame = true;
+
+					mame_config.build = LauncherConfig::get_xml_attribute(e, "build");
+					mame_config.debug = LauncherConfig::get_xml_attribute(e, "debug");
+					mame_config.mameconfig = LauncherConfig::get_xml_attribute(e, "mameconfig");
+				},
+				Event::Start(e) if e.name().as_ref() == b"machine" => {
+					machine_writer = Some(quick_xml::Writer::new(Vec::new()));
+					machine_name = LauncherConfig::get_xml_attribute(e, "name").unwrap_or("".into());
+					sourcefile = LauncherConfig::get_xml_attribute(e, "sourcefile").unwrap_or("".into());
+					device_ref_names.clear();
+					rom_regions.clear();
+				},
+				Event::Start(e) | Event::Empty(e) if machine_writer.is_some() => {
+					if e.name().as_ref() == b"device_ref" {
+						device_ref_names.push(LauncherConfig::get_xml_attribute(e, "name").unwrap_or("".into()));
+					} else if e.name().as_ref() == b"rom" {
+						rom_regions.push(LauncherConfig::get_xml_attribute(e, "region").unwrap_or("".into()));
+					}
+				},
+				Event::Eof => break,
+				_ => { }
+			};
+
+			let machine_ended = match &event {
+				Event::End(e) => e.name().as_ref() == b"machine",
+				_ => false
+			};
+
+			match machine_writer.as_mut() {
+				Some(writer) => {
+					writer.write_event(event)?;
+				},
+				_ => { }
+			};
+
+			if machine_ended {
+				match machine_writer.take() {
+					Some(writer) => {
+						if crate::boxprofile::is_webtv_driver(&machine_name, &sourcefile, &device_ref_names, &rom_regions) {
+							let machine_xml = String::from_utf8(writer.into_inner())?;
+
+							machines.push(quick_xml::de::from_str(&machine_xml)?);
+						}
+					},
+					_ => { }
+				};
+			}
+
+			buf.clear();
+		}
+
+		if !found_mame {
+			return Err("MAME didn't give us a machine list.".into());
+		}
+
+		mame_config.machine = Some(machines);
+
+		Ok(mame_config)
+	}
+
+	fn get_xml_attribute(element: &BytesStart, attribute_name: &str) -> Option<String> {
+		match element.try_get_attribute(attribute_name) {
+			Ok(Some(attribute)) => {
+				match attribute.unescape_value() {
+					Ok(value) => Some(value.to_string()),
+					_ => None
+				}
+			},
+			_ => None
+		}
+	}
+
 	pub fn get_mame_config(mame_path: &String) -> Result<MAMEDocument, Box<dyn std::error::Error>> {
 		let mut command = Command::new(mame_path);
 
@@ -333,7 +1265,8 @@ impl LauncherConfig {
 				rommy_path: Some("".into()),
 				last_opened_exe_path: Some("".into()),
 				last_opened_rom_path: Some("".into()),
-				last_opened_img_path: Some("".into())
+				last_opened_img_path: Some("".into()),
+				mame_installations: None
 			},
 			mame_options: MAMEOptions {
 				selected_box: Some("wtv1sony".into()),
@@ -371,3 +1304,349 @@ impl LauncherConfig {
 		Ok(toml_config)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::wtv::buildio::get_test_file_path;
+
+	const TEST_MAME_XML: &'static str = r#"<?xml version="1.0"?>
+<mame build="0.270 (mame0270)" debug="no" mameconfig="10">
+	<machine name="wtv2lc2" sourcefile="webtv/wtv2.cpp">
+		<description>WebTV LC2</description>
+		<year>1999</year>
+		<manufacturer>Sony</manufacturer>
+		<biosset name="bf0" description="Standard bootrom"/>
+		<rom name="bootrom.o" bios="bf0" size="2097152" crc="0123abcd" sha1="da39a3ee5e6b4b0d3255bfef95601890afd80709" region="bootrom" offset="0"/>
+		<rom name="serial_id" size="8" status="nodump" region="serial_id" offset="0"/>
+		<device_ref name="solo_asic"/>
+		<chip type="cpu" tag="maincpu" name="R4640" clock="167000000"/>
+		<disk name="hdimg" region="ata:0:hdd:image" index="0" writable="yes"/>
+		<device type="harddisk" tag="ata:0:hdd:image">
+			<instance name="harddisk" briefname="hard"/>
+			<extension name="chd"/>
+		</device>
+		<slot name="modem">
+			<slotoption name="null" devname="null_modem"/>
+		</slot>
+		<feature type="graphics" status="imperfect"/>
+	</machine>
+</mame>
+"#;
+
+	fn get_test_cache_entry(mame_path: &String) -> MAMEConfigCacheEntry {
+		MAMEConfigCacheEntry {
+			stamp: MAMEExecutableStamp::new(mame_path).unwrap(),
+			mame: quick_xml::de::from_str(TEST_MAME_XML).unwrap()
+		}
+	}
+
+	#[test]
+	fn mame_config_cache_round_trips() {
+		let mame_path = get_test_file_path("cache_round_trip_mame");
+		let cache_file_path = get_test_file_path("cache_round_trip.toml");
+		std::fs::write(&mame_path, "mame").unwrap();
+
+		let mut mame_cache = MAMEConfigCache { version: MAME_CACHE_VERSION, entry: vec![] };
+		mame_cache.set_entry(get_test_cache_entry(&mame_path));
+		mame_cache.write(&cache_file_path).unwrap();
+
+		// Attributes keep the names quick-xml gave them.
+		let toml_cache_text = std::fs::read_to_string(&cache_file_path).unwrap();
+		assert!(toml_cache_text.contains(r#""@name" = "wtv2lc2""#));
+		assert!(toml_cache_text.contains(r#""@sha1" = "da39a3ee5e6b4b0d3255bfef95601890afd80709""#));
+
+		let read_cache = MAMEConfigCache::read(&cache_file_path).unwrap();
+		assert_eq!(read_cache.version, MAME_CACHE_VERSION);
+
+		let entry = read_cache.find_entry(&MAMEExecutableStamp::new(&mame_path).unwrap()).unwrap();
+		assert_eq!(format!("{:?}", entry.mame), format!("{:?}", mame_cache.entry[0].mame));
+		assert_eq!(entry.mame.build, Some("0.270 (mame0270)".into()));
+		assert_eq!(entry.mame.debug, Some("no".into()));
+
+		let machine = &entry.mame.machine.as_ref().unwrap()[0];
+		assert_eq!(machine.name, Some("wtv2lc2".into()));
+		assert_eq!(machine.sourcefile, Some("webtv/wtv2.cpp".into()));
+		assert_eq!(machine.description, Some("WebTV LC2".into()));
+
+		let roms = machine.rom.as_ref().unwrap();
+		assert_eq!(roms.len(), 2);
+		assert_eq!(roms[0].size, Some(0x200000));
+		assert_eq!(roms[0].crc, Some("0123abcd".into()));
+		assert_eq!(roms[1].status, Some("nodump".into()));
+		assert_eq!(roms[1].region, Some("serial_id".into()));
+
+		assert_eq!(machine.device_ref.as_ref().unwrap()[0].name, Some("solo_asic".into()));
+		assert_eq!(machine.chip.as_ref().unwrap()[0].clock, Some(167000000));
+		assert_eq!(machine.disk.as_ref().unwrap()[0].writable, Some("yes".into()));
+		assert_eq!(machine.device.as_ref().unwrap()[0].instance.as_ref().unwrap()[0].briefname, Some("hard".into()));
+		assert_eq!(machine.slot.as_ref().unwrap()[0].slotoption.as_ref().unwrap()[0].devname, Some("null_modem".into()));
+
+		let _ = std::fs::remove_file(&mame_path);
+		let _ = std::fs::remove_file(&cache_file_path);
+	}
+
+	#[test]
+	fn changed_mame_executable_misses_the_cache() {
+		let mame_path = get_test_file_path("cache_stamp_mame");
+		let other_mame_path = get_test_file_path("cache_stamp_other_mame");
+		std::fs::write(&mame_path, "mame").unwrap();
+		std::fs::write(&other_mame_path, "other mame").unwrap();
+
+		let mut mame_cache = MAMEConfigCache { version: MAME_CACHE_VERSION, entry: vec![] };
+		mame_cache.set_entry(get_test_cache_entry(&mame_path));
+		mame_cache.set_entry(get_test_cache_entry(&other_mame_path));
+
+		let old_stamp = MAMEExecutableStamp::new(&mame_path).unwrap();
+		assert!(mame_cache.find_entry(&old_stamp).is_some());
+
+		std::fs::write(&mame_path, "updated mame").unwrap();
+
+		let new_stamp = MAMEExecutableStamp::new(&mame_path).unwrap();
+		assert!(new_stamp != old_stamp);
+		assert!(mame_cache.find_entry(&new_stamp).is_none());
+
+		// The new entry replaces the old one for the same executable.
+		mame_cache.set_entry(get_test_cache_entry(&mame_path));
+		assert_eq!(mame_cache.entry.len(), 2);
+		assert!(mame_cache.find_entry(&old_stamp).is_none());
+		assert!(mame_cache.find_entry(&new_stamp).is_some());
+		assert!(mame_cache.find_entry(&MAMEExecutableStamp::new(&other_mame_path).unwrap()).is_some());
+
+		let _ = std::fs::remove_file(&mame_path);
+		let _ = std::fs::remove_file(&other_mame_path);
+	}
+
+	#[test]
+	fn only_webtv_machines_are_read_from_the_machine_list() {
+		let mame_xml = r#"<?xml version="1.0"?>
+<!DOCTYPE mame [
+<!ELEMENT mame (machine+)>
+]>
+<mame build="0.270 (mame0270)" debug="yes" mameconfig="10">
+	<machine name="pacman" sourcefile="pacman/pacman.cpp">
+		<description>Pac-Man (Midway)</description>
+		<rom name="pacman.6e" size="4096" crc="c1e6ab10" sha1="e87e059c5be45753f7e9f33dff851f16d6751181" region="maincpu" offset="0"/>
+		<device_ref name="z80"/>
+	</machine>
+	<machine name="wtv1sony" sourcefile="webtv/wtv1.cpp">
+		<description>Sony INT-W100 WebTV Internet Terminal</description>
+		<year>1996</year>
+		<rom name="bootrom.o" size="2097152" crc="0123abcd" sha1="da39a3ee5e6b4b0d3255bfef95601890afd80709" region="bootrom" offset="0"/>
+		<rom name="serial_id" size="8" status="nodump" region="serial_id" offset="0"/>
+		<device_ref name="r4640be"/>
+		<device_ref name="spot_asic"/>
+	</machine>
+	<machine name="r4640be" sourcefile="devices/cpu/mips/mips3.cpp" isdevice="yes" runnable="no">
+		<description>IDT R4640 (big)</description>
+		<device_ref name="r4640be"/>
+	</machine>
+</mame>
+"#;
+
+		let mame_config = LauncherConfig::read_webtv_machines(mame_xml.as_bytes()).unwrap();
+		assert_eq!(mame_config.build, Some("0.270 (mame0270)".into()));
+		assert_eq!(mame_config.debug, Some("yes".into()));
+
+		let machines = mame_config.machine.unwrap();
+		assert_eq!(machines.len(), 1);
+
+		let machine = &machines[0];
+		assert_eq!(machine.name, Some("wtv1sony".into()));
+		assert_eq!(machine.sourcefile, Some("webtv/wtv1.cpp".into()));
+		assert_eq!(machine.description, Some("Sony INT-W100 WebTV Internet Terminal".into()));
+		assert_eq!(machine.year, Some("1996".into()));
+
+		let roms = machine.rom.as_ref().unwrap();
+		assert_eq!(roms.len(), 2);
+		assert_eq!(roms[0].name, Some("bootrom.o".into()));
+		assert_eq!(roms[0].size, Some(0x200000));
+		assert_eq!(roms[0].crc, Some("0123abcd".into()));
+		assert_eq!(roms[0].sha1, Some("da39a3ee5e6b4b0d3255bfef95601890afd80709".into()));
+		assert_eq!(roms[0].region, Some("bootrom".into()));
+		assert_eq!(roms[1].status, Some("nodump".into()));
+		assert_eq!(roms[1].region, Some("serial_id".into()));
+
+		let device_ref_names: Vec<String> = machine.device_ref.as_ref().unwrap().iter().map(|device_ref| device_ref.name.clone().unwrap()).collect();
+		assert_eq!(device_ref_names, vec!["r4640be", "spot_asic"]);
+	}
+
+	#[test]
+	fn machine_list_needs_a_mame_element() {
+		assert!(LauncherConfig::read_webtv_machines("<notmame></notmame>".as_bytes()).is_err());
+		assert!(LauncherConfig::read_webtv_machines("".as_bytes()).is_err());
+	}
+
+	#[test]
+	fn ini_lines_are_split_into_options() {
+		let mame_options = MAMEDirectories::parse_ini(r#"
+#
+# CORE SEARCH PATH OPTIONS
+#
+<UNADORNED0>              wtv2lc2
+rompath                   roms;$HOME/wtv roms;/opt/mame/roms
+nvram_directory           "my nvram"
+  diff_directory	diff
+cfg_directory
+"#);
+
+		let expected_options = [
+			("rompath", "roms;$HOME/wtv roms;/opt/mame/roms"),
+			("nvram_directory", "my nvram"),
+			("diff_directory", "diff"),
+			("cfg_directory", "")
+		];
+
+		for (option_name, value) in expected_options {
+			assert_eq!(mame_options.get(option_name).cloned(), Some(value.to_string()), "{}", option_name);
+		}
+
+		assert_eq!(mame_options.len(), expected_options.len());
+	}
+
+	#[test]
+	fn paths_are_expanded_and_resolved_from_the_mame_directory() {
+		env::set_var("MAME_LAUNCHER_TEST_DIR", "/srv/mame");
+		env::remove_var("MAME_LAUNCHER_TEST_UNSET");
+
+		let paths = [
+			("/opt/mame", "roms", "/opt/mame/roms"),
+			("/opt/mame", " roms/ ", "/opt/mame/roms"),
+			("/opt/mame", "/var/roms", "/var/roms"),
+			("/opt/mame", "$MAME_LAUNCHER_TEST_DIR/roms", "/srv/mame/roms"),
+			("/opt/mame", "${MAME_LAUNCHER_TEST_DIR}/roms/", "/srv/mame/roms"),
+			("/opt/mame", "${MAME_LAUNCHER_TEST_DIR}roms", "/srv/mameroms"),
+			("/opt/mame", "$MAME_LAUNCHER_TEST_UNSET/roms", "/roms"),
+			("/opt/mame", "/", "/"),
+			("", "roms", "roms")
+		];
+
+		for (mame_directory_path, path, expected_path) in paths {
+			assert_eq!(MAMEDirectories::resolve_path(&mame_directory_path.to_string(), path), expected_path, "{}", path);
+		}
+	}
+
+	#[test]
+	fn rompath_is_split_on_semicolons() {
+		env::set_var("MAME_LAUNCHER_TEST_DIR", "/srv/mame");
+
+		let rompaths = [
+			("roms", vec!["/opt/mame/roms"]),
+			("roms; $MAME_LAUNCHER_TEST_DIR/wtv ;/var/roms;", vec!["/opt/mame/roms", "/srv/mame/wtv", "/var/roms"]),
+			(";;", vec!["/opt/mame/roms"]),
+			("", vec!["/opt/mame/roms"])
+		];
+
+		for (rompath, expected_rom_paths) in rompaths {
+			let mame_options = HashMap::from([("rompath".to_string(), rompath.to_string())]);
+
+			assert_eq!(MAMEDirectories::from_options(&"/opt/mame".into(), &mame_options).rom_paths, expected_rom_paths, "{}", rompath);
+		}
+
+		let mame_directories = MAMEDirectories::from_options(&"/opt/mame".into(), &HashMap::new());
+		assert_eq!(mame_directories.nvram_directory, "/opt/mame/nvram");
+		assert_eq!(mame_directories.diff_directory, "/opt/mame/diff");
+	}
+
+	fn get_test_capabilities() -> MAMECapabilities {
+		MAMECapabilities {
+			options: MAMECapabilities::parse_usage(r#"
+MAME v0.270 (mame0270)
+Copyright Nicola Salmoria and the MAME team
+
+Usage:  mame [machine] [media] [software] [options]
+
+Options:
+
+#
+# CORE CONFIGURATION OPTIONS
+#
+  -readconfig, -rc            enable loading of configuration files
+  -rompath, -rp, -biospath, -bp   path to ROM sets and hard disk images
+  -bios                       select the system BIOS to use
+  -window, -w                 enable window mode; otherwise, full screen mode is assumed
+  -maximize, -max             default to maximized windows
+  -debug, -d                  enable/disable debugger
+  -plugin                     list of plugins to enable
+  -noplugin                   list of plugins to disable
+"#)
+		}
+	}
+
+	#[test]
+	fn usage_lists_options_and_their_aliases() {
+		let mame_capabilities = get_test_capabilities();
+
+		let mut options: Vec<&str> = mame_capabilities.options.iter().map(|option_name| option_name.as_str()).collect();
+		options.sort();
+		assert_eq!(options, vec![
+			"bios", "biospath", "bp", "d", "debug", "max", "maximize", "noplugin", "plugin", "rc", "readconfig", "rompath", "rp", "w", "window"
+		]);
+
+		let supported_options = [
+			("-window", true),
+			("-WINDOW", true),
+			("-nowindow", true),
+			("-nomax", true),
+			("-noreadconfig", true),
+			("-noplugin", true),
+			("-nodrc", false),
+			("-nothing", false),
+			("-listxml", false)
+		];
+
+		for (option_name, supported) in supported_options {
+			assert_eq!(mame_capabilities.supports(option_name), supported, "{}", option_name);
+		}
+
+		// Usage MAME couldn't give us means everything is allowed.
+		assert!(MAMECapabilities::default().supports("-nothing"));
+	}
+
+	#[test]
+	fn custom_options_that_replace_launcher_options_conflict() {
+		let mame_capabilities = get_test_capabilities();
+
+		let launcher_arguments = ["wtv2lc2", "-bios", "bf0", "-nomaximize", "-rompath", "/opt/mame/roms", "-plugin", "hiscore"];
+
+		let conflicts: [(&[&str], &[&str]); 5] = [
+			(&["-BIOS", "bfe"], &["-BIOS"]),
+			(&["-maximize"], &["-maximize"]),
+			(&["-debug", "-window"], &[]),
+			(&["-noplugin", "hiscore"], &[]),
+			(&["-bios", "bfe", "-bios", "bf0"], &["-bios"])
+		];
+
+		for (custom_arguments, expected_conflicts) in conflicts {
+			let custom_arguments: Vec<String> = custom_arguments.iter().map(|argument| argument.to_string()).collect();
+			let mut mame_arguments: Vec<String> = launcher_arguments.iter().map(|argument| argument.to_string()).collect();
+			mame_arguments.extend(custom_arguments.clone());
+
+			assert_eq!(mame_capabilities.get_conflicting_options(&mame_arguments, &custom_arguments), expected_conflicts, "{:?}", custom_arguments);
+		}
+	}
+
+	#[test]
+	fn rejects_unclosed_quote() {
+		assert!(split_custom_options("-hard \"/images/disk.chd").is_err());
+		assert!(split_custom_options("-snapname 'it").is_err());
+	}
+
+	#[test]
+	fn keeps_backslash_before_an_ending_quote() {
+		let custom_arguments = split_custom_options("-rompath \"C:\\My Roms\\\" -snapname \"say \\\"hi\\\"\"").unwrap();
+
+		assert_eq!(custom_arguments, vec!["-rompath", "C:\\My Roms\\", "-snapname", "say \"hi\""]);
+	}
+
+	#[test]
+	fn command_line_splits_back_into_the_arguments() {
+		let arguments: Vec<String> = vec!["C:\\My Roms\\".into(), "say \"hi\" now".into(), "it's \"here\"".into(), "".into()];
+
+		let command_line = get_command_line(&"mame".into(), &arguments);
+
+		let mut expected_arguments = vec!["mame".to_string()];
+		expected_arguments.extend(arguments);
+		assert_eq!(split_custom_options(&command_line).unwrap(), expected_arguments);
+	}
+}
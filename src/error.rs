@@ -0,0 +1,32 @@
+// By: Eric MacDonald (eMac)
+
+// Problems starting MAME. File and image errors are in wtv::error since the format code reports them.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchError {
+	MAMENotFound { mame_path: String },
+	PortUnavailable { endpoint: String },
+	BadCustomOptions { message: String }
+}
+
+impl std::fmt::Display for LaunchError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			LaunchError::MAMENotFound { mame_path } => {
+				if mame_path == "" {
+					write!(f, "No MAME executable! Please setup the path to your WebTV MAME executable.")
+				} else {
+					write!(f, "MAME executable '{}' not found! Please setup the correct path to your WebTV MAME executable.", mame_path)
+				}
+			},
+			LaunchError::PortUnavailable { endpoint } => {
+				write!(f, "Couldn't find an available port for {}. Close anything else listening on local ports or choose another endpoint.", endpoint)
+			},
+			LaunchError::BadCustomOptions { message } => {
+				write!(f, "Couldn't read the custom options: {}. Check that every quote is closed.", message)
+			}
+		}
+	}
+}
+
+impl std::error::Error for LaunchError { }
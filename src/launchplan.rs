@@ -0,0 +1,576 @@
+// By: Eric MacDonald (eMac)
+
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::Mutex;
+use regex::Regex;
+use crate::config::{get_command_line, split_custom_options, LauncherConfig, MAMECapabilities, MAMEOptions};
+use crate::boxprofile::BoxProfile;
+use crate::error::LaunchError;
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+pub const DEFAULT_DEBUG_ENDPOINT: &'static str = "Launcher Console";
+
+// The port last given to the debug console. Kept so a dry run or exported script has the port the next launch will use.
+static RESERVED_DEBUG_PORT: Mutex<Option<u16>> = Mutex::new(None);
+
+// Things the launcher needs to do while MAME is running.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchTask {
+	// The launcher's debug console listens on this port for the box's debug serial port.
+	DebugListener { port: u16 },
+	// The box has no debug serial port so console input is typed into MAME's window.
+	WindowInput
+}
+
+// The bitbanger slots MAME lists for the box, as "<slot>; <bitbanger>". Empty if the box doesn't have one.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct LaunchSlots {
+	pub modem_bitb_startpoint: String,
+	pub debug_bitb_startpoint: String
+}
+
+// Everything needed to start MAME. Nothing is started or checked on disk so the plan can be shown or saved instead.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchPlan {
+	pub program: String,
+	pub arguments: Vec<String>,
+	pub working_directory: String,
+	// These are also in arguments. Kept so they can be checked against what the launcher set.
+	pub custom_arguments: Vec<String>,
+	pub tasks: Vec<LaunchTask>,
+	// Problems that didn't stop MAME from being planned but should be shown.
+	pub warnings: Vec<LaunchError>
+}
+
+impl LaunchPlan {
+	// pick_port is only asked for a port when the debug console is local.
+	pub fn new<F: FnOnce() -> Option<u16>>(mame_path: &String, mame_options: &MAMEOptions, box_profile: &BoxProfile, mame_capabilities: &MAMECapabilities, slots: &LaunchSlots, pick_port: F) -> Result<LaunchPlan, LaunchError> {
+		let selected_box = mame_options.selected_box.clone().unwrap_or("".into());
+
+		let custom_arguments = split_custom_options(&mame_options.custom_options.clone().unwrap_or("".into()))?;
+
+		let mut plan = LaunchPlan {
+			program: mame_path.clone(),
+			arguments: vec![],
+			working_directory: LauncherConfig::get_parent(mame_path.clone()).unwrap_or("".into()),
+			custom_arguments: custom_arguments.clone(),
+			tasks: vec![],
+			warnings: vec![]
+		};
+
+		plan.arguments.push(selected_box.clone());
+
+		// Options are left out if this MAME doesn't have them so it doesn't refuse to start.
+		if mame_capabilities.supports("nomouse") {
+			plan.arguments.push("-nomouse".into());
+		}
+
+		if mame_options.verbose_mode.unwrap_or(false) && mame_capabilities.supports("verbose") {
+			plan.arguments.push("-verbose".into());
+		}
+
+		if mame_options.windowed_mode.unwrap_or(true) && mame_capabilities.supports("window") {
+			plan.arguments.push("-window".into());
+
+			if mame_capabilities.supports("nomaximize") {
+				plan.arguments.push("-nomaximize".into());
+			}
+		}
+
+		if mame_capabilities.supports("drc") {
+			if mame_options.use_drc.unwrap_or(true) {
+				plan.arguments.push("-drc".into());
+			} else {
+				plan.arguments.push("-nodrc".into());
+			}
+		}
+
+		if mame_options.debug_mode.unwrap_or(false) && mame_capabilities.supports("debug") {
+			plan.arguments.push("-debug".into());
+		}
+
+		if mame_options.skip_info_screen.unwrap_or(true) && mame_capabilities.supports("skip_gameinfo") {
+			plan.arguments.push("-skip_gameinfo".into());
+		}
+
+		if mame_options.disable_sound.unwrap_or(false) && mame_capabilities.supports("sound") {
+			plan.arguments.push("-sound".into());
+			plan.arguments.push("none".into());
+		}
+
+		let selected_bootrom = match mame_options.selected_bootroms.as_ref() {
+			Some(selected_bootroms) => selected_bootroms.get(&selected_box).cloned().unwrap_or("".into()),
+			_ => "".into()
+		};
+		if selected_bootrom != "" && selected_bootrom != "None" && selected_bootrom != "WinCE" {
+			plan.arguments.push("-bios".into());
+			plan.arguments.push(Regex::new(r"\.o$").unwrap().replace_all(&selected_bootrom, "").to_string());
+		}
+
+		// Box options go first so the user's custom options can override them.
+		plan.arguments.extend(box_profile.mame_options.clone());
+
+		plan.arguments.extend(custom_arguments);
+
+		if mame_options.console_input.unwrap_or(false) {
+			match LaunchPlan::get_slot(&slots.debug_bitb_startpoint) {
+				Some((slot_select, bitb_select)) => {
+					plan.arguments.push("-".to_owned() + &slot_select);
+					plan.arguments.push("null_modem".into());
+
+					let selected_debug_bitb_endpoint = mame_options.selected_debug_bitb_endpoint.clone().unwrap_or(DEFAULT_DEBUG_ENDPOINT.into());
+					let usable_debug_bitb_endpoint = match selected_debug_bitb_endpoint.as_str() {
+						DEFAULT_DEBUG_ENDPOINT | "127.0.0.1" | "localhost" | "local" => match pick_port() {
+							Some(debug_bitb_port) => {
+								plan.tasks.push(LaunchTask::DebugListener { port: debug_bitb_port });
+
+								"127.0.0.1:".to_owned() + &debug_bitb_port.to_string()
+							},
+							None => {
+								plan.warnings.push(LaunchError::PortUnavailable { endpoint: "the debug console".into() });
+
+								"".into()
+							}
+						},
+						_ => selected_debug_bitb_endpoint.clone()
+					};
+
+					if usable_debug_bitb_endpoint != "" {
+						plan.arguments.push("-".to_owned() + &bitb_select);
+						plan.arguments.push(LaunchPlan::get_bitb_endpoint(&usable_debug_bitb_endpoint));
+					}
+				},
+				_ => {
+					#[cfg(target_os = "windows")]
+					if mame_capabilities.supports("keyboardprovider") {
+						plan.arguments.push("-keyboardprovider".into());
+						plan.arguments.push("win32".into());
+					}
+					if mame_capabilities.supports("background_input") {
+						plan.arguments.push("-background_input".into());
+					}
+
+					plan.tasks.push(LaunchTask::WindowInput);
+				}
+			};
+		}
+
+		let selected_modem_bitb_endpoint = mame_options.selected_modem_bitb_endpoint.clone().unwrap_or("".into());
+		if selected_modem_bitb_endpoint != "" {
+			match LaunchPlan::get_slot(&slots.modem_bitb_startpoint) {
+				Some((slot_select, bitb_select)) => {
+					plan.arguments.push("-".to_owned() + &slot_select);
+					plan.arguments.push("null_modem".into());
+					plan.arguments.push("-".to_owned() + &bitb_select);
+					plan.arguments.push(LaunchPlan::get_bitb_endpoint(&selected_modem_bitb_endpoint));
+				},
+				_ => { }
+			};
+		}
+
+		let selected_hdimg_path = match mame_options.selected_hdimg_paths.as_ref() {
+			Some(selected_hdimg_paths) => selected_hdimg_paths.get(&selected_box).cloned().unwrap_or("".into()),
+			_ => "".into()
+		};
+		let selected_hdimg_enabled = match mame_options.selected_hdimg_enabled.as_ref() {
+			Some(selected_hdimg_enabled) => selected_hdimg_enabled.get(&selected_box).cloned().unwrap_or(false),
+			_ => false
+		};
+		if selected_hdimg_enabled && selected_hdimg_path != "" {
+			plan.arguments.push("-hard".into());
+			plan.arguments.push(selected_hdimg_path);
+		}
+
+		Ok(plan)
+	}
+
+	// The same port is given out until something else takes it, like the debug console of a MAME that's still running.
+	pub fn reserve_debug_port() -> Option<u16> {
+		let mut reserved_debug_port = match RESERVED_DEBUG_PORT.lock() {
+			Ok(reserved_debug_port) => reserved_debug_port,
+			Err(_e) => return portpicker::pick_unused_port()
+		};
+
+		match *reserved_debug_port {
+			Some(debug_port) => {
+				// Checked the way the debug console listens on it.
+				if TcpListener::bind(("127.0.0.1", debug_port)).is_ok() {
+					return Some(debug_port);
+				}
+			},
+			_ => { }
+		};
+
+		*reserved_debug_port = portpicker::pick_unused_port();
+
+		*reserved_debug_port
+	}
+
+	// The port the debug console listens on or 0 if it isn't used.
+	pub fn get_debug_bitb_port(&self) -> u16 {
+		for task in self.tasks.iter() {
+			match task {
+				LaunchTask::DebugListener { port } => return *port,
+				_ => { }
+			};
+		}
+
+		0
+	}
+
+	pub fn to_command(&self) -> Command {
+		let mut mame_command = Command::new(self.program.clone());
+
+		#[cfg(target_os = "windows")]
+		mame_command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+		mame_command.current_dir(self.working_directory.clone());
+		mame_command.args(self.arguments.clone());
+
+		mame_command
+	}
+
+	pub fn get_command_line(&self) -> String {
+		get_command_line(&self.program, &self.arguments)
+	}
+
+	// Only one of the scripts is exported on each platform.
+	#[allow(dead_code)]
+	pub fn to_shell_script(&self) -> String {
+		let mut script = "#!/bin/sh\n".to_string();
+
+		script += &LaunchPlan::get_script_notes(&self.tasks, "# ");
+		script += &("cd ".to_owned() + &LaunchPlan::quote_shell_argument(&self.working_directory) + " || exit 1\n");
+		script += &("exec ".to_owned() + &LaunchPlan::quote_shell_argument(&self.program));
+		for argument in self.arguments.iter() {
+			script += " ";
+			script += &LaunchPlan::quote_shell_argument(argument);
+		}
+		script += "\n";
+
+		script
+	}
+
+	#[allow(dead_code)]
+	pub fn to_batch_script(&self) -> String {
+		let mut script = "@echo off\r\n".to_string();
+
+		script += &LaunchPlan::get_script_notes(&self.tasks, "rem ").replace("\n", "\r\n");
+		script += &("cd /d ".to_owned() + &LaunchPlan::quote_batch_argument(&self.working_directory) + "\r\n");
+		script += &LaunchPlan::quote_batch_argument(&self.program);
+		for argument in self.arguments.iter() {
+			script += " ";
+			script += &LaunchPlan::quote_batch_argument(argument);
+		}
+		script += "\r\n";
+
+		script
+	}
+
+	// The slot and bitbanger from "<slot>; <bitbanger>".
+	fn get_slot(bitb_startpoint: &String) -> Option<(String, String)> {
+		match Regex::new(r"^(?<slot_select>[^; ]+?)\; (?<bitb_select>.+?)$").unwrap().captures(bitb_startpoint.as_str()) {
+			Some(matches) => Some((matches["slot_select"].to_string(), matches["bitb_select"].to_string())),
+			None => None
+		}
+	}
+
+	// host:port endpoints are sockets. Anything else is a serial port.
+	fn get_bitb_endpoint(bitb_endpoint: &String) -> String {
+		if Regex::new(r"^[^\:]+\:\d+$").unwrap().is_match(bitb_endpoint.as_str()) {
+			"socket.".to_owned() + bitb_endpoint
+		} else {
+			bitb_endpoint.clone()
+		}
+	}
+
+	// A script doesn't do what the launcher does alongside MAME so say what's missing.
+	fn get_script_notes(tasks: &Vec<LaunchTask>, comment_prefix: &str) -> String {
+		let mut notes = "".to_string();
+
+		for task in tasks.iter() {
+			match task {
+				LaunchTask::DebugListener { port } => {
+					notes += &(comment_prefix.to_owned() + "MAME connects the debug serial port to 127.0.0.1:" + &port.to_string() + ". Start something listening there first.\n");
+				},
+				LaunchTask::WindowInput => {
+					notes += &(comment_prefix.to_owned() + "Console input was typed into MAME's window by the launcher.\n");
+				}
+			};
+		}
+
+		notes
+	}
+
+	fn quote_shell_argument(argument: &String) -> String {
+		if argument != "" && argument.chars().all(|character| character.is_ascii_alphanumeric() || "_-./:,=+@%".contains(character)) {
+			argument.clone()
+		} else {
+			"'".to_owned() + &argument.replace("'", "'\\''") + "'"
+		}
+	}
+
+	fn quote_batch_argument(argument: &String) -> String {
+		let argument = argument.replace("%", "%%");
+
+		if argument != "" && !argument.chars().any(|character| character.is_whitespace() || "\"&|<>^(),;=".contains(character)) {
+			argument
+		} else {
+			"\"".to_owned() + &argument.replace("\"", "\"\"") + "\""
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::{HashMap, HashSet};
+
+	fn get_mame_options(selected_box: &str) -> MAMEOptions {
+		MAMEOptions {
+			selected_box: Some(selected_box.into()),
+			selected_bootroms: None,
+			selected_modem_bitb_endpoint: Some("".into()),
+			selected_debug_bitb_endpoint: Some(DEFAULT_DEBUG_ENDPOINT.into()),
+			selected_hdimg_paths: None,
+			selected_hdimg_enabled: None,
+			verbose_mode: Some(false),
+			windowed_mode: Some(false),
+			use_drc: Some(true),
+			debug_mode: Some(false),
+			skip_info_screen: Some(false),
+			validate_checksums: Some(true),
+			console_input: Some(false),
+			disable_sound: Some(false),
+			custom_options: Some("".into())
+		}
+	}
+
+	fn get_slots() -> LaunchSlots {
+		LaunchSlots {
+			modem_bitb_startpoint: "modem; bitb1".into(),
+			debug_bitb_startpoint: "debug; bitb2".into()
+		}
+	}
+
+	fn get_plan(mame_options: &MAMEOptions, slots: &LaunchSlots) -> LaunchPlan {
+		LaunchPlan::new(&"/opt/mame/mame".into(), mame_options, &BoxProfile::default(), &MAMECapabilities::default(), slots, || Some(5555)).unwrap()
+	}
+
+	fn get_arguments(arguments: &str) -> Vec<String> {
+		arguments.split(" ").map(|argument| argument.to_string()).collect()
+	}
+
+	#[test]
+	fn plans_box_with_launcher_options() {
+		let plan = get_plan(&get_mame_options("wtv1sony"), &get_slots());
+
+		assert_eq!(plan.program, "/opt/mame/mame");
+		assert_eq!(plan.working_directory, "/opt/mame");
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc"));
+		assert_eq!(plan.tasks, vec![]);
+		assert_eq!(plan.get_debug_bitb_port(), 0);
+	}
+
+	#[test]
+	fn plans_modem_socket() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.selected_modem_bitb_endpoint = Some("wtv.ooguy.com:1122".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc -modem null_modem -bitb1 socket.wtv.ooguy.com:1122"));
+	}
+
+	#[test]
+	fn plans_modem_serial_port() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.selected_modem_bitb_endpoint = Some("/dev/ttyUSB0".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc -modem null_modem -bitb1 /dev/ttyUSB0"));
+	}
+
+	#[test]
+	fn skips_modem_without_slot() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.selected_modem_bitb_endpoint = Some("wtv.ooguy.com:1122".into());
+
+		let plan = get_plan(&mame_options, &LaunchSlots::default());
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc"));
+	}
+
+	#[test]
+	fn plans_local_debug_console() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc -debug null_modem -bitb2 socket.127.0.0.1:5555"));
+		assert_eq!(plan.tasks, vec![LaunchTask::DebugListener { port: 5555 }]);
+		assert_eq!(plan.get_debug_bitb_port(), 5555);
+	}
+
+	#[test]
+	fn plans_remote_debug_console() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+		mame_options.selected_debug_bitb_endpoint = Some("127.0.0.1:2000".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc -debug null_modem -bitb2 socket.127.0.0.1:2000"));
+		assert_eq!(plan.tasks, vec![]);
+	}
+
+	#[test]
+	fn warns_when_no_debug_port() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+
+		let plan = LaunchPlan::new(&"/opt/mame/mame".into(), &mame_options, &BoxProfile::default(), &MAMECapabilities::default(), &get_slots(), || None).unwrap();
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -drc -debug null_modem"));
+		assert_eq!(plan.tasks, vec![]);
+		assert_eq!(plan.warnings, vec![LaunchError::PortUnavailable { endpoint: "the debug console".into() }]);
+	}
+
+	#[test]
+	fn types_into_window_without_debug_slot() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+		mame_options.selected_modem_bitb_endpoint = Some("wtv.ooguy.com:1122".into());
+
+		let slots = LaunchSlots {
+			modem_bitb_startpoint: "modem; bitb1".into(),
+			debug_bitb_startpoint: "".into()
+		};
+
+		let plan = get_plan(&mame_options, &slots);
+
+		let mut expected_arguments = get_arguments("wtv1sony -nomouse -drc");
+		#[cfg(target_os = "windows")]
+		expected_arguments.extend(get_arguments("-keyboardprovider win32"));
+		expected_arguments.extend(get_arguments("-background_input -modem null_modem -bitb1 socket.wtv.ooguy.com:1122"));
+
+		assert_eq!(plan.arguments, expected_arguments);
+		assert_eq!(plan.tasks, vec![LaunchTask::WindowInput]);
+	}
+
+	#[test]
+	fn plans_modem_and_debug_console() {
+		let mut mame_options = get_mame_options("wtv2");
+		mame_options.console_input = Some(true);
+		mame_options.selected_modem_bitb_endpoint = Some("127.0.0.1:1122".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv2 -nomouse -drc -debug null_modem -bitb2 socket.127.0.0.1:5555 -modem null_modem -bitb1 socket.127.0.0.1:1122"));
+	}
+
+	#[test]
+	fn plans_enabled_hdimg_for_box() {
+		let mut mame_options = get_mame_options("wtv2");
+		mame_options.selected_hdimg_paths = Some(HashMap::from([("wtv2".to_string(), "/images/my disk.chd".to_string()), ("wtv1sony".to_string(), "/images/other.chd".to_string())]));
+		mame_options.selected_hdimg_enabled = Some(HashMap::from([("wtv2".to_string(), true)]));
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		let mut expected_arguments = get_arguments("wtv2 -nomouse -drc -hard");
+		expected_arguments.push("/images/my disk.chd".into());
+
+		assert_eq!(plan.arguments, expected_arguments);
+	}
+
+	#[test]
+	fn skips_disabled_hdimg() {
+		let mut mame_options = get_mame_options("wtv2");
+		mame_options.selected_hdimg_paths = Some(HashMap::from([("wtv2".to_string(), "/images/disk.chd".to_string())]));
+		mame_options.selected_hdimg_enabled = Some(HashMap::from([("wtv2".to_string(), false)]));
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(plan.arguments, get_arguments("wtv2 -nomouse -drc"));
+	}
+
+	#[test]
+	fn plans_bootrom_and_custom_options() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.selected_bootroms = Some(HashMap::from([("wtv1sony".to_string(), "bootrom_2046.o".to_string())]));
+		mame_options.custom_options = Some("-ramsize 8M -cfg_directory \"/my cfg\"".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		let mut expected_arguments = get_arguments("wtv1sony -nomouse -drc -bios bootrom_2046 -ramsize 8M -cfg_directory");
+		expected_arguments.push("/my cfg".into());
+
+		assert_eq!(plan.arguments, expected_arguments);
+		assert_eq!(plan.custom_arguments, vec!["-ramsize", "8M", "-cfg_directory", "/my cfg"]);
+	}
+
+	#[test]
+	fn leaves_out_unsupported_options() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.windowed_mode = Some(true);
+		mame_options.skip_info_screen = Some(true);
+
+		let mame_capabilities = MAMECapabilities {
+			options: HashSet::from(["window".to_string(), "mouse".to_string()])
+		};
+
+		let plan = LaunchPlan::new(&"/opt/mame/mame".into(), &mame_options, &BoxProfile::default(), &mame_capabilities, &get_slots(), || Some(5555)).unwrap();
+
+		assert_eq!(plan.arguments, get_arguments("wtv1sony -nomouse -window"));
+	}
+
+	#[test]
+	fn dry_run_and_launch_use_the_same_port() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+
+		let dry_run_plan = LaunchPlan::new(&"/opt/mame/mame".into(), &mame_options, &BoxProfile::default(), &MAMECapabilities::default(), &get_slots(), || LaunchPlan::reserve_debug_port()).unwrap();
+		let launch_plan = LaunchPlan::new(&"/opt/mame/mame".into(), &mame_options, &BoxProfile::default(), &MAMECapabilities::default(), &get_slots(), || LaunchPlan::reserve_debug_port()).unwrap();
+
+		assert!(dry_run_plan.get_debug_bitb_port() > 0);
+		assert_eq!(dry_run_plan.to_shell_script(), launch_plan.to_shell_script());
+
+		// Once the launcher is listening on it the next plan gets another port.
+		let _listener = TcpListener::bind(("127.0.0.1", launch_plan.get_debug_bitb_port())).unwrap();
+		assert_ne!(LaunchPlan::reserve_debug_port(), Some(launch_plan.get_debug_bitb_port()));
+	}
+
+	#[test]
+	fn exports_shell_script() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.console_input = Some(true);
+		mame_options.custom_options = Some("-snapname \"it's here\"".into());
+
+		let plan = get_plan(&mame_options, &get_slots());
+
+		assert_eq!(
+			plan.to_shell_script(),
+			"#!/bin/sh\n".to_owned()
+			+ "# MAME connects the debug serial port to 127.0.0.1:5555. Start something listening there first.\n"
+			+ "cd /opt/mame || exit 1\n"
+			+ "exec /opt/mame/mame wtv1sony -nomouse -drc -snapname 'it'\\''s here' -debug null_modem -bitb2 socket.127.0.0.1:5555\n"
+		);
+	}
+
+	#[test]
+	fn exports_batch_script() {
+		let mut mame_options = get_mame_options("wtv1sony");
+		mame_options.custom_options = Some("-snapname \"100% done\"".into());
+
+		let plan = LaunchPlan::new(&"C:\\MAME\\mame.exe".into(), &mame_options, &BoxProfile::default(), &MAMECapabilities::default(), &get_slots(), || Some(5555)).unwrap();
+
+		assert!(plan.to_batch_script().ends_with("C:\\MAME\\mame.exe wtv1sony -nomouse -drc -snapname \"100%% done\"\r\n"));
+	}
+}
@@ -6,12 +6,16 @@
 // You need to run this launcher with the SLINT_BACKEND=winit-software env to force the software renderer.
 
 mod config;
+mod boxprofile;
+mod romset;
+mod launchplan;
+mod error;
 mod wtv;
 
 use std::{
 	collections::HashMap, 
 	fs::File,
-	io::{BufReader, Read, Write, Cursor, Seek, SeekFrom}, 
+	io::{BufReader, Read, Write, Cursor}, 
 	path::Path, process::{Command, Stdio},
 	env,
 	process::{ChildStdout, ChildStderr},
@@ -27,11 +31,10 @@ use rand::{
 	distributions::{Alphanumeric, DistString}
 };
 use regex::Regex;
-use native_dialog::FileDialog;
+use native_dialog::{FileDialog, MessageDialog, MessageType};
 use sysinfo::{Pid, System};
 use rodio;
 use serialport;
-use portpicker;
 use hex;
 use which::which;
 use open;
@@ -61,21 +64,31 @@ use {
 	accessibility_sys::{AXIsProcessTrustedWithOptions, kAXTrustedCheckOptionPrompt}
 };
 
-use config::{LauncherConfig, MAMEMachineNode, MAMEOptions, Paths, PersistentConfig};
+use config::{LauncherConfig, MAMECapabilities, MAMEDirectories, MAMEDocument, MAMEInstallation, MAMEMachineNode, MAMEMachineROMNode, MAMEOptions, Paths, PersistentConfig};
+use boxprofile::{discover_boxes, BoxProfile, BoxProfiles, BootROMStorage, AppROMStorage, AddressRange};
+use romset::RomsetWrite;
+use launchplan::{LaunchPlan, LaunchSlots, LaunchTask, DEFAULT_DEBUG_ENDPOINT};
+use error::LaunchError;
 use wtv::{
 	buildio::{
 		BuildIO,
 		BuildIODataCollation,
 		diskio::CompressedHunkDiskIO,
-		romio::ROMIO,
-		flashdiskio::FlashdiskIO
+		romio::{ROMIO, ROMInterleave},
+		flashdiskio::FlashdiskIO,
+		mdocgeometry::MDOCGeometry
 	},
 	buildmeta::{
 		BuildMeta,
 		BuildMetaLayout,
 		BuildInfo,
+		BuildKind,
+		BuildClassification,
 		BuildFlag
 	},
+	error::LauncherError,
+	flashdiskformat::FlashdiskAllocations,
+	romhash::RomHash,
 	ssid::{SSIDInfo, SSIDBoxType, SSIDManufacture}
 };
 
@@ -84,42 +97,10 @@ slint::include_modules!();
 const STACK_SIZE: usize = 32 * 1024 * 1024;
 const SSID_ROM_FILE: &'static str = "ds2401.bin";
 const DEFAULT_BOOTORM_FILE_NAME: &'static str = "bootrom.o";
-const BOOTROM_BASE_ADDRESS: u32 = 0x9fc00000;
-const BOOTROM_FLASH_FILE_PREFIX: &'static str = "bootrom_flash";
-// wtv2 (Plus) boxes will be detected and ran from this launcher but we assume (and can only verify) a flash-based approm 
-const APPROM1_FLASH_BASE_ADDRESS: u32 = 0x9f000000;
-const APPROM2_FLASH_BASE_ADDRESS: u32 = 0x9fe00000;
-const APPROM3_DISK_BASE_ADDRESS_MIN: u32 = 0x80300000;
-const APPROM3_DISK_BASE_ADDRESS_MAX: u32 = 0x84400000;
-const APPROM1_FLASH_FILE_PREFIX: &'static str = "bank0_flash";
-const APPROM2_FLASH_FILE_PREFIX: &'static str = "approm_flash";
-const APPROM3_FLASH_FILE_PREFIX: &'static str = "bank1_flash";
 const APPROM_HDIMG_PREFIX: &'static str = "hdimg";
 const ALLOW_APPROM2_FILES: bool = false;
-const DEFAULT_BOX_ORDER: [&'static str; 19] = [
-	"wtv1bf0",
-	"wtv1sony",
-	"wtv1phil",
-	"wtv2lc2",
-	"wtv2sony",
-	"wtv2phil",
-	"wtv2jpp",
-	"wtv2jpc",
-	"wtv2wld",
-	"wtv1bfe",
-	"wtv1pal",
-	"wtv1dev",
-	"wtv1dv2",
-	"wtv2drb",
-	"wtv2npl",
-	"wtv2ncl",
-	"wtv2esr",
-	"wtv2utv",
-	"wtv2uvd"
-];
 const DEFAULT_FLASHDISK_SIZE: u64 = 8 * 1024 * 1024;
 const PUBLIC_TOUCHPP_ADDRESS: &'static str = "wtv.ooguy.com:1122";
-const DEFAULT_DEBUG_ENDPOINT: &'static str = "Launcher Console";
 const DEBUG_READ_BUFFER_SIZE: usize = 1024;
 const CONSOLE_READ_BUFFER_SIZE: usize = 1024;
 const CONSOLE_UPDATE_INTERVAL_USEC: u128 = 50000;
@@ -161,7 +142,10 @@ enum BuildStorageState {
 	CantReadBuild,
 	CodeChecksumMismatch,
 	RomfsChecksumMismatch,
-	BadBaseAddress
+	BadBaseAddress,
+	AdminInfoCorrupt,
+	// The box's ROMs are in a 7z which we can't read.
+	UnsupportedRomset
 }
 
 #[allow(dead_code)]
@@ -173,7 +157,9 @@ enum SSIDStorageState {
 	ManufactureMismatch,
 	CRCMismatch,
 	BoxTypeMismatch,
-	CantReadSSID
+	CantReadSSID,
+	RomSizeMismatch,
+	RomHashMismatch
 }
 
 #[allow(dead_code)]
@@ -268,6 +254,28 @@ fn disable_loading(ui_weak: &slint::Weak<MainWindow>) {
 	});
 }
 
+// Checks a ROM file against the size and hashes MAME has for it. Gives back the SHA1 we found and what MAME will make of the file.
+// MAME won't load a ROM that's the wrong size but only warns about the hash.
+// There's nothing to compare against for "nodump" ROMs and "baddump" hashes are of a dump MAME already knows is wrong so only the size is checked for those.
+fn verify_mame_rom(rom: &MAMEMachineROMNode, file_path: &String, validate_hashes: bool) -> (String, BuildStorageState) {
+	let rom_hash = match RomHash::from_file(file_path) {
+		Ok(rom_hash) => rom_hash,
+		Err(_e) => return ("".into(), BuildStorageState::CantReadBuild)
+	};
+
+	let rom_status = rom.status.clone().unwrap_or("".into());
+
+	let rom_state = if rom.size.is_some() && rom_hash.size != rom.size.unwrap() as u64 {
+		BuildStorageState::RomSizeMismatch
+	} else if validate_hashes && rom_status != "nodump" && rom_status != "baddump" && !rom_hash.matches(&rom.crc, &rom.sha1) {
+		BuildStorageState::RomHashMismatch
+	} else {
+		BuildStorageState::BuildLooksGood
+	};
+
+	(rom_hash.sha1_string(), rom_state)
+}
+
 fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Result<Vec<VerifiableBuildItem>, Box<dyn std::error::Error>> {
 	let mut bootroms: Vec<VerifiableBuildItem> = vec![];
 
@@ -276,10 +284,12 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 		.clone()
 		.unwrap_or("".into());
 
+	let box_profile = BoxProfile::for_box(&selected_box);
+
 	let config_persistent_paths = config.persistent.paths.clone();
 
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 	let mut biossets = HashMap::new();
 	for biosset in selected_machine.clone().biosset.unwrap_or(vec![]).iter() {
@@ -339,9 +349,13 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 				build_info: None
 			};
 
-			let bootrom_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &rom_name.clone().to_string();
+			let bootrom_path = romset::find_rom_file(&mame_directories, &selected_box, &rom_name.clone().to_string());
 
 			if Path::new(&bootrom_path).exists() {
+				let (rom_hash, rom_state) = verify_mame_rom(rom, &bootrom_path, validate_checksums);
+
+				bootrom.hash = rom_hash.into();
+
 				match BuildMeta::open_rom(bootrom_path, None, validate_checksums) {
 					Ok(build_meta) => {
 						bootrom.build_info = Some(build_meta.build_info[0].clone());
@@ -355,7 +369,7 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 							bootrom.build_storage_state = BuildStorageState::CodeChecksumMismatch;
 						} else if validate_checksums && build_meta.build_info[0].romfs_header.romfs_checksum != build_meta.build_info[0].calculated_romfs_checksum {
 							bootrom.build_storage_state = BuildStorageState::RomfsChecksumMismatch;
-						} else if build_meta.build_info[0].build_header.build_base_address != BOOTROM_BASE_ADDRESS {
+						} else if !AddressRange::any_contains(&box_profile.bootrom_base_addresses, build_meta.build_info[0].build_header.build_base_address) {
 							bootrom.build_storage_state = BuildStorageState::BadBaseAddress;
 						} else {
 							bootrom.build_storage_state = BuildStorageState::BuildLooksGood;
@@ -367,6 +381,13 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 						bootrom.hint = "".into();
 					}
 				}
+
+				// A bad size stops MAME so it's reported over anything else. A hash mismatch is only reported if the build itself looks fine.
+				if rom_state == BuildStorageState::RomSizeMismatch || (rom_state == BuildStorageState::RomHashMismatch && bootrom.build_storage_state == BuildStorageState::BuildLooksGood) {
+					bootrom.build_storage_state = rom_state;
+				}
+			} else if romset::find_sevenzip_romset(&mame_directories, &selected_box).is_some() {
+				bootrom.build_storage_state = BuildStorageState::UnsupportedRomset;
 			} else {
 				bootrom.build_storage_state = BuildStorageState::FileNotFound;
 			}
@@ -378,10 +399,10 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 	// No ROM bootroms available. Check if this is a developer box and look for flash files.
 	// MAME doesn't tell me about this so we're just guessing if these files are used.
 	if bootroms.iter().count() == 0 {
-		if Regex::new(r"^wtv\d+dev$").unwrap().is_match(selected_box.as_str()) {
+		if box_profile.bootrom_storage == BootROMStorage::StrippedFlash {
 			let mut bootrom = VerifiableBuildItem {
 				hint: "".into(),
-				value: BOOTROM_FLASH_FILE_PREFIX.into(),
+				value: box_profile.bootrom_flash_prefix.clone().into(),
 				status: "".into(),
 				can_revert: false,
 				description: "BootROM Flash Build".into(),
@@ -391,17 +412,14 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 				build_info: None
 			};
 
-			let bootrom_path_prefix = mame_directory_path + "/nvram/" + &selected_box + "/" + BOOTROM_FLASH_FILE_PREFIX;
+			let bootrom_path_prefix = mame_directories.nvram_directory.clone() + "/" + &selected_box + "/" + &box_profile.bootrom_flash_prefix;
 
-			let bootrom_path0 = bootrom_path_prefix.clone() + "0";
-			let bootrom_path1 = bootrom_path_prefix.clone() + "1";
+			let flash_collation = box_profile.flash_collation.to_collation();
+			let (bootrom_chips_found, bootrom_chip_count) = count_flash_chip_files(&bootrom_path_prefix, flash_collation);
 
-			let bootrom_path0_exists = Path::new(&bootrom_path0).exists();
-			let bootrom_path1_exists = Path::new(&bootrom_path1).exists();
-
-			if bootrom_path0_exists || bootrom_path1_exists {
-				if bootrom_path0_exists && bootrom_path1_exists {
-					match BuildMeta::open_rom(bootrom_path_prefix, Some(BuildIODataCollation::StrippedROMs), validate_checksums) {
+			if bootrom_chips_found > 0 {
+				if bootrom_chips_found == bootrom_chip_count {
+					match BuildMeta::open_rom(bootrom_path_prefix, Some(flash_collation), validate_checksums) {
 						Ok(build_meta) => {
 							bootrom.build_info = Some(build_meta.build_info[0].clone());
 							if (build_meta.build_info[0].build_header.build_flags & BuildFlag::Debug) != 0x00 {
@@ -414,7 +432,7 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 								bootrom.build_storage_state = BuildStorageState::CodeChecksumMismatch;
 							} else if validate_checksums && build_meta.build_info[0].romfs_header.romfs_checksum != build_meta.build_info[0].calculated_romfs_checksum {
 								bootrom.build_storage_state = BuildStorageState::RomfsChecksumMismatch;
-							} else if build_meta.build_info[0].build_header.build_base_address != BOOTROM_BASE_ADDRESS {
+							} else if !AddressRange::any_contains(&box_profile.bootrom_base_addresses, build_meta.build_info[0].build_header.build_base_address) {
 								bootrom.build_storage_state = BuildStorageState::BadBaseAddress;
 							} else {
 								bootrom.build_storage_state = BuildStorageState::BuildLooksGood;
@@ -434,7 +452,7 @@ fn get_bootroms(config: &LauncherConfig, selected_machine: &MAMEMachineNode) ->
 			}
 
 			bootroms.push(bootrom);
-		} else if Regex::new(r"^wtv\d+wld$").unwrap().is_match(selected_box.as_str()) {
+		} else if box_profile.bootrom_storage == BootROMStorage::NoBootROM {
 			let bootrom = VerifiableBuildItem {
 				hint: "".into(),
 				value: "None".into(),
@@ -465,11 +483,13 @@ fn get_flash_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode
 	let config_persistent_paths = config.persistent.paths.clone();
 
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
+
+	let box_profile = BoxProfile::for_box(&selected_box);
 
 	let mut approm = VerifiableBuildItem {
 		hint: "".into(),
-		value: APPROM1_FLASH_FILE_PREFIX.into(),
+		value: box_profile.approm_flash_prefix.clone().into(),
 		status: "".into(),
 		can_revert: false,
 		description: "AppROM Flash Build".into(),
@@ -479,47 +499,34 @@ fn get_flash_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode
 		build_info: None
 	};
 
-	let approm1_path_prefix: String;
-	let approm2_path_prefix: String;
+	let approm_directory_path = get_box_nvram_directory(&mame_directories, &selected_box, selected_bootrom_index);
+	let approm1_path_prefix = approm_directory_path.clone() + "/" + &box_profile.approm_flash_prefix;
+	let approm2_path_prefix = approm_directory_path.clone() + "/" + &box_profile.approm2_flash_prefix;
 
-	if selected_bootrom_index > 0 {
-		approm1_path_prefix = mame_directory_path.clone() + "/nvram/" + &selected_box + "_" + &selected_bootrom_index.to_string() + "/" + APPROM1_FLASH_FILE_PREFIX;
-		approm2_path_prefix = mame_directory_path.clone() + "/nvram/" + &selected_box + "_" + &selected_bootrom_index.to_string() + "/" + APPROM2_FLASH_FILE_PREFIX;
-	} else {
-		approm1_path_prefix = mame_directory_path.clone() + "/nvram/" + &selected_box + "/" + APPROM1_FLASH_FILE_PREFIX;
-		approm2_path_prefix = mame_directory_path.clone() + "/nvram/" + &selected_box + "/" + APPROM2_FLASH_FILE_PREFIX;
-	}
+	let flash_collation = box_profile.flash_collation.to_collation();
 
 	let approm_path_prefix;
 	
 	// MAME doesn't provide details about the flash device used, so we must guess this. If approm2 files are there, then use approm2 paths otherwise use an approm1 path.
-	if ALLOW_APPROM2_FILES && (Path::new(&(approm2_path_prefix.clone() + "0")).exists() || Path::new(&(approm2_path_prefix.clone() + "1")).exists()) {
+	if ALLOW_APPROM2_FILES && count_flash_chip_files(&approm2_path_prefix, flash_collation).0 > 0 {
 		approm_path_prefix = approm2_path_prefix.clone();
 	} else {
 		approm_path_prefix = approm1_path_prefix.clone();
 	}
 
-	let approm_path0 = approm_path_prefix.clone() + "0";
-	let approm_path1 = approm_path_prefix.clone() + "1";
-
-	let approm_path0_exists = Path::new(&approm_path0).exists();
-	let approm_path1_exists = Path::new(&approm_path1).exists();
+	let (approm_chips_found, approm_chip_count) = count_flash_chip_files(&approm_path_prefix, flash_collation);
 
 	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
 
-	if approm_path0_exists || approm_path1_exists {
-		if approm_path0_exists && approm_path1_exists {
-			if Regex::new(r"^wtv\d+wld$").unwrap().is_match(selected_box.as_str()) {
-				let approm_path_prefix = mame_directory_path.clone() + "/nvram/" + &selected_box + "/" + APPROM3_FLASH_FILE_PREFIX;
-	
-				let approm_path2 = approm_path_prefix.clone() + "0";
-				let approm_path3 = approm_path_prefix.clone() + "1";
-			
-				let approm_path2_exists = Path::new(&approm_path2).exists();
-				let approm_path3_exists = Path::new(&approm_path3).exists();
+	if approm_chips_found > 0 {
+		if approm_chips_found == approm_chip_count {
+			if box_profile.wince_flash_prefix != "" {
+				let wince_path_prefix = mame_directories.nvram_directory.clone() + "/" + &selected_box + "/" + &box_profile.wince_flash_prefix;
 
-				if approm_path2_exists || approm_path3_exists {
-					if approm_path2_exists && approm_path3_exists {
+				let (wince_chips_found, wince_chip_count) = count_flash_chip_files(&wince_path_prefix, flash_collation);
+
+				if wince_chips_found > 0 {
+					if wince_chips_found == wince_chip_count {
 						approm.hint = "".into();
 						approm.value = "WinCE".into();
 						approm.status = "unverified".into();
@@ -534,7 +541,7 @@ fn get_flash_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode
 					approm.build_storage_state = BuildStorageState::StrippedFlashMissing;
 				}
 			} else {
-				match BuildMeta::open_rom(approm_path_prefix.clone(), Some(BuildIODataCollation::StrippedROMs), validate_checksums) {
+				match BuildMeta::open_rom(approm_path_prefix.clone(), Some(flash_collation), validate_checksums) {
 					Ok(build_meta) => {
 						approm.build_info = Some(build_meta.build_info[0].clone());
 						if (build_meta.build_info[0].build_header.build_flags & BuildFlag::Debug) != 0x00 {
@@ -547,7 +554,7 @@ fn get_flash_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode
 							approm.build_storage_state = BuildStorageState::CodeChecksumMismatch;
 						} else if validate_checksums && build_meta.build_info[0].romfs_header.romfs_checksum != build_meta.build_info[0].calculated_romfs_checksum {
 							approm.build_storage_state = BuildStorageState::RomfsChecksumMismatch;
-						} else if build_meta.build_info[0].build_header.build_base_address != APPROM1_FLASH_BASE_ADDRESS && build_meta.build_info[0].build_header.build_base_address != APPROM2_FLASH_BASE_ADDRESS {
+						} else if !AddressRange::any_contains(&box_profile.flash_approm_base_addresses, build_meta.build_info[0].build_header.build_base_address) {
 							approm.build_storage_state = BuildStorageState::BadBaseAddress;
 						} else {
 							approm.build_storage_state = BuildStorageState::BuildLooksGood;
@@ -572,17 +579,19 @@ fn get_flash_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode
 	Ok(approms)
 }
 
-fn populate_approms_from_disk_file(approms: &mut Vec<VerifiableBuildItem>, file_path: String, collation: Option<BuildIODataCollation>, prefix: String, discription: String, is_preset_disk: bool, validate_checksums: bool)  -> Result<(), Box<dyn std::error::Error>> {
-	let can_revert = match is_preset_disk {
-		true => {
-			let diff_file_path = CompressedHunkDiskIO::find_diff_file(file_path.clone()).unwrap_or("".into());
+// How many of a stripped flash's chip files are there and how many there should be.
+fn count_flash_chip_files(path_prefix: &String, collation: BuildIODataCollation) -> (usize, usize) {
+	let chip_file_paths = ROMInterleave::for_collation(collation).get_chip_file_paths(path_prefix);
+	let chips_found = chip_file_paths.iter().filter(|chip_file_path| Path::new(chip_file_path).exists()).count();
 
-			diff_file_path != "" && Path::new(&diff_file_path).exists()
-		},
-		_ => false
-	};
+	(chips_found, chip_file_paths.len())
+}
 
-	match BuildMeta::open_disk(file_path.clone(), collation, validate_checksums) {
+// Only preset disks have a diff file. User HDD images are given an empty diff path.
+fn populate_approms_from_disk_file(approms: &mut Vec<VerifiableBuildItem>, file_path: String, diff_file_path: String, collation: Option<BuildIODataCollation>, prefix: String, discription: String, validate_checksums: bool, approm_base_addresses: &Vec<AddressRange>)  -> Result<(), Box<dyn std::error::Error>> {
+	let can_revert = diff_file_path != "" && Path::new(&diff_file_path).exists();
+
+	match BuildMeta::open_disk_image(file_path.clone(), diff_file_path, collation, validate_checksums) {
 		Ok(build_meta) => {
 			let mut build_index = 0;
 			for buildinfo in build_meta.build_info.iter() {
@@ -615,7 +624,7 @@ fn populate_approms_from_disk_file(approms: &mut Vec<VerifiableBuildItem>, file_
 					approm.build_storage_state = BuildStorageState::CodeChecksumMismatch;
 				} else if validate_checksums && buildinfo.romfs_header.romfs_checksum != buildinfo.calculated_romfs_checksum {
 					approm.build_storage_state = BuildStorageState::RomfsChecksumMismatch;
-				} else if buildinfo.build_header.build_base_address < APPROM3_DISK_BASE_ADDRESS_MIN || buildinfo.build_header.build_base_address > APPROM3_DISK_BASE_ADDRESS_MAX {
+				} else if !AddressRange::any_contains(approm_base_addresses, buildinfo.build_header.build_base_address) {
 					approm.build_storage_state = BuildStorageState::BadBaseAddress;
 				} else {
 					approm.build_storage_state = BuildStorageState::BuildLooksGood;
@@ -638,6 +647,18 @@ fn populate_approms_from_disk_file(approms: &mut Vec<VerifiableBuildItem>, file_
 	Ok(())
 }
 
+// Where MAME keeps its changes to a preset CHD.
+fn get_diff_file_path(mame_directories: &MAMEDirectories, file_path: &String) -> String {
+	CompressedHunkDiskIO::find_diff_file(file_path, &mame_directories.rom_paths, &mame_directories.diff_directory)
+}
+
+// Disk images are assumed to be in the byte order the box's HD uses but dumps from other readers and emulators can be in a different order.
+fn get_disk_collation(selected_box: &String, file_path: &String) -> BuildIODataCollation {
+	let box_collation = BoxProfile::for_box(selected_box).disk_collation.to_collation();
+
+	BuildMeta::detect_collation(file_path.clone(), box_collation)
+}
+
 fn get_disk_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode, selected_hdimg_path: String) -> Result<Vec<VerifiableBuildItem>, Box<dyn std::error::Error>> {
 	let mut approms: Vec<VerifiableBuildItem> = vec![];
 
@@ -648,31 +669,30 @@ fn get_disk_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode,
 
 	let config_persistent_paths = config.persistent.paths.clone();
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
-
-	let disk_collation = match Regex::new(r"^wtv\d+utv").unwrap().is_match(selected_box.as_str()) {
-		true => BuildIODataCollation::ByteSwapped1632,
-		false => BuildIODataCollation::ByteSwapped16,
-	};
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
 
+	let box_profile = BoxProfile::for_box(&selected_box);
+
 	if selected_machine.disk.iter().count() > 0 {
 		match selected_machine.disk.clone() {
 			Some(disks) => {
 				let disk_name = disks[0].name.clone().unwrap_or("".into());
 				let disk_file = disk_name.clone() + ".chd";
 
-				let preset_img_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &disk_file;
+				let preset_img_path = romset::find_rom_file(&mame_directories, &selected_box, &disk_file);
+				let disk_collation = get_disk_collation(&selected_box, &preset_img_path);
 
 				let _ = populate_approms_from_disk_file(
 					&mut approms, 
-					preset_img_path, 
+					preset_img_path.clone(), 
+					get_diff_file_path(&mame_directories, &preset_img_path), 
 					Some(disk_collation), 
 					disk_name.clone(), 
 					"From preset ".to_owned() + &disk_file.clone() + " file",
-					true,
-					validate_checksums
+					validate_checksums,
+					&box_profile.disk_approm_base_addresses
 				);
 			},
 			_ => {
@@ -682,14 +702,17 @@ fn get_disk_approms(config: &LauncherConfig, selected_machine: &MAMEMachineNode,
 	}
 
 	if selected_hdimg_path != "" {
+		let disk_collation = get_disk_collation(&selected_box, &selected_hdimg_path);
+
 		let _ = populate_approms_from_disk_file(
 			&mut approms, 
 			selected_hdimg_path, 
+			"".into(), 
 			Some(disk_collation), 
 			APPROM_HDIMG_PREFIX.to_string(), 
 			"From your HDD image file.".into(),
-			false,
-			validate_checksums
+			validate_checksums,
+			&box_profile.disk_approm_base_addresses
 		);
 	}
 
@@ -706,7 +729,7 @@ fn get_flashdisk_approms(config: &LauncherConfig, selected_machine: &MAMEMachine
 
 	let config_persistent_paths = config.persistent.paths.clone();
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 	let mut approm = VerifiableBuildItem {
 		hint: "".into(),
@@ -720,27 +743,21 @@ fn get_flashdisk_approms(config: &LauncherConfig, selected_machine: &MAMEMachine
 		build_info: None
 	};
 
-	let file_path;
-	if selected_bootrom_index > 0 {
-		file_path = mame_directory_path.clone() + "/nvram/" + &selected_box + "_" + &selected_bootrom_index.to_string() + "/mdoc_flash0";
-	} else {
-		file_path = mame_directory_path.clone() + "/nvram/" + &selected_box + "/mdoc_flash0";
-	}
+	let box_profile = BoxProfile::for_box(&selected_box);
 
-	let flashdisk_size = match get_flashdisk_size(&selected_machine) {
-		Ok(flashdisk_size) => flashdisk_size as u64,
-		_ => DEFAULT_FLASHDISK_SIZE
-	};
+	let file_path = get_flashdisk_path(&mame_directories, &selected_box, selected_bootrom_index);
+
+	let flashdisk_geometry = get_flashdisk_geometry(&selected_machine);
 
 	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
 
 	if Path::new(&file_path).exists() {
-		if flashdisk_size > Path::new(&file_path).metadata().unwrap().len() {
+		if flashdisk_geometry.usr_size() > Path::new(&file_path).metadata().unwrap().len() {
 			// The file is borked so remove it. There are some cases where this isn't intended but most times this will correct some issues.
 			let _ = std::fs::remove_file(&file_path);
 			approm.build_storage_state = BuildStorageState::FileNotFound;
 		} else {
-			match BuildMeta::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), validate_checksums) {
+			match BuildMeta::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), Some(flashdisk_geometry), validate_checksums) {
 				Ok(build_meta) => {
 					let mut build_index = 0;
 					for buildinfo in build_meta.build_info.iter() {
@@ -759,7 +776,9 @@ fn get_flashdisk_approms(config: &LauncherConfig, selected_machine: &MAMEMachine
 
 						if build_meta.build_count == 0 {
 							approm.build_storage_state = BuildStorageState::CantReadBuild;
-						} else if buildinfo.build_header.build_base_address < APPROM3_DISK_BASE_ADDRESS_MIN || buildinfo.build_header.build_base_address > APPROM3_DISK_BASE_ADDRESS_MAX {
+						} else if !build_meta.admin_info_valid {
+							approm.build_storage_state = BuildStorageState::AdminInfoCorrupt;
+						} else if !AddressRange::any_contains(&box_profile.flashdisk_approm_base_addresses, buildinfo.build_header.build_base_address) {
 							approm.build_storage_state = BuildStorageState::BadBaseAddress;
 						} else {
 							approm.build_storage_state = BuildStorageState::BuildLooksGood;
@@ -798,9 +817,10 @@ fn get_ssids(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Res
 	let config_persistent_paths = config.persistent.paths.clone();
 
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 	let mut ssid_file: String = SSID_ROM_FILE.into();
+	let mut ssid_rom: Option<MAMEMachineROMNode> = None;
 
 	for rom in selected_machine.clone().rom.unwrap_or(vec![]).iter() {
 		let rom_region = 
@@ -813,12 +833,15 @@ fn get_ssids(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Res
 				rom.name
 				.clone()
 				.unwrap_or(SSID_ROM_FILE.into());
+			ssid_rom = Some(rom.clone());
 
 			break;
 		}
 	}
 
-	let ssid_file_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &ssid_file.clone();
+	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
+
+	let ssid_file_path = romset::find_rom_file(&mame_directories, &selected_box, &ssid_file.clone());
 
 	let mut ssid = VerifiableSSIDItem {
 		hint: "".into(),
@@ -829,6 +852,11 @@ fn get_ssids(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Res
 	};
 
 	if Path::new(&ssid_file_path).exists() {
+		let rom_state = match ssid_rom {
+			Some(rom) => verify_mame_rom(&rom, &ssid_file_path, validate_checksums).1,
+			_ => BuildStorageState::BuildLooksGood
+		};
+
 		let mut f = File::open(ssid_file_path)?;
 
 		let mut raw_ssid = [0x00; 0x08];
@@ -846,9 +874,9 @@ fn get_ssids(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Res
 				} else if ssid_info.box_type != SSIDBoxType::MAME {
 					ssid.ssid_storage_state = SSIDStorageState::BoxTypeMismatch;
 				} else {
-					if Regex::new(r"^wtv\d+sony$").unwrap().is_match(selected_box.as_str())  && ssid_info.manufacture != SSIDManufacture::Sony {
-						ssid.ssid_storage_state = SSIDStorageState::ManufactureMismatch;
-					} else if Regex::new(r"^wtv\d+phil$").unwrap().is_match(selected_box.as_str()) && ssid_info.manufacture != SSIDManufacture::Phillips {
+					let expected_manufacture = BoxProfile::for_box(&selected_box).ssid_manufacture;
+
+					if expected_manufacture.is_some() && ssid_info.manufacture != SSIDManufacture::from_u16(expected_manufacture.unwrap()) {
 						ssid.ssid_storage_state = SSIDStorageState::ManufactureMismatch;
 					} else {
 						ssid.ssid_storage_state = SSIDStorageState::SSIDLooksGood;
@@ -860,6 +888,12 @@ fn get_ssids(config: &LauncherConfig, selected_machine: &MAMEMachineNode) -> Res
 				ssid.ssid_storage_state = SSIDStorageState::CantReadSSID;
 			}
 		}
+
+		if rom_state == BuildStorageState::RomSizeMismatch {
+			ssid.ssid_storage_state = SSIDStorageState::RomSizeMismatch;
+		} else if rom_state == BuildStorageState::RomHashMismatch && ssid.ssid_storage_state == SSIDStorageState::SSIDLooksGood {
+			ssid.ssid_storage_state = SSIDStorageState::RomHashMismatch;
+		}
 	} else {
 		ssid.ssid_storage_state = SSIDStorageState::FileNotFound;
 	}
@@ -1031,11 +1065,14 @@ fn populate_selected_box_bootroms(ui_weak: &slint::Weak<MainWindow>, config: &La
 						ui.set_launcher_state_message("The BootROM image doesn't exist. Please choose a bootrom.o file!".into());
 						ui_mame.set_bootrom_import_state(BuildImportState::WillCreate);
 					},
+					BuildStorageState::UnsupportedRomset => {
+						ui.set_launcher_state_message("This box's ROMs are in a 7z which can't be read here. Extract it or repack it as a zip. MAME can still use it.".into());
+					},
 					BuildStorageState::RomSizeMismatch => {
-						ui.set_launcher_state_message("BootROM size mismatch! MAME will probably reject this BootROM!".into());
+						ui.set_launcher_state_message("BootROM isn't the size MAME expects! MAME will refuse to start with this BootROM!".into());
 					},
 					BuildStorageState::RomHashMismatch => {
-						ui.set_launcher_state_message("BootROM hash mismatch! MAME will probably reject this BootROM!".into());
+						ui.set_launcher_state_message("BootROM doesn't match MAME's CRC32/SHA1 hashes. MAME will warn about this BootROM but should still start.".into());
 					},
 					BuildStorageState::StrippedFlashCyclopsed => {
 						ui.set_launcher_state_message("Found one BootROM flash file but couldn't find the other. Choosing a new bootrom.o file may fix this.".into());
@@ -1057,6 +1094,9 @@ fn populate_selected_box_bootroms(ui_weak: &slint::Weak<MainWindow>, config: &La
 					}
 					BuildStorageState::BadBaseAddress => {
 						ui.set_launcher_state_message("BootROM base address incorrect! Did you choose an AppROM image? Please choose a new bootrom.o file if it doesn't run!".into());
+					},
+					BuildStorageState::AdminInfoCorrupt => {
+						// BootROMs don't have admin info.
 					}
 				}
 			}
@@ -1079,7 +1119,7 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 	let config_persistent_paths = config.persistent.paths.clone();
 
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 	let selected_box = selected_machine.name.clone().unwrap_or("".into());
 
@@ -1095,17 +1135,12 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 		build_info: None,
 	};
 
-	let mut uses_disk_approms = false;
-	let mut uses_mdoc_approms = false;
 	let can_choose_hdimg;
 	let can_revert_approm;
 
-	for device in selected_machine.device.clone().unwrap_or(vec![]).iter() {
-		if device.dtype.clone().unwrap_or("".to_string()) == "harddisk" {
-			uses_disk_approms = true;
-			break;
-		}
-	}
+	let approm_storage_type = get_machine_storage_type(&selected_machine, &BoxProfile::for_box(&selected_machine.name.clone().unwrap_or("".into())));
+	let uses_disk_approms = approm_storage_type == BuildStorageType::DiskBuild;
+	let uses_mdoc_approms = approm_storage_type == BuildStorageType::FlashdiskBuild;
 
 	let available_approms;
 	if uses_disk_approms {
@@ -1117,7 +1152,7 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 					if disks[0].modifiable.clone().unwrap_or("".into()) == "yes" {
 						can_choose_hdimg = true;
 					} else {
-						can_choose_hdimg = !Path::new(&(mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &disk_name + ".chd")).exists();
+						can_choose_hdimg = !Path::new(&(romset::find_rom_file(&mame_directories, &selected_box, &(disk_name.clone() + ".chd")))).exists();
 					}
 				},
 				_ => {
@@ -1185,15 +1220,6 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 	} else {
 		can_choose_hdimg = false;
 
-		if selected_machine.device_ref.iter().count() > 0 {
-			for device_ref in selected_machine.device_ref.clone().unwrap_or(vec![]).iter() {
-				if device_ref.name.clone().unwrap_or("".to_string()) == "mdoc_collection" {
-					uses_mdoc_approms = true;
-					break;
-				}
-			}
-		}
-
 		if uses_mdoc_approms {
 			available_approms = match get_flashdisk_approms(config, &selected_machine, selected_bootrom_index) {
 				Ok(approms) => approms,
@@ -1229,6 +1255,17 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 		ui_mame.set_uses_mdoc_approms(uses_mdoc_approms);
 		ui_mame.set_can_choose_hdimg(can_choose_hdimg);
 		ui_mame.set_can_revert_approm(can_revert_approm);
+		ui_mame.set_can_repair_admininfo(selected_approm.build_storage_state == BuildStorageState::AdminInfoCorrupt);
+		// The NFTL has to be good before the admin info can be read back through it.
+		let can_edit_admininfo = uses_mdoc_approms
+			&& selected_approm.build_storage_state != BuildStorageState::UnknownBuildState
+			&& selected_approm.build_storage_state != BuildStorageState::FileNotFound;
+		ui_mame.set_can_edit_admininfo(can_edit_admininfo);
+		if !can_edit_admininfo {
+			ui_mame.set_editing_admininfo(false);
+		}
+		// Only offered after a check finds something on the current flashdisk.
+		ui_mame.set_can_repair_flashdisk(false);
 
 		// Convert available approms into a list the UI can use.
 		let selectable_approms: slint::VecModel<HintedItem> = Default::default();
@@ -1264,6 +1301,9 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 						ui.set_launcher_state_message("The AppROM image doesn't exist. Please choose a approm.o file!".into());
 						ui_mame.set_approm_import_state(BuildImportState::WillCreate);
 					},
+					BuildStorageState::UnsupportedRomset => {
+						// AppROMs aren't kept in romsets.
+					},
 					BuildStorageState::RomSizeMismatch => {
 						// Not checking an AppROM as a verified MAME ROM.
 					},
@@ -1291,6 +1331,9 @@ fn populate_selected_box_approms(ui_weak: &slint::Weak<MainWindow>, config: &Lau
 					BuildStorageState::BadBaseAddress => {
 						ui.set_launcher_state_message("AppROM base address incorrect! Did you choose an image for the wrong box? Please choose a new approm.o file if it doesn't run!".into());
 						// The case where they select a bfe approm for a bf0 bootrom or a bf0 approm for a bfe bootrom wil still break. Check for this case?
+					},
+					BuildStorageState::AdminInfoCorrupt => {
+						ui.set_launcher_state_message("The flashdisk admin info is corrupt! The box may not find its build or NVRAM. Use \"Repair Disk Info\" to fix it.".into());
 					}
 					}
 			}
@@ -1429,6 +1472,12 @@ fn populate_selected_box_ssids(ui_weak: &slint::Weak<MainWindow>, config: &Launc
 					SSIDStorageState::CantReadSSID => {
 						ui.set_launcher_state_message("Error parsing SSID? You can generate a new one below!".into());
 					},
+					SSIDStorageState::RomSizeMismatch => {
+						ui.set_launcher_state_message("SSID file is the wrong size so MAME will refuse to start. You can generate a new one below!".into());
+					},
+					SSIDStorageState::RomHashMismatch => {
+						ui.set_launcher_state_message("SSID doesn't match the hash MAME expects. MAME will warn about this but should still start.".into());
+					},
 				}
 			}
 		} else {
@@ -1453,10 +1502,9 @@ fn populate_selected_box_ssids(ui_weak: &slint::Weak<MainWindow>, config: &Launc
 		let selectable_ssid_manufactures: slint::VecModel<HintedItem> = Default::default();
 		let mut ssid_manufacture_matched = false;
 		let mut first_ssid_manufacture = SSIDManufacture::Generic;
+		let expected_manufacture = BoxProfile::for_box(&machine_name).ssid_manufacture;
 		for available_ssid_manufacture in available_ssid_manufactures.iter() {
-			if Regex::new(r"^wtv\d+sony$").unwrap().is_match(machine_name.as_str()) && available_ssid_manufacture.manufacture != SSIDManufacture::Sony {
-				continue;
-			} else if Regex::new(r"^wtv\d+phil$").unwrap().is_match(machine_name.as_str()) && available_ssid_manufacture.manufacture != SSIDManufacture::Phillips {
+			if expected_manufacture.is_some() && available_ssid_manufacture.manufacture != SSIDManufacture::from_u16(expected_manufacture.unwrap()) {
 				continue;
 			} else if selected_ssid_manufacture.manufacture == available_ssid_manufacture.manufacture {
 				ssid_manufacture_matched = true;
@@ -1668,6 +1716,45 @@ fn populate_config(ui_weak: &slint::Weak<MainWindow>) -> Result<(), Box<dyn std:
 	let config_persistent_mame = config.persistent.mame_options.clone();
 	let mame_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
 
+	struct MAMEInstallationItem {
+		pub hint: slint::SharedString,
+		pub tooltip: slint::SharedString,
+		pub value: slint::SharedString
+	}
+
+	let mame_installations = config_persistent_paths.mame_installations.clone().unwrap_or(vec![]);
+	let mut mame_installation_items: Vec<MAMEInstallationItem> = vec![];
+	let mut selected_mame_installation: String = "".into();
+	let mut remembered_box = config_persistent_mame.selected_box.clone();
+	for mame_installation in mame_installations.iter() {
+		let installation_mame_path = Paths::resolve_mame_path(Some(mame_installation.mame_path.clone()));
+
+		let hint = if !Path::new(&installation_mame_path).exists() {
+			"Executable not found".to_string()
+		} else {
+			LauncherConfig::get_mame_build(&installation_mame_path).unwrap_or("Unknown version".into())
+		};
+
+		mame_installation_items.push(
+			MAMEInstallationItem {
+				hint: hint.into(),
+				tooltip: mame_installation.mame_path.clone().into(),
+				value: mame_installation.name.clone().into()
+			}
+		);
+
+		if Some(mame_installation.mame_path.clone()) == config_persistent_paths.mame_path {
+			selected_mame_installation = mame_installation.name.clone();
+
+			match mame_installation.selected_box.clone() {
+				Some(selected_box) => {
+					remembered_box = Some(selected_box);
+				},
+				_ => { }
+			}
+		}
+	}
+
 	let ui_weak_cpy = ui_weak.clone();
 	let _ = ui_weak.upgrade_in_event_loop(move |ui| {
 		let ui_paths = ui.global::<UIPaths>();
@@ -1715,6 +1802,27 @@ fn populate_config(ui_weak: &slint::Weak<MainWindow>) -> Result<(), Box<dyn std:
 		ui_paths.set_last_opened_rom_path(config_persistent_paths.last_opened_rom_path.unwrap_or("".into()).into());
 		ui_paths.set_last_opened_img_path(config_persistent_paths.last_opened_img_path.unwrap_or("".into()).into());
 
+		////
+		//
+		// Setup->MAME Installations
+		//
+		////
+
+		let selectable_mame_installations: slint::VecModel<HintedItem> = Default::default();
+		for mame_installation_item in mame_installation_items.iter() {
+			selectable_mame_installations.push(
+				HintedItem {
+					hint: mame_installation_item.hint.clone(),
+					tooltip: mame_installation_item.tooltip.clone(),
+					value: mame_installation_item.value.clone(),
+					icon: slint::Image::from_rgb8(slint::SharedPixelBuffer::new(0, 0))
+				}
+			);
+		}
+
+		ui_paths.set_selectable_mame_installations(slint::ModelRc::new(slint::VecModel::from(selectable_mame_installations)));
+		ui_paths.set_selected_mame_installation(selected_mame_installation.into());
+
 		////
 		//
 		// Start->Selected Modem
@@ -1839,125 +1947,72 @@ fn populate_config(ui_weak: &slint::Weak<MainWindow>) -> Result<(), Box<dyn std:
 	//
 	////
 
-	struct SortableHintedItem {
+	struct BoxItem {
 		pub hint: slint::SharedString,
 		pub tooltip: slint::SharedString,
 		pub value: slint::SharedString,
-		pub icon: String,
-		pub sort_value: String
+		pub icon: String
 	}
 
-	let mut boxes: Vec<SortableHintedItem> = vec![];
+	let mut boxes: Vec<BoxItem> = vec![];
 	let mut selected_box: String = "".into();
 	if mame_path != "" {
-		for machine in config_mame.machine.unwrap_or(vec![]).iter() {
-			if machine.runnable.clone().unwrap_or("".into()) != "no" {
-				let machine_name = 
-					machine.name
-					.clone()
-					.unwrap_or("".into());
-				let machine_description =
-					machine.description
-					.clone()
-					.unwrap_or("".into());
-
-				let mut can_connect = true;
-				for feature in machine.clone().feature.unwrap_or(vec![]).iter() {
-					if feature.ftype.clone().unwrap_or("".into()) == "lan" && feature.status.clone().unwrap_or("".into()) == "unemulated" {
-						can_connect = false;
-						break;
-					}
-				}
-
-				if Regex::new(r"^wtv\d+").unwrap().is_match(machine_name.as_str()) {
-					let sort_re: Regex = Regex::new(r"^wtv(?<box_iteration>\d+)(?<box_name>.+)").unwrap();
-					let mut sort_value: String = String::new();
-
-					let mut icon_path = "".to_string();
-
-					match sort_re.captures(machine_name.as_str()) {
-						Some(matches) => {
-							// This sets up sorting so the wtv1 boxes are first, wtv2 boxes are second and sony and phillips boxes come before any other box type.
-
-							sort_value.push_str("0");
-							sort_value.push_str(&matches["box_iteration"]);
-
-							if matches["box_name"] == *"utv" || matches["box_name"] == *"uvd" {
-								icon_path = "ui/images/box-icons/utv".to_string();
-							} else if matches["box_name"] == *"wld" {
-								icon_path = "ui/images/box-icons/wld".to_string();
-							} else if matches["box_name"] == *"bfe" {
-								icon_path = "ui/images/box-icons/bfe".to_string();
-							} else if matches["box_iteration"] == *"1" {
-								icon_path = "ui/images/box-icons/wtv1".to_string();
-							} else if matches["box_iteration"] == *"2" {
-								icon_path = "ui/images/box-icons/wtv2".to_string();
-							}
+		let box_profiles = BoxProfiles::load();
 
-							if icon_path != "" {
-								if !can_connect {
-									icon_path += "-nc.svg";
-								} else {
-									icon_path += ".svg";
-								}
-							}
+		for machine in discover_boxes(&config_mame).iter() {
+			let machine_name = 
+				machine.name
+				.clone()
+				.unwrap_or("".into());
+			let machine_description =
+				machine.description
+				.clone()
+				.unwrap_or("".into());
 
-							#[cfg(target_os = "macos")]
-							{
-								let executable_dir = 
-									LauncherConfig::get_parent_from_pathbuf(env::current_exe()
-										.unwrap_or("".into()))
-										.unwrap_or("".into());
+			let mut can_connect = true;
+			for feature in machine.clone().feature.unwrap_or(vec![]).iter() {
+				if feature.ftype.clone().unwrap_or("".into()) == "lan" && feature.status.clone().unwrap_or("".into()) == "unemulated" {
+					can_connect = false;
+					break;
+				}
+			}
 
-								icon_path = executable_dir + "/" + &icon_path;
-							}
+			let mut icon_path = "".to_string();
 
+			let box_profile = box_profiles.for_box(&machine_name);
+			if box_profile.icon != "" {
+				icon_path = "ui/images/box-icons/".to_owned() + &box_profile.icon;
 
-							if matches["box_name"] == *"sony" {
-								sort_value.push_str("0");
-							} else if matches["box_name"] == *"phil" {
-								sort_value.push_str("1");
-							} else {
-								sort_value.push_str("2");
-								sort_value.push_str(&matches["box_name"]);
-							}
-						}
-						None => {
-						}
-					}
+				if !can_connect {
+					icon_path += "-nc.svg";
+				} else {
+					icon_path += ".svg";
+				}
 
-					boxes.push(
-						SortableHintedItem {
-							hint: machine_description.clone().into(),
-							tooltip: "".into(),
-							value: machine_name.clone().into(),
-							icon: icon_path.into(),
-							sort_value: sort_value.clone().into(),
-						}
-					);
+				#[cfg(target_os = "macos")]
+				{
+					let executable_dir = 
+						LauncherConfig::get_parent_from_pathbuf(env::current_exe()
+							.unwrap_or("".into()))
+							.unwrap_or("".into());
 
-					if config_persistent_mame.selected_box == machine_name.clone().into() {
-						selected_box = machine_name.into();
-					}
+					icon_path = executable_dir + "/" + &icon_path;
 				}
 			}
-		}
-		boxes.sort_by(
-			| cmp_a, cmp_b | {
-				let a_preset_order = DEFAULT_BOX_ORDER.iter().position(|&val| val == cmp_a.value.as_str()).unwrap_or(usize::MAX);
-				let b_preset_order = DEFAULT_BOX_ORDER.iter().position(|&val| val == cmp_b.value.as_str()).unwrap_or(usize::MAX);
-
-				if a_preset_order == b_preset_order {
-					if cmp_a.sort_value == cmp_b.sort_value {
-						cmp_a.hint.partial_cmp(&cmp_b.hint).unwrap()
-					} else {
-						cmp_a.sort_value.partial_cmp(&cmp_b.sort_value).unwrap()
-					}
-				} else {
-					a_preset_order.cmp(&b_preset_order)
+
+			boxes.push(
+				BoxItem {
+					hint: machine_description.clone().into(),
+					tooltip: "".into(),
+					value: machine_name.clone().into(),
+					icon: icon_path.into()
 				}
+			);
+
+			if remembered_box == machine_name.clone().into() {
+				selected_box = machine_name.into();
 			}
-		);
+		}
 	}
 
 
@@ -1966,11 +2021,20 @@ fn populate_config(ui_weak: &slint::Weak<MainWindow>) -> Result<(), Box<dyn std:
 			selected_box = boxes[0].value.clone().into();
 		}
 
+		let mame_capabilities = MAMECapabilities::probe(&mame_path);
+
 		let selected_box_cpy = selected_box.clone();
 		let _ = ui_weak.upgrade_in_event_loop(move |ui| {
 			let ui_mame = ui.global::<UIMAMEOptions>();
 
 			ui.set_mame_broken(false);
+
+			ui_mame.set_can_skip_info_screen(mame_capabilities.supports("skip_gameinfo"));
+			ui_mame.set_can_use_windowed_mode(mame_capabilities.supports("window"));
+			ui_mame.set_can_disable_sound(mame_capabilities.supports("sound"));
+			ui_mame.set_can_use_verbose_mode(mame_capabilities.supports("verbose"));
+			ui_mame.set_can_use_debug_mode(mame_capabilities.supports("debug"));
+			ui_mame.set_can_use_drc(mame_capabilities.supports("drc"));
 			
 			let selectable_boxes: slint::VecModel<HintedItem> = Default::default();
 			for selectable_box in boxes.iter() {
@@ -2074,7 +2138,7 @@ fn save_ssid(raw_ssid: [u8; 0x08], ui_weak: slint::Weak<MainWindow>, is_blocking
 
 		let config_persistent_paths = config.persistent.paths.clone();
 		let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-		let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+		let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 
 		let mut ssid_file: String = SSID_ROM_FILE.into();
@@ -2105,28 +2169,25 @@ fn save_ssid(raw_ssid: [u8; 0x08], ui_weak: slint::Weak<MainWindow>, is_blocking
 			}
 		}
 
-		let ssid_directory_path = mame_directory_path.clone() + "/roms/" + &selected_box;
-		let ssid_file_path = ssid_directory_path.clone() + "/" + &ssid_file.clone();
-
-		match std::fs::create_dir_all(ssid_directory_path) {
-			Ok(_) => {
-				match File::create(ssid_file_path) {
-					Ok(mut f) => {
-						let _ = f.write(&raw_ssid);
-					},
-					_ => {
-						// Problem creating destination.
-					}
-				};
-			},
-			_ => {
-				// Problem creating destination path.
-			}
-		}
+		let save_result = romset::write_rom_file(&mame_directories, &selected_box, &ssid_file, |ssid_directory_path, ssid_file_path| {
+			write_ssid_file(&ssid_directory_path, &ssid_file_path, &raw_ssid)
+		});
 
 		disable_loading(&ui_weak);
 
-		let _ = save_config(ui_weak.clone(), true, None, None, None);
+		match save_result {
+			Err(e) => {
+				let _ = LauncherConfig::log_error("Couldn't save the SSID", e.as_ref());
+
+				let _ = save_config_with_message(ui_weak.clone(), "Couldn't save the SSID: ".to_owned() + &e.to_string());
+			},
+			Ok((_, RomsetWrite::LooseFile)) => {
+				let _ = save_config(ui_weak.clone(), true, None, None, None);
+			},
+			Ok((_, romset_write)) => {
+				let _ = save_config_with_message(ui_weak.clone(), "SSID saved. ".to_owned() + &romset_write.to_message());
+			}
+		};
 	});
 
 	if is_blocking {
@@ -2136,7 +2197,122 @@ fn save_ssid(raw_ssid: [u8; 0x08], ui_weak: slint::Weak<MainWindow>, is_blocking
 	Ok(())
 }
 
-fn import_bootrom(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_source: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn write_ssid_file(ssid_directory_path: &String, ssid_file_path: &String, raw_ssid: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+	std::fs::create_dir_all(ssid_directory_path).map_err(|e| LauncherError::from_io_error(ssid_directory_path, &e))?;
+
+	let mut f = File::create(ssid_file_path).map_err(|e| LauncherError::from_io_error(ssid_file_path, &e))?;
+	f.write_all(raw_ssid).map_err(|e| LauncherError::from_io_error(ssid_file_path, &e))?;
+
+	Ok(())
+}
+
+// Looks at a build before it's imported. Builds that can't work where they're going are refused with the reason why.
+// Anything that only looks wrong is returned as a warning so the user can decide. A storage size of 0 isn't checked.
+fn check_build_for_import(source_path: &String, selected_box: &String, is_bootrom: bool, storage_type: BuildStorageType, storage_size: u64) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+	let mut warnings: Vec<String> = vec![];
+
+	let classification = BuildMeta::classify_build(source_path.clone(), &BoxProfiles::load().get_build_address_ranges()).map_err(|e| LauncherError::with_file_path(source_path, e))?;
+
+	if classification.file_size < 0x40 {
+		return Err("The file is too small to be a WebTV build.".into());
+	} else if storage_size > 0 && classification.file_size > storage_size {
+		return Err(Box::new(LauncherError::WrongSize { file_path: source_path.clone(), expected_size: storage_size, found_size: classification.file_size }));
+	} else if !classification.build_header.starts_with_branch() {
+		return Err("The file doesn't start like a WebTV build. Is it compressed or a flash dump?".into());
+	}
+
+	let build_size = (classification.build_header.build_dword_length as u64) * 4;
+	if storage_size > 0 && build_size > storage_size {
+		return Err(("The build is ".to_owned() + &format!("0x{:x}", build_size) + " bytes which won't fit in the " + &format!("0x{:x}", storage_size) + " bytes this box has for it.").into());
+	}
+
+	let uses_disk = storage_type == BuildStorageType::DiskBuild || storage_type == BuildStorageType::FlashdiskBuild;
+	let unknown_warning = "Couldn't tell where this build runs from. It may not be for this box.".to_string();
+
+	if is_bootrom {
+		match classification.kind {
+			BuildKind::BootROM | BuildKind::ClassicBootROM => { },
+			BuildKind::UnknownBuild => warnings.push(unknown_warning),
+			_ => return Err("This is an AppROM, not a BootROM. Import it as an AppROM instead.".into())
+		};
+	} else {
+		match classification.kind {
+			BuildKind::BootROM | BuildKind::ClassicBootROM => {
+				return Err("This is a BootROM, not an AppROM. Import it as a BootROM instead.".into());
+			},
+			BuildKind::FlashAppROM => {
+				if uses_disk {
+					return Err("This AppROM runs from flash but this box loads its AppROM from a disk.".into());
+				}
+			},
+			BuildKind::DiskAppROM => {
+				if !uses_disk {
+					return Err("This AppROM runs from a disk but this box loads its AppROM from flash.".into());
+				}
+			},
+			BuildKind::FlashdiskAppROM => {
+				if storage_type != BuildStorageType::FlashdiskBuild {
+					return Err("This AppROM runs from a flashdisk but this box doesn't have one.".into());
+				}
+			},
+			BuildKind::WinCEBuild => { },
+			BuildKind::UnknownBuild => warnings.push(unknown_warning)
+		};
+	}
+
+	let box_profile = BoxProfile::for_box(selected_box);
+
+	if classification.kind == BuildKind::WinCEBuild && !box_profile.runs_wince {
+		warnings.push("This is a WinCE build but ".to_owned() + selected_box + " doesn't run WinCE.");
+	}
+
+	if classification.satellite && !box_profile.satellite {
+		warnings.push("This is a satellite build but ".to_owned() + selected_box + " isn't a satellite box.");
+	}
+
+	Ok(warnings)
+}
+
+// Asks before importing a build that looks like it doesn't belong in this box.
+fn confirm_build_import(item_name: &str, warnings: &Vec<String>) -> bool {
+	if warnings.len() == 0 {
+		return true;
+	}
+
+	MessageDialog::new()
+		.set_type(MessageType::Warning)
+		.set_title(&(item_name.to_owned() + " may not work in this box"))
+		.set_text(&(warnings.join("\n") + "\n\nImport it anyway?"))
+		.show_confirm()
+		.unwrap_or(false)
+}
+
+// Runs the pre-import check. Returns false with the reason shown if the import shouldn't go ahead.
+fn accept_build_for_import(ui: &MainWindow, item_name: &str, source_path: &String, selected_box: &String, is_bootrom: bool, storage_type: BuildStorageType, storage_size: u64) -> bool {
+	match check_build_for_import(source_path, selected_box, is_bootrom, storage_type, storage_size) {
+		Ok(warnings) => {
+			if confirm_build_import(item_name, &warnings) {
+				true
+			} else {
+				ui.set_launcher_state_message((item_name.to_owned() + " import cancelled.").into());
+
+				false
+			}
+		},
+		Err(e) => {
+			let context = "Didn't import the ".to_owned() + item_name;
+
+			let _ = LauncherConfig::log_error(&context, e.as_ref());
+
+			ui.set_launcher_state_message((context + ": " + &e.to_string()).into());
+
+			false
+		}
+	}
+}
+
+// The config is loaded when the BootROM is saved unless the caller already has it.
+fn import_bootrom(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_source: bool, config: Option<LauncherConfig>) -> Result<(), Box<dyn std::error::Error>> {
 	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
 		let ui_weak = ui.as_weak();
 
@@ -2144,19 +2320,36 @@ fn import_bootrom(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_
 		let selected_box = ui_mame.get_selected_box().to_string();
 		let try_bootrom_file: String = ui_mame.get_selected_bootrom().into();
 
+		let box_profile = BoxProfile::for_box(&selected_box);
+
+		if !accept_build_for_import(&ui, "BootROM", &source_path, &selected_box, true, BuildStorageType::MaskRomBuild, box_profile.bootrom_size) {
+			// The source is a temporary file from Rommy that nothing else will clean up.
+			if remove_source {
+				match std::fs::remove_file(source_path.clone()) {
+					_ => { }
+				};
+			}
+
+			return;
+		}
+
 		let _ = std::thread::spawn(move || {
 			enable_loading(&ui_weak, "Saving BootROM".into());
 
-			let config = LauncherConfig::new().unwrap();
+			let config = match config {
+				Some(config) => config,
+				_ => LauncherConfig::new().unwrap()
+			};
+			let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
 
 			let config_persistent_paths = config.persistent.paths.clone();
 			let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-			let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+			let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
 
 			let mut bootrom_file: String = "".into();
 			let mut bootrom_collation = BuildIODataCollation::Raw;
-			let bootrom_rom_size = 0x200000;
+			let bootrom_rom_size = box_profile.bootrom_size;
 
 			let mut bootroms: Vec<String> = vec![];
 
@@ -2215,222 +2408,257 @@ fn import_bootrom(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_
 				bootrom_file = bootroms[0].clone();
 			}
 
-			let mut bootrom_directory_path: String = "".into();
-			let mut bootrom_file_path: String = "".into();
+			let import_result: Result<(BuildInfo, RomsetWrite), Box<dyn std::error::Error>>;
 
-			let is_dev_box = Regex::new(r"^wtv\d+dev$").unwrap().is_match(selected_box.as_str());
+			if bootrom_file == "" && box_profile.bootrom_storage == BootROMStorage::StrippedFlash {
+				let bootrom_directory_path = mame_directories.nvram_directory.clone() + "/" + &selected_box;
+				let bootrom_file_path = bootrom_directory_path.clone() + "/" + &bootrom_file.clone() + "/" + &box_profile.bootrom_flash_prefix;
+				bootrom_collation = box_profile.flash_collation.to_collation();
 
-			if bootrom_file == "" && is_dev_box {
-				bootrom_directory_path = mame_directory_path.clone() + "/nvram/" + &selected_box;
-				bootrom_file_path = bootrom_directory_path.clone() + "/" + &bootrom_file.clone() + "/" + &BOOTROM_FLASH_FILE_PREFIX;
-				bootrom_collation = BuildIODataCollation::StrippedROMs;
+				import_result =
+					import_bootrom_file(&source_path, bootrom_directory_path, bootrom_file_path, bootrom_collation, bootrom_rom_size, validate_checksums)
+					.map(|build_info| (build_info, RomsetWrite::LooseFile));
 			} else if bootrom_file != "" {
-				bootrom_directory_path = mame_directory_path.clone() + "/roms/" + &selected_box;
-				bootrom_file_path = bootrom_directory_path.clone() + "/" + &bootrom_file.clone();
-				bootrom_collation = BuildIODataCollation::Raw;
+				// Mask ROM bootroms are part of the box's romset which may be zipped.
+				import_result = romset::write_rom_file(&mame_directories, &selected_box, &bootrom_file, |bootrom_directory_path, bootrom_file_path| {
+					import_bootrom_file(&source_path, bootrom_directory_path, bootrom_file_path, bootrom_collation, bootrom_rom_size, validate_checksums)
+				});
+			} else {
+				import_result = Err("Couldn't find where to put the BootROM for this box.".into());
 			}
 
-			if bootrom_directory_path != "" && bootrom_file_path != "" {
-				match std::fs::create_dir_all(bootrom_directory_path) {
-					Ok(_) => {
-						match ROMIO::create(bootrom_file_path, Some(bootrom_collation), bootrom_rom_size) {
-							Ok(mut destf) => {
-								match File::open(source_path.clone()) {
-									Ok(mut srcf) => {
-										let mut buffer: Vec<u8> = vec![0x00; bootrom_rom_size as usize];
-
-										let _ = srcf.read(&mut buffer);
-										let _ = destf.write(&mut buffer);
-
-										if remove_source {
-											match std::fs::remove_file(source_path.clone()) {
-												_ => { }
-											};
-										}
-									},
-									_ => {
-										// Problem opening source.
-									}
-								};
-							},
-							_ => {
-								// Problem opening destination.
-							}
-						};
-					},
-					_ => {
-						// Problem creating destination path.
-					}
-				}
+			// The source is kept if the import didn't work out.
+			if remove_source && import_result.is_ok() {
+				match std::fs::remove_file(source_path.clone()) {
+					_ => { }
+				};
 			}
 
+			let mut romset_message = "".to_string();
+
+			let message = get_import_message("BootROM", import_result.map(|(build_info, romset_write)| {
+				romset_message = romset_write.to_message();
+
+				build_info
+			}));
+
+			let message = if romset_message != "" {
+				message + " " + &romset_message
+			} else {
+				message
+			};
+
 			disable_loading(&ui_weak);
 
-			let _ = save_config(ui_weak.clone(), true, None, None, None);
+			let _ = save_config_with_message(ui_weak.clone(), message);
 		});
 	});
 
 	Ok(())
 }
 
-fn set_disk_selected_approm(selected_box: &String, file_path: &String, selected_index: u8, validate_checksums: bool) -> Result<(), Box<dyn std::error::Error>> {
-	let disk_collation = match Regex::new(r"^wtv\d+utv").unwrap().is_match(selected_box.as_str()) {
-		true => BuildIODataCollation::ByteSwapped1632,
-		false => BuildIODataCollation::ByteSwapped16,
-	};
+fn import_bootrom_file(source_path: &String, bootrom_directory_path: String, bootrom_file_path: String, bootrom_collation: BuildIODataCollation, bootrom_rom_size: u64, validate_checksums: bool) -> Result<BuildInfo, Box<dyn std::error::Error>> {
+	std::fs::create_dir_all(&bootrom_directory_path).map_err(|e| LauncherError::from_io_error(&bootrom_directory_path, &e))?;
 
-	match BuildMeta::open_disk(file_path.to_string(), Some(disk_collation), validate_checksums) {
-		Ok(mut buildmeta) => {
-			if buildmeta.selected_build_index != selected_index {
-				let _ = buildmeta.set_selected_build_index(selected_index);
-			}
-		},
-		_ => {
-			// Problem opening destination.
-		}
-	};
+	let source_collation = BuildMeta::detect_collation(source_path.clone(), BuildIODataCollation::Raw);
+	let mut buffer = BuildMeta::read_file(source_path.clone(), source_collation)?;
 
-	Ok(())
-}
+	// Anything past the end of the flash would be cut off so it's likely not a BootROM.
+	if buffer.len() > bootrom_rom_size as usize {
+		return Err(Box::new(LauncherError::WrongSize { file_path: source_path.clone(), expected_size: bootrom_rom_size, found_size: buffer.len() as u64 }));
+	}
 
-fn import_flash_approm(config: &LauncherConfig, selected_box: &String, selected_bootrom_index: usize, source_data: &mut Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-	let config_persistent_paths = config.persistent.paths.clone();
-	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let source_size = buffer.len();
+	buffer.resize(bootrom_rom_size as usize, 0x00);
 
-	let approm_directory_path: String;
-	if selected_bootrom_index > 0 {
-		approm_directory_path = mame_directory_path.clone() + "/nvram/" + &selected_box + "_" + &selected_bootrom_index.to_string();
-	} else {
-		approm_directory_path = mame_directory_path.clone() + "/nvram/" + &selected_box;
-	}
+	let mut destf = ROMIO::create(bootrom_file_path.clone(), Some(bootrom_collation), bootrom_rom_size)?;
+	destf.write(&mut buffer.clone())?;
+	destf.commit().map_err(|e| LauncherError::with_file_path(&bootrom_file_path, e))?;
 
-	let approm_file_path = approm_directory_path.clone() + "/" + APPROM1_FLASH_FILE_PREFIX;
-	let approm_collation = BuildIODataCollation::StrippedROMs;
-	let approm_rom_size;
+	BuildMeta::open_rom(bootrom_file_path, Some(bootrom_collation), validate_checksums)?
+		.verify_build(&buffer[0..source_size])
+}
 
-	let is_dev_box = Regex::new(r"^wtv\d+dev$").unwrap().is_match(selected_box.as_str());
-	let is_pal_box = Regex::new(r"^wtv\d+pal$").unwrap().is_match(selected_box.as_str());
+fn set_disk_selected_approm(selected_box: &String, file_path: &String, diff_file_path: &String, selected_index: u8, validate_checksums: bool) -> Result<(), Box<dyn std::error::Error>> {
+	let disk_collation = get_disk_collation(selected_box, file_path);
 
-	if is_dev_box || is_pal_box {
-		approm_rom_size = 0x400000;
-	} else {
-		approm_rom_size = 0x200000;
-	}
+	let mut buildmeta = BuildMeta::open_disk_image_writable(file_path.to_string(), diff_file_path.to_string(), Some(disk_collation), validate_checksums)?;
 
-	if approm_directory_path != "" && approm_file_path != "" {
-		match std::fs::create_dir_all(approm_directory_path) {
-			Ok(_) => {
-				match ROMIO::create(approm_file_path, Some(approm_collation), approm_rom_size) {
-					Ok(mut destf) => {
-						let _ = destf.seek(0);
-						let _ = destf.write(source_data);
-					},
-					_ => {
-						// Problem opening destination.
-					}
-				};
-			},
-			_ => {
-				// Problem creating destination path.
-			}
-		}
+	if buildmeta.selected_build_index != selected_index {
+		buildmeta.set_selected_build_index(selected_index)?;
 	}
 
 	Ok(())
 }
 
-fn import_disk_approm(selected_box: &String, file_path: &String, source_data: &mut Vec<u8>, validate_checksums: bool) -> Result<(), Box<dyn std::error::Error>> {
-	let disk_collation = match Regex::new(r"^wtv\d+utv").unwrap().is_match(selected_box.as_str()) {
-		true => BuildIODataCollation::ByteSwapped1632,
-		false => BuildIODataCollation::ByteSwapped16,
-	};
+fn import_flash_approm(config: &LauncherConfig, selected_box: &String, selected_bootrom_index: usize, source_data: &mut Vec<u8>, source_size: usize) -> Result<BuildInfo, Box<dyn std::error::Error>> {
+	let config_persistent_paths = config.persistent.paths.clone();
+	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
-	match BuildMeta::open_disk(file_path.to_string(), Some(disk_collation), validate_checksums) {
-		Ok(mut buildmeta) => {
-			let _ = buildmeta.write_build(source_data);
-		},
-		_ => {
-			// Problem opening destination.
-		}
-	};
+	let approm_directory_path = get_box_nvram_directory(&mame_directories, selected_box, selected_bootrom_index);
 
-	Ok(())
+	let box_profile = BoxProfile::for_box(selected_box);
+
+	let approm_file_path = approm_directory_path.clone() + "/" + &box_profile.approm_flash_prefix;
+	let approm_collation = box_profile.flash_collation.to_collation();
+	let approm_rom_size = box_profile.approm_size;
+
+	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
+
+	std::fs::create_dir_all(approm_directory_path)?;
+
+	let mut destf = ROMIO::create(approm_file_path.clone(), Some(approm_collation), approm_rom_size)?;
+	let _ = destf.seek(0);
+	destf.write(&mut source_data.clone())?;
+	destf.commit()?;
+
+	BuildMeta::open_rom(approm_file_path, Some(approm_collation), validate_checksums)?
+		.verify_build(&source_data[0..source_size.min(approm_rom_size as usize)])
+}
+
+fn import_disk_approm(selected_box: &String, file_path: &String, diff_file_path: &String, source_data: &mut Vec<u8>, source_size: usize, validate_checksums: bool) -> Result<BuildInfo, Box<dyn std::error::Error>> {
+	let disk_collation = get_disk_collation(selected_box, file_path);
+
+	// Collations are converted in place so the source is kept as-is for the check afterwards.
+	BuildMeta::open_disk_image_writable(file_path.to_string(), diff_file_path.to_string(), Some(disk_collation), validate_checksums)?
+		.write_build(&mut source_data.clone())?;
+
+	BuildMeta::open_disk_image(file_path.to_string(), diff_file_path.to_string(), Some(disk_collation), validate_checksums)?
+		.verify_build(&source_data[0..source_size])
 }
 
-fn import_flashdisk_approm(config: &LauncherConfig, selected_box: &String, selected_bootrom_index: usize, size: u64, source_data: &mut Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+fn import_flashdisk_approm(config: &LauncherConfig, selected_box: &String, selected_bootrom_index: usize, geometry: MDOCGeometry, source_data: &mut Vec<u8>, source_size: usize) -> Result<BuildInfo, Box<dyn std::error::Error>> {
 	let config_persistent_paths = config.persistent.paths.clone();
 	let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-	let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+	let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
-	let disk_directory_path: String;
-	if selected_bootrom_index > 0 {
-		disk_directory_path = mame_directory_path.clone() + "/nvram/" + &selected_box + "_" + &selected_bootrom_index.to_string();
+	let disk_directory_path = get_box_nvram_directory(&mame_directories, selected_box, selected_bootrom_index);
+	let disk_file_path = get_flashdisk_path(&mame_directories, selected_box, selected_bootrom_index);
+
+	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
+
+	if Path::new(&disk_file_path).exists() {
+		BuildMeta::open_flashdisk_writable(disk_file_path.clone(), Some(BuildIODataCollation::Raw), Some(geometry), validate_checksums)?
+			.write_build(&mut source_data.clone())?;
 	} else {
-		disk_directory_path = mame_directory_path.clone() + "/nvram/" + &selected_box;
+		std::fs::create_dir_all(disk_directory_path)?;
+
+		let io = FlashdiskIO::create_flashdisk(disk_file_path.clone(), Some(BuildIODataCollation::Raw), geometry)?;
+
+		BuildMeta::new(Box::new(io), Some(BuildMetaLayout::FlashdiskLayout), validate_checksums)?
+			.write_build(&mut source_data.clone())?;
 	}
 
-	let disk_file_path = disk_directory_path.clone() + "/mdoc_flash0";
+	BuildMeta::open_flashdisk(disk_file_path, Some(BuildIODataCollation::Raw), Some(geometry), validate_checksums)?
+		.verify_build(&source_data[0..source_size])
+}
 
-	let validate_checksums = config.persistent.mame_options.validate_checksums.unwrap_or(true);
+// What's shown once an import has been written and read back.
+fn get_import_message(item_name: &str, import_result: Result<BuildInfo, Box<dyn std::error::Error>>) -> String {
+	match import_result {
+		Ok(build_info) => {
+			let mut message = item_name.to_owned()
+				+ " imported and verified. Build " + &build_info.build_header.build_version.to_string()
+				+ ", code checksum " + &format!("0x{:08x}", build_info.build_header.code_checksum);
 
-	if Path::new(&disk_file_path).exists() {
-		match BuildMeta::open_flashdisk(disk_file_path, Some(BuildIODataCollation::Raw), validate_checksums) {
-			Ok(mut buildmeta) => {
-				let _ = buildmeta.write_build(source_data);
-			},
-			_ => {
-				// Problem opening destination.
-			}
-		};
-	} else if disk_directory_path != "" && disk_file_path != "" {
-		match std::fs::create_dir_all(disk_directory_path) {
-			Ok(_) => {
-				match FlashdiskIO::create(disk_file_path, Some(BuildIODataCollation::Raw), size) {
-					Ok(io) => {
-						match BuildMeta::new(io, Some(BuildMetaLayout::FlashdiskLayout), validate_checksums) {
-							Ok(mut buildmeta) => {
-								let _ = buildmeta.write_build(source_data);
-							},
-							_ => {
-								// Problem opening destination.
-							}
-						};
-					},
-					_ => {
-						// Problem opening destination.
-					}
-				};
-			},
-			_ => {
-				// Problem creating destination path.
+			if build_info.romfs_header.romfs_dword_length > 0 {
+				message += &(", ROMFS checksum ".to_owned() + &format!("0x{:08x}", build_info.romfs_header.romfs_checksum));
 			}
+
+			message + "."
+		},
+		Err(e) => {
+			let context = "Couldn't import the ".to_owned() + item_name;
+
+			let _ = LauncherConfig::log_error(&context, e.as_ref());
+
+			context + ": " + &e.to_string()
 		}
 	}
+}
+
+// Reloading the config clears the state message so the import message is shown after the reload is done.
+fn save_config_with_message(ui_weak: slint::Weak<MainWindow>, message: String) -> Result<(), Box<dyn std::error::Error>> {
+	save_config(ui_weak.clone(), false, None, None, None)?;
+
+	let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+		let ui_weak = ui.as_weak();
+
+		let _ = std::thread::spawn(move || {
+			let _ = populate_config(&ui_weak);
+
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				ui.set_launcher_state_message(message.into());
+			});
+		});
+	});
 
 	Ok(())
 }
 
-fn get_flashdisk_size(selected_machine: &MAMEMachineNode) -> Result<usize, Box<dyn std::error::Error>> {
-	if selected_machine.device_ref.iter().count() > 0 {
-		for device_ref in selected_machine.device_ref.clone().unwrap_or(vec![]).iter() {
-			let device_ref_name = device_ref.name.clone().unwrap_or("".to_string());
-			if device_ref_name == "mdoc_2810_0016" {
-				return Ok(16 * 1024 * 1024);
-			} else if device_ref_name == "mdoc_2810_0008" {
-				return Ok(8 * 1024 * 1024);
-			} else if device_ref_name == "mdoc_2810_0004" {
-				return Ok(4 * 1024 * 1024);
-			} else if device_ref_name == "mdoc_2810_0002" {
-				return Ok(2 * 1024 * 1024);
-			}
-		}
+fn get_flashdisk_geometry(selected_machine: &MAMEMachineNode) -> MDOCGeometry {
+	for device_ref in selected_machine.device_ref.clone().unwrap_or(vec![]).iter() {
+		let device_ref_name = device_ref.name.clone().unwrap_or("".to_string());
+
+		match MDOCGeometry::from_device_name(&device_ref_name) {
+			Some(geometry) => {
+				return geometry;
+			},
+			_ => { }
+		};
 	}
 
-	Ok(DEFAULT_FLASHDISK_SIZE as usize)
+	MDOCGeometry::from_usr_size(DEFAULT_FLASHDISK_SIZE)
+}
+
+// Each bootrom after the first gets its own nvram folder so they don't share flash or disk data.
+fn get_box_nvram_directory(mame_directories: &MAMEDirectories, selected_box: &String, selected_bootrom_index: usize) -> String {
+	if selected_bootrom_index > 0 {
+		mame_directories.nvram_directory.clone() + "/" + selected_box + "_" + &selected_bootrom_index.to_string()
+	} else {
+		mame_directories.nvram_directory.clone() + "/" + selected_box
+	}
+}
+
+fn get_flashdisk_path(mame_directories: &MAMEDirectories, selected_box: &String, selected_bootrom_index: usize) -> String {
+	get_box_nvram_directory(mame_directories, selected_box, selected_bootrom_index) + "/" + &BoxProfile::for_box(selected_box).flashdisk_file
 }
 
-fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_source: bool, correct_checksums: bool) -> Result<(), Box<dyn std::error::Error>> {
+// Runs a flashdisk task off the UI thread behind the loading screen. The task is given the flashdisk path and a failure is shown in the status message.
+fn spawn_flashdisk_task<F>(ui_weak: slint::Weak<MainWindow>, selected_box: String, selected_bootrom_index: usize, loading_message: &'static str, failure_message: &'static str, task: F)
+where
+	F: FnOnce(&slint::Weak<MainWindow>, String) -> Result<(), Box<dyn std::error::Error>> + Send + 'static
+{
+	let _ = std::thread::spawn(move || {
+		enable_loading(&ui_weak, loading_message.into());
+
+		let task_result = match LauncherConfig::get_persistent_config() {
+			Ok(persistent_config) => {
+				let mame_executable_path = Paths::resolve_mame_path(persistent_config.paths.mame_path.clone());
+				let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
+
+				task(&ui_weak, get_flashdisk_path(&mame_directories, &selected_box, selected_bootrom_index))
+			},
+			Err(e) => Err(e)
+		};
+
+		match task_result {
+			Err(e) => {
+				let message = failure_message.to_owned() + &e.to_string();
+				let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+					ui.set_launcher_state_message(message.into());
+				});
+			},
+			_ => { }
+		};
+
+		disable_loading(&ui_weak);
+	});
+}
+
+// The config is loaded when the AppROM is saved unless the caller already has it.
+fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_source: bool, correct_checksums: bool, config: Option<LauncherConfig>) -> Result<(), Box<dyn std::error::Error>> {
 	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
 		let ui_weak = ui.as_weak();
 
@@ -2444,25 +2672,50 @@ fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_s
 		let selected_hdimg_path: String = ui_mame.get_selected_hdimg_path().to_string();
 		let selected_hdimg_enabled = ui_mame.get_selected_hdimg_enabled();
 
+		// The disk's build area isn't known until it's opened so only flash sizes are checked here.
+		let (storage_type, storage_size) = if uses_disk_approms {
+			(BuildStorageType::DiskBuild, 0)
+		} else if uses_mdoc_approms {
+			(BuildStorageType::FlashdiskBuild, 0)
+		} else {
+			(BuildStorageType::StrippedFlashBuild, BoxProfile::for_box(&selected_box).approm_size)
+		};
+
+		if !accept_build_for_import(&ui, "AppROM", &source_path, &selected_box, false, storage_type, storage_size) {
+			if remove_source {
+				match std::fs::remove_file(source_path.clone()) {
+					_ => { }
+				};
+			}
+
+			return;
+		}
+
 		let _ = std::thread::spawn(move || {
 			enable_loading(&ui_weak, "Saving AppROM".into());
 
-			let config = LauncherConfig::new().unwrap();
+			let config = match config {
+				Some(config) => config,
+				_ => LauncherConfig::new().unwrap()
+			};
 
-			match File::open(source_path.clone()) {
-				Ok(mut srcf) => {
-					let source_size = srcf.metadata().unwrap().len();
+			let mut import_result: Result<BuildInfo, Box<dyn std::error::Error>> = Err("There's nowhere to import the AppROM to for this box.".into());
 
-					if source_size > 0 {
-						// Don't read more than 64MB
-						let mut source_data: Vec<u8> = vec![0x00; source_size.max(4000000) as usize];
+			// Dumps from readers that swap bytes are put back in the order the box uses.
+			let source_collation = BuildMeta::detect_collation(source_path.clone(), BuildIODataCollation::Raw);
 
-						let _ = srcf.seek(SeekFrom::Start(0));
-						let _ = srcf.read(&mut source_data);
+			match BuildMeta::read_file(source_path.clone(), source_collation) {
+				Ok(mut source_data) => {
+					let source_size = source_data.len() as u64;
+
+					if source_size == 0 {
+						import_result = Err("The AppROM file is empty.".into());
+					} else {
+						source_data.resize(source_size.max(4000000) as usize, 0x00);
 
 						// This serves as a convience like it does in my WebTV Disk Editor.
 						if correct_checksums {
-							match BuildMeta::open_rom(source_path.clone(), None, correct_checksums) {
+							match BuildMeta::open_disk(source_path.clone(), Some(source_collation), correct_checksums) {
 								Ok(build_meta) => {
 									let correct_code_checksum = build_meta.build_info[0].calculated_code_checksum;
 									let correct_romfs_checksum = build_meta.build_info[0].calculated_romfs_checksum;
@@ -2488,7 +2741,7 @@ fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_s
 
 						if uses_disk_approms {
 							if selected_hdimg_enabled && selected_hdimg_path != "" {
-								let _ = import_disk_approm(&selected_box, &selected_hdimg_path, &mut source_data, correct_checksums);
+								import_result = import_disk_approm(&selected_box, &selected_hdimg_path, &"".into(), &mut source_data, source_size as usize, correct_checksums);
 							} else {
 								for machine in config.mame.machine.unwrap_or(vec![]).iter() {
 									let machine_name = 
@@ -2505,11 +2758,11 @@ fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_s
 
 													let config_persistent_paths = config.persistent.paths.clone();
 													let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-													let mame_directory_path = LauncherConfig::get_parent(mame_executable_path).unwrap_or("".into());
+													let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 									
-													let preset_img_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &disk_file;
+													let preset_img_path = romset::find_rom_file(&mame_directories, &selected_box, &disk_file);
 
-													let _ = import_disk_approm(&selected_box, &preset_img_path, &mut source_data, correct_checksums);
+													import_result = import_disk_approm(&selected_box, &preset_img_path, &get_diff_file_path(&mame_directories, &preset_img_path), &mut source_data, source_size as usize, correct_checksums);
 												},
 												_ => {
 													//
@@ -2527,33 +2780,33 @@ fn import_approm(source_path: String, ui_weak: slint::Weak<MainWindow>, remove_s
 									.unwrap_or("".into());
 
 								if machine_name == *selected_box {
-									let flashdisk_size = match get_flashdisk_size(&machine) {
-										Ok(flashdisk_size) => flashdisk_size as u64,
-										_ => DEFAULT_FLASHDISK_SIZE
-									};
+									let flashdisk_geometry = get_flashdisk_geometry(&machine);
 
-									let _ = import_flashdisk_approm(&config, &selected_box, selected_bootrom_index, flashdisk_size, &mut source_data);
+									import_result = import_flashdisk_approm(&config, &selected_box, selected_bootrom_index, flashdisk_geometry, &mut source_data, source_size as usize);
 								}
 							}
 						} else {
-							let _ = import_flash_approm(&config, &selected_box, selected_bootrom_index, &mut source_data);
+							import_result = import_flash_approm(&config, &selected_box, selected_bootrom_index, &mut source_data, source_size as usize);
 						}
 					}
 
-					if remove_source {
+					// The source is kept if the import didn't work out.
+					if remove_source && import_result.is_ok() {
 						match std::fs::remove_file(source_path.clone()) {
 							_ => { }
 						};
 					}
 				},
-				_ => {
-					// Problem opening source.
+				Err(e) => {
+					import_result = Err(e);
 				}
 			};
 
+			let message = get_import_message("AppROM", import_result);
+
 			disable_loading(&ui_weak);
 
-			let _ = save_config(ui_weak.clone(), true, None, None, None);
+			let _ = save_config_with_message(ui_weak.clone(), message);
 		});
 	});
 
@@ -2615,6 +2868,26 @@ fn save_config(ui_weak: slint::Weak<MainWindow>, reload: bool, new_bootroms: Opt
 			}
 		};
 
+		let mut mame_installations = get_mame_installations();
+
+		// The selected box belongs to the MAME that was loaded, which isn't the one in the UI if we're switching.
+		let loaded_mame_path = match LauncherConfig::get_persistent_config() {
+			Ok(config) => {
+				config.paths.mame_path.unwrap_or("".into())
+			},
+			_ => {
+				"".into()
+			}
+		};
+		let selected_box: String = ui_mame.get_selected_box().into();
+		if selected_box != "" {
+			for mame_installation in mame_installations.iter_mut() {
+				if mame_installation.mame_path == loaded_mame_path {
+					mame_installation.selected_box = Some(selected_box.clone());
+				}
+			}
+		}
+
 		let new_config = PersistentConfig {
 			paths: Paths {
 				mame_path: Some(ui_paths.get_mame_path().into()),
@@ -2622,7 +2895,8 @@ fn save_config(ui_weak: slint::Weak<MainWindow>, reload: bool, new_bootroms: Opt
 				rommy_path: Some(ui_paths.get_rommy_path().into()),
 				last_opened_exe_path: Some(ui_paths.get_last_opened_exe_path().into()),
 				last_opened_rom_path: Some(ui_paths.get_last_opened_rom_path().into()),
-				last_opened_img_path: Some(ui_paths.get_last_opened_img_path().into())
+				last_opened_img_path: Some(ui_paths.get_last_opened_img_path().into()),
+				mame_installations: Some(mame_installations)
 			},
 			mame_options: MAMEOptions {
 				selected_box: Some(ui_mame.get_selected_box().into()),
@@ -2655,6 +2929,107 @@ fn save_config(ui_weak: slint::Weak<MainWindow>, reload: bool, new_bootroms: Opt
 	Ok(())
 }
 
+fn get_mame_installations() -> Vec<MAMEInstallation> {
+	match LauncherConfig::get_persistent_config() {
+		Ok(config) => {
+			config.paths.mame_installations.unwrap_or(vec![])
+		},
+		_ => {
+			vec![]
+		}
+	}
+}
+
+// Installations are changed in the config directly. save_config keeps them from there.
+fn save_mame_installations(mame_installations: Vec<MAMEInstallation>) -> Result<(), Box<dyn std::error::Error>> {
+	let mut persistent_config = LauncherConfig::get_persistent_config()?;
+
+	persistent_config.paths.mame_installations = Some(mame_installations);
+
+	LauncherConfig::save_persistent_config(&persistent_config)
+}
+
+fn save_mame_installation(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
+	let ui_paths = ui.global::<UIPaths>();
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let name: String = ui_paths.get_selected_mame_installation().trim().into();
+	let mame_path: String = ui_paths.get_mame_path().into();
+
+	if name == "" {
+		ui.set_launcher_state_message("Type a name for this MAME installation before saving it.".into());
+
+		return Ok(());
+	} else if mame_path == "" {
+		ui.set_launcher_state_message("Choose a MAME executable before saving it as an installation.".into());
+
+		return Ok(());
+	}
+
+	let selected_box: String = ui_mame.get_selected_box().into();
+
+	// A name or executable can only be used by one installation so saving again renames or repoints it.
+	let mut mame_installations = get_mame_installations();
+	mame_installations.retain(|mame_installation| mame_installation.name != name && mame_installation.mame_path != mame_path);
+	mame_installations.push(
+		MAMEInstallation {
+			name: name.clone(),
+			mame_path: mame_path,
+			selected_box: match selected_box.as_str() {
+				"" => None,
+				_ => Some(selected_box)
+			}
+		}
+	);
+
+	ui_paths.set_selected_mame_installation(name.into());
+
+	save_mame_installations(mame_installations)?;
+
+	save_config(ui_weak.clone(), true, None, None, None)
+}
+
+fn remove_mame_installation(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
+	let ui_paths = ui.global::<UIPaths>();
+
+	let name: String = ui_paths.get_selected_mame_installation().trim().into();
+
+	let mut mame_installations = get_mame_installations();
+	mame_installations.retain(|mame_installation| mame_installation.name != name);
+
+	ui_paths.set_selected_mame_installation("".into());
+
+	save_mame_installations(mame_installations)?;
+
+	save_config(ui_weak.clone(), true, None, None, None)
+}
+
+fn select_mame_installation(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
+	let ui_paths = ui.global::<UIPaths>();
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let name: String = ui_paths.get_selected_mame_installation().into();
+
+	match get_mame_installations().iter().find(|mame_installation| mame_installation.name == name) {
+		Some(mame_installation) => {
+			if mame_installation.mame_path != ui_paths.get_mame_path().as_str() {
+				ui_paths.set_mame_path(mame_installation.mame_path.clone().into());
+
+				ui_mame.set_selected_modem_bitb_startpoint("".into());
+				ui_mame.set_selected_debug_bitb_startpoint("".into());
+
+				save_config(ui_weak.clone(), true, None, None, None)?;
+			}
+		},
+		_ => { }
+	}
+
+	Ok(())
+}
+
 fn choose_executable_file(ui_weak: slint::Weak<MainWindow>) -> Result<String, Box<dyn std::error::Error>> {
 	let ui = ui_weak.unwrap();
 	let ui_paths = ui.global::<UIPaths>();
@@ -2765,7 +3140,7 @@ fn start_bootrom_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn
 							match get_rommy_file(selected_file_path, python_path, rommy_path) {
 								Ok(rommy_file_path) => {
 									if rommy_file_path != "" {
-										let _ = import_bootrom(rommy_file_path.clone(), ui_weak_cpy, true);
+										let _ = import_bootrom(rommy_file_path.clone(), ui_weak_cpy, true, None);
 									} else {
 										ui_weak.unwrap().set_launcher_state_message("There was a problem running Rommy.".into());
 									}
@@ -2778,7 +3153,7 @@ fn start_bootrom_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn
 							disable_loading(&ui_weak);
 						});
 					} else {
-						let _ = import_bootrom(selected_file_path.clone(), ui_weak, false);
+						let _ = import_bootrom(selected_file_path.clone(), ui_weak, false, None);
 					}
 				});
 			}
@@ -2789,6 +3164,172 @@ fn start_bootrom_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn
 	Ok(())
 }
 
+fn get_machine_storage_type(machine: &MAMEMachineNode, box_profile: &BoxProfile) -> BuildStorageType {
+	match box_profile.approm_storage {
+		Some(AppROMStorage::StrippedFlash) => return BuildStorageType::StrippedFlashBuild,
+		Some(AppROMStorage::Disk) => return BuildStorageType::DiskBuild,
+		Some(AppROMStorage::Flashdisk) => return BuildStorageType::FlashdiskBuild,
+		_ => { }
+	};
+
+	for device in machine.device.clone().unwrap_or(vec![]).iter() {
+		if device.dtype.clone().unwrap_or("".to_string()) == "harddisk" {
+			return BuildStorageType::DiskBuild;
+		}
+	}
+
+	for device_ref in machine.device_ref.clone().unwrap_or(vec![]).iter() {
+		if device_ref.name.clone().unwrap_or("".to_string()) == "mdoc_collection" {
+			return BuildStorageType::FlashdiskBuild;
+		}
+	}
+
+	BuildStorageType::StrippedFlashBuild
+}
+
+fn get_build_kind_name(kind: BuildKind) -> &'static str {
+	match kind {
+		BuildKind::UnknownBuild => "unknown build",
+		BuildKind::ClassicBootROM => "classic BootROM",
+		BuildKind::BootROM => "BootROM",
+		BuildKind::FlashAppROM => "flash AppROM",
+		BuildKind::DiskAppROM => "disk AppROM",
+		BuildKind::FlashdiskAppROM => "flashdisk AppROM",
+		BuildKind::WinCEBuild => "WinCE build"
+	}
+}
+
+// The boxes MAME has that can run a build, in the order they're shown.
+fn get_compatible_boxes(config: &LauncherConfig, classification: &BuildClassification) -> Vec<String> {
+	let mut compatible_boxes: Vec<String> = vec![];
+
+	let box_profiles = BoxProfiles::load();
+
+	for machine in discover_boxes(&config.mame).iter() {
+		let box_name = machine.name.clone().unwrap_or("".into());
+		let box_profile = box_profiles.for_box(&box_name);
+		let storage_type = get_machine_storage_type(machine, &box_profile);
+
+		let is_compatible = match classification.kind {
+			BuildKind::BootROM => box_profile.bootrom_storage != BootROMStorage::NoBootROM,
+			BuildKind::ClassicBootROM => box_profile.runs_classic_bootroms,
+			BuildKind::FlashAppROM => storage_type == BuildStorageType::StrippedFlashBuild && !box_profile.runs_wince,
+			BuildKind::DiskAppROM => {
+				match storage_type {
+					BuildStorageType::DiskBuild => !box_profile.runs_wince,
+					// Disk and flashdisk builds can run from the same address so the box's own ranges decide.
+					BuildStorageType::FlashdiskBuild => AddressRange::any_contains(&box_profile.flashdisk_approm_base_addresses, classification.build_header.build_base_address),
+					_ => false
+				}
+			},
+			BuildKind::FlashdiskAppROM => storage_type == BuildStorageType::FlashdiskBuild,
+			BuildKind::WinCEBuild => box_profile.runs_wince,
+			BuildKind::UnknownBuild => false
+		};
+
+		if is_compatible && (!classification.satellite || box_profile.satellite) {
+			compatible_boxes.push(box_name);
+		}
+	}
+
+	compatible_boxes
+}
+
+// Works out what a build is then hands it to the bootrom or approm import if it fits the selected box.
+fn route_build_import(ui_weak: slint::Weak<MainWindow>, config: &LauncherConfig, source_path: String, remove_source: bool, selected_box: String, validate_checksums: bool) -> Result<(), Box<dyn std::error::Error>> {
+	let classification = BuildMeta::classify_build(source_path.clone(), &BoxProfiles::load().get_build_address_ranges()).map_err(|e| LauncherError::with_file_path(&source_path, e))?;
+
+	if classification.kind == BuildKind::UnknownBuild {
+		return Err("Couldn't tell what kind of build this is. Use the BootROM or AppROM import instead.".into());
+	}
+
+	let compatible_boxes = get_compatible_boxes(config, &classification);
+	let kind_name = get_build_kind_name(classification.kind);
+
+	if compatible_boxes.len() == 0 {
+		return Err(("This is a ".to_owned() + kind_name + " but none of the boxes MAME has can run it.").into());
+	} else if !compatible_boxes.contains(&selected_box) {
+		return Err(("This is a ".to_owned() + kind_name + " for " + &compatible_boxes.join(", ") + ". Select one of those boxes and import it again.").into());
+	}
+
+	match classification.kind {
+		BuildKind::BootROM | BuildKind::ClassicBootROM => {
+			import_bootrom(source_path, ui_weak, remove_source, Some(config.clone()))
+		},
+		_ => {
+			import_approm(source_path, ui_weak, remove_source, validate_checksums, Some(config.clone()))
+		}
+	}
+}
+
+fn start_build_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	match choose_build_file(ui_weak.clone()) {
+		Ok(selected_file_path) => {
+			if selected_file_path != "" {
+				let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+					let ui_weak: slint::Weak<MainWindow> = ui.as_weak();
+
+					let ui_paths = ui.global::<UIPaths>();
+					let ui_mame = ui.global::<UIMAMEOptions>();
+
+					let rommy_enabled = ui_paths.get_rommy_enabled();
+					let python_path: String = ui_paths.get_python_path().into();
+					let rommy_path: String = ui_paths.get_rommy_path().into();
+					let selected_box = ui_mame.get_selected_box().to_string();
+					let validate_checksums = ui_mame.get_validate_checksums();
+
+					let _ = std::thread::spawn(move || {
+						enable_loading(&ui_weak, "Checking Build".into());
+
+						let mut source_path = selected_file_path.clone();
+						let mut remove_source = false;
+						let mut route_result: Result<(), Box<dyn std::error::Error>> = Ok(());
+
+						if rommy_enabled && !Regex::new(r"\.(o|bin|img)$").unwrap().is_match(&selected_file_path) {
+							source_path = get_rommy_file(selected_file_path, python_path, rommy_path).unwrap_or("".into());
+							remove_source = true;
+
+							if source_path == "" {
+								route_result = Err("There was a problem running Rommy.".into());
+							}
+						}
+
+						disable_loading(&ui_weak);
+
+						if route_result.is_ok() {
+							route_result = match LauncherConfig::new() {
+								Ok(config) => route_build_import(ui_weak.clone(), &config, source_path.clone(), remove_source, selected_box, validate_checksums),
+								Err(e) => Err(e)
+							};
+
+							if route_result.is_err() && remove_source {
+								match std::fs::remove_file(source_path) {
+									_ => { }
+								};
+							}
+						}
+
+						match route_result {
+							Err(e) => {
+								let _ = LauncherConfig::log_error("Didn't import the build", e.as_ref());
+
+								let message = "Didn't import the build: ".to_owned() + &e.to_string();
+								let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+									ui.set_launcher_state_message(message.into());
+								});
+							},
+							_ => { }
+						};
+					});
+				});
+			}
+		},
+		_ => { }
+	}
+
+	Ok(())
+}
+
 fn start_approm_select(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
 	let selected_approm = ui_weak.unwrap().global::<UIMAMEOptions>().get_selected_approm();
 
@@ -2827,7 +3368,12 @@ fn start_approm_select(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn s
 					};
 
 					if selected_hdimg_path != "" {
-						let _ = set_disk_selected_approm(&selected_box.clone(), &selected_hdimg_path, selected_index, validate_checksums);
+						match set_disk_selected_approm(&selected_box.clone(), &selected_hdimg_path, &"".into(), selected_index, validate_checksums) {
+							Err(e) => {
+								let _ = LauncherConfig::log_error("Couldn't select the AppROM on the disk", e.as_ref());
+							},
+							_ => { }
+						};
 					}
 				} else {
 					for machine in config.mame.machine.unwrap_or(vec![]).iter() {
@@ -2845,11 +3391,16 @@ fn start_approm_select(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn s
 
 										let config_persistent_paths = config.persistent.paths.clone();
 										let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-										let mame_directory_path = LauncherConfig::get_parent(mame_executable_path.clone()).unwrap_or("".into());
+										let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
 
-										let preset_img_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &disk_file;
+										let preset_img_path = romset::find_rom_file(&mame_directories, &selected_box, &disk_file);
 
-										let _ = set_disk_selected_approm(&selected_box.clone(), &preset_img_path, selected_index, validate_checksums);
+										match set_disk_selected_approm(&selected_box.clone(), &preset_img_path, &get_diff_file_path(&mame_directories, &preset_img_path), selected_index, validate_checksums) {
+											Err(e) => {
+												let _ = LauncherConfig::log_error("Couldn't select the AppROM on the disk", e.as_ref());
+											},
+											_ => { }
+										};
 									},
 									_ => {
 										//
@@ -2872,102 +3423,392 @@ fn start_approm_select(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn s
 	Ok(())
 }
 
-fn start_approm_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
-	match choose_build_file(ui_weak.clone()) {
-		Ok(selected_file_path) => {
-			if selected_file_path != "" {
-				let _ = ui_weak.upgrade_in_event_loop(move |ui| {
-					let ui_weak: slint::Weak<MainWindow> = ui.as_weak();
+fn start_approm_import(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	match choose_build_file(ui_weak.clone()) {
+		Ok(selected_file_path) => {
+			if selected_file_path != "" {
+				let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+					let ui_weak: slint::Weak<MainWindow> = ui.as_weak();
+
+					let ui_paths = ui.global::<UIPaths>();
+					let ui_mame = ui.global::<UIMAMEOptions>();
+
+					let rommy_enabled = ui_paths.get_rommy_enabled();
+					let python_path: String = ui_paths.get_python_path().into();
+					let rommy_path: String = ui_paths.get_rommy_path().into();
+
+					if rommy_enabled && !Regex::new(r"\.(o|bin|img)$").unwrap().is_match(&selected_file_path) {
+						let validate_checksums = ui_mame.get_validate_checksums();
+						let ui_weak_cpy = ui_weak.clone();
+						let _ = std::thread::spawn(move || {
+							enable_loading(&ui_weak, "Running Rommy".into());
+
+							match get_rommy_file(selected_file_path, python_path, rommy_path) {
+								Ok(rommy_file_path) => {
+									if rommy_file_path != "" {
+										let _ = import_approm(rommy_file_path.clone(), ui_weak_cpy, true, validate_checksums, None);
+									} else {
+										
+									}
+								},
+								_ => {
+
+								}
+							}
+
+							disable_loading(&ui_weak);
+						});
+					} else {
+						// There's a case where someone might want to choose a flash_bank0 file since people are distributing them around.
+						// I'm not going to handle that case. Have the user choose an actual .o file.
+						let _ = import_approm(selected_file_path.clone(), ui_weak, false, ui_mame.get_validate_checksums(), None);
+					}
+				});
+			}
+		},
+		_ => { }
+	}
+
+	Ok(())
+}
+
+fn revert_approm(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = std::thread::spawn(move || {
+		enable_loading(&ui_weak, "Reverting".into());
+
+		match LauncherConfig::new() {
+			Ok(config) => {
+				let selected_box = config.persistent.mame_options.selected_box.clone().unwrap_or("".into());
+
+				for machine in config.mame.machine.unwrap_or(vec![]).iter() {
+					let machine_name = 
+						machine.name
+						.clone()
+						.unwrap_or("".into());
+
+					if machine_name == *selected_box {
+						if machine.disk.iter().count() > 0 {
+							match machine.disk.clone() {
+								Some(disks) => {
+									let disk_name = disks[0].name.clone().unwrap_or("".into());
+									let disk_file = disk_name.clone() + ".chd";
+
+									let config_persistent_paths = config.persistent.paths.clone();
+									let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
+									let mame_directories = MAMEDirectories::resolve(&mame_executable_path);
+
+									let preset_img_path = romset::find_rom_file(&mame_directories, &selected_box, &disk_file);
+
+									let diff_file_path = get_diff_file_path(&mame_directories, &preset_img_path);
+
+									if diff_file_path != "" && Path::new(&diff_file_path).exists() {
+										let _ = std::fs::rename(&diff_file_path, diff_file_path.clone() + ".bak");
+
+										let _ = load_config(ui_weak.clone());
+									}
+								},
+								_ => {
+									//
+								}
+							}
+						}
+					}
+				}
+			},
+			_ => { }
+		};
+
+		disable_loading(&ui_weak);
+	});
+
+	Ok(())
+}
+
+fn repair_flashdisk_admininfo(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+		let ui_mame = ui.global::<UIMAMEOptions>();
+		let selected_box = ui_mame.get_selected_box().to_string();
+		let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Repairing Disk Info", "Couldn't repair the flashdisk admin info: ", |ui_weak, file_path| {
+			BuildMeta::open_flashdisk_writable(file_path, Some(BuildIODataCollation::Raw), None, false)?
+				.repair_admininfo()?;
+
+			let _ = load_config(ui_weak.clone());
+
+			Ok(())
+		});
+	});
+
+	Ok(())
+}
+
+fn check_flashdisk(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+		let ui_mame = ui.global::<UIMAMEOptions>();
+		let selected_box = ui_mame.get_selected_box().to_string();
+		let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Checking Disk", "Couldn't check the flashdisk: ", |ui_weak, file_path| {
+			let issues = FlashdiskIO::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), None, true)?
+				.check()?;
+
+			let message;
+			if issues.len() > 0 {
+				message = "Found ".to_owned() + &issues.len().to_string() + " problem(s) on the flashdisk. " + &issues[0].to_string() + " Use \"Repair Disk\" to rebuild it.";
+			} else {
+				message = "No problems found on the flashdisk.".to_owned();
+			}
+
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				ui.global::<UIMAMEOptions>().set_can_repair_flashdisk(issues.len() > 0);
+				ui.set_launcher_state_message(message.into());
+			});
+
+			Ok(())
+		});
+	});
+
+	Ok(())
+}
+
+fn repair_flashdisk(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+		let ui_mame = ui.global::<UIMAMEOptions>();
+		let selected_box = ui_mame.get_selected_box().to_string();
+		let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Repairing Disk", "Couldn't repair the flashdisk: ", |ui_weak, file_path| {
+			FlashdiskIO::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), None, false)?
+				.repair()?;
+
+			let _ = load_config(ui_weak.clone());
+
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				ui.set_launcher_state_message("Rebuilt the flashdisk. A backup of the old file was saved next to it.".into());
+			});
+
+			Ok(())
+		});
+	});
+
+	Ok(())
+}
+
+fn export_flashdisk_image(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
+	let ui_paths = ui.global::<UIPaths>();
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let selected_box = ui_mame.get_selected_box().to_string();
+	let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+	let mut last_opened_img_path: String = ui_paths.get_last_opened_img_path().into();
+
+	if last_opened_img_path == "" {
+		last_opened_img_path = "~".into();
+	}
+
+	let default_image_file_name = selected_box.clone() + "_flashdisk.img";
+
+	let chooser = 
+		FileDialog::new()
+		.set_location(&last_opened_img_path)
+		.set_filename(&default_image_file_name)
+		.add_filter("Linear Flash Image", &["img", "bin"]);
+
+	let selected_file_pathbuf = chooser.show_save_single_file().unwrap_or(None);
+
+	let mut image_file_path: String = "".into();
+	match selected_file_pathbuf {
+		Some(path) => {
+			match path.to_str() {
+				Some(path_str) => {
+					image_file_path = path_str.into();
+				},
+				_ => { }
+			}
+		},
+		_ => { }
+	}
+
+	if image_file_path != "" {
+		ui_paths.set_last_opened_img_path(LauncherConfig::get_parent(image_file_path.clone()).unwrap_or("".into()).into());
+
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Exporting Disk", "Couldn't export the flashdisk: ", |ui_weak, file_path| {
+			FlashdiskIO::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), None, true)?
+				.export_linear_image(image_file_path)?;
+
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				ui.set_launcher_state_message("Exported the flashdisk.".into());
+			});
+
+			Ok(())
+		});
+	}
+
+	Ok(())
+}
+
+fn import_flashdisk_image(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
+	let ui_paths = ui.global::<UIPaths>();
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let selected_box = ui_mame.get_selected_box().to_string();
+	let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+	let mut last_opened_img_path: String = ui_paths.get_last_opened_img_path().into();
+
+	if last_opened_img_path == "" {
+		last_opened_img_path = "~".into();
+	}
+
+	let chooser = 
+		FileDialog::new()
+		.set_location(&last_opened_img_path)
+		.set_filename("".into())
+		.add_filter("Flash Image or DiskOnChip Dump", &["img", "bin", "dump"]);
+
+	let selected_file_pathbuf = chooser.show_open_single_file().unwrap_or(None);
+
+	let mut image_file_path: String = "".into();
+	match selected_file_pathbuf {
+		Some(path) => {
+			match path.to_str() {
+				Some(path_str) => {
+					image_file_path = path_str.into();
+				},
+				_ => { }
+			}
+		},
+		_ => { }
+	}
+
+	if image_file_path != "" && Path::new(&image_file_path).exists() {
+		ui_paths.set_last_opened_img_path(LauncherConfig::get_parent(image_file_path.clone()).unwrap_or("".into()).into());
 
-					let ui_paths = ui.global::<UIPaths>();
-					let ui_mame = ui.global::<UIMAMEOptions>();
+		let flashdisk_box = selected_box.clone();
+		spawn_flashdisk_task(ui.as_weak(), flashdisk_box, selected_bootrom_index, "Importing Disk", "Couldn't import the flashdisk: ", move |ui_weak, disk_file_path| {
+			let config = LauncherConfig::new()?;
 
-					let rommy_enabled = ui_paths.get_rommy_enabled();
-					let python_path: String = ui_paths.get_python_path().into();
-					let rommy_path: String = ui_paths.get_rommy_path().into();
+			let mut flashdisk_geometry = MDOCGeometry::from_usr_size(DEFAULT_FLASHDISK_SIZE);
+			for machine in config.mame.machine.unwrap_or(vec![]).iter() {
+				if machine.name.clone().unwrap_or("".into()) == selected_box {
+					flashdisk_geometry = get_flashdisk_geometry(&machine);
+				}
+			}
 
-					if rommy_enabled && !Regex::new(r"\.(o|bin|img)$").unwrap().is_match(&selected_file_path) {
-						let validate_checksums = ui_mame.get_validate_checksums();
-						let ui_weak_cpy = ui_weak.clone();
-						let _ = std::thread::spawn(move || {
-							enable_loading(&ui_weak, "Running Rommy".into());
+			let image_size = Path::new(&image_file_path).metadata().map(|metadata| metadata.len()).unwrap_or(0);
 
-							match get_rommy_file(selected_file_path, python_path, rommy_path) {
-								Ok(rommy_file_path) => {
-									if rommy_file_path != "" {
-										let _ = import_approm(rommy_file_path.clone(), ui_weak_cpy, true, validate_checksums);
-									} else {
-										
-									}
-								},
-								_ => {
+			std::fs::create_dir_all(LauncherConfig::get_parent(disk_file_path.clone())?)?;
 
-								}
-							}
+			// Dumps from hardware readers have the spare data between the pages, everything else is treated as a linear image.
+			if FlashdiskIO::is_interleaved_dump_size(image_size, &flashdisk_geometry) {
+				FlashdiskIO::import_interleaved_dump(disk_file_path, image_file_path, flashdisk_geometry)?;
+			} else {
+				let image_collation = BuildMeta::detect_collation(image_file_path.clone(), BuildIODataCollation::Raw);
 
-							disable_loading(&ui_weak);
-						});
-					} else {
-						// There's a case where someone might want to choose a flash_bank0 file since people are distributing them around.
-						// I'm not going to handle that case. Have the user choose an actual .o file.
-						let _ = import_approm(selected_file_path.clone(), ui_weak, false, ui_mame.get_validate_checksums());
-					}
-				});
+				FlashdiskIO::import_linear_image(disk_file_path, image_file_path, Some(image_collation), flashdisk_geometry)?;
 			}
-		},
-		_ => { }
+
+			let _ = load_config(ui_weak.clone());
+
+			Ok(())
+		});
 	}
 
 	Ok(())
 }
 
-fn revert_approm(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
-	let _ = std::thread::spawn(move || {
-		enable_loading(&ui_weak, "Reverting".into());
+fn edit_flashdisk_admininfo(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+		let ui_mame = ui.global::<UIMAMEOptions>();
+		let selected_box = ui_mame.get_selected_box().to_string();
+		let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
 
-		match LauncherConfig::new() {
-			Ok(config) => {
-				let selected_box = config.persistent.mame_options.selected_box.clone().unwrap_or("".into());
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Reading Disk Info", "Couldn't read the flashdisk admin info: ", |ui_weak, file_path| {
+			let allocations = BuildMeta::open_flashdisk(file_path, Some(BuildIODataCollation::Raw), None, false)?
+				.get_flashdisk_allocations();
 
-				for machine in config.mame.machine.unwrap_or(vec![]).iter() {
-					let machine_name = 
-						machine.name
-						.clone()
-						.unwrap_or("".into());
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				let ui_mame = ui.global::<UIMAMEOptions>();
 
-					if machine_name == *selected_box {
-						if machine.disk.iter().count() > 0 {
-							match machine.disk.clone() {
-								Some(disks) => {
-									let disk_name = disks[0].name.clone().unwrap_or("".into());
-									let disk_file = disk_name.clone() + ".chd";
+				ui_mame.set_admininfo_primary_nv_bytes(format!("0x{:x}", allocations.primary_nv_bytes).into());
+				ui_mame.set_admininfo_secondary_nv_bytes(format!("0x{:x}", allocations.secondary_nv_bytes).into());
+				ui_mame.set_admininfo_diag_bytes(format!("0x{:x}", allocations.diag_bytes).into());
+				ui_mame.set_admininfo_tier3_bytes(format!("0x{:x}", allocations.tier3_bytes).into());
+				ui_mame.set_admininfo_select_bytes(format!("0x{:x}", allocations.select_bytes).into());
+				ui_mame.set_admininfo_browser1_bytes(format!("0x{:x}", allocations.browser1_bytes).into());
+				ui_mame.set_admininfo_browser_bytes(format!("0x{:x}", allocations.browser_bytes).into());
+				ui_mame.set_admininfo_browser_size(format!("0x{:x}", allocations.browser_size).into());
+				ui_mame.set_editing_admininfo(true);
+			});
 
-									let config_persistent_paths = config.persistent.paths.clone();
-									let mame_executable_path = Paths::resolve_mame_path(config_persistent_paths.mame_path.clone());
-									let mame_directory_path = LauncherConfig::get_parent(mame_executable_path.clone()).unwrap_or("".into());
+			Ok(())
+		});
+	});
 
-									let preset_img_path = mame_directory_path.clone() + "/roms/" + &selected_box + "/" + &disk_file;
+	Ok(())
+}
 
-									let diff_file_path = CompressedHunkDiskIO::find_diff_file(preset_img_path.clone()).unwrap_or("".into());
+// Sizes can be typed in hex (0x...) or decimal.
+fn parse_admininfo_size(name: &str, value: &str) -> Result<u32, Box<dyn std::error::Error>> {
+	let value = value.trim();
 
-									if diff_file_path != "" && Path::new(&diff_file_path).exists() {
-										let _ = std::fs::rename(&diff_file_path, diff_file_path.clone() + ".bak");
+	let parse_result = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+		Some(hex_value) => u32::from_str_radix(hex_value, 16),
+		None => value.parse::<u32>()
+	};
 
-										let _ = load_config(ui_weak.clone());
-									}
-								},
-								_ => {
-									//
-								}
-							}
-						}
-					}
-				}
-			},
-			_ => { }
+	match parse_result {
+		Ok(size) => Ok(size),
+		Err(_) => Err(format!("'{}' isn't a valid size for {}.", value, name).into())
+	}
+}
+
+fn save_flashdisk_admininfo(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
+	let _ = ui_weak.upgrade_in_event_loop(move |ui: MainWindow| {
+		let ui_mame = ui.global::<UIMAMEOptions>();
+		let selected_box = ui_mame.get_selected_box().to_string();
+		let selected_bootrom_index: usize = ui_mame.get_selected_bootrom_index() as usize;
+
+		let allocations = (|| -> Result<FlashdiskAllocations, Box<dyn std::error::Error>> {
+			Ok(FlashdiskAllocations {
+				primary_nv_bytes: parse_admininfo_size("primary NV", &ui_mame.get_admininfo_primary_nv_bytes())?,
+				secondary_nv_bytes: parse_admininfo_size("secondary NV", &ui_mame.get_admininfo_secondary_nv_bytes())?,
+				diag_bytes: parse_admininfo_size("diag", &ui_mame.get_admininfo_diag_bytes())?,
+				tier3_bytes: parse_admininfo_size("tier3", &ui_mame.get_admininfo_tier3_bytes())?,
+				select_bytes: parse_admininfo_size("select", &ui_mame.get_admininfo_select_bytes())?,
+				browser1_bytes: parse_admininfo_size("browser1", &ui_mame.get_admininfo_browser1_bytes())?,
+				browser_bytes: parse_admininfo_size("browser", &ui_mame.get_admininfo_browser_bytes())?,
+				browser_size: parse_admininfo_size("browser size", &ui_mame.get_admininfo_browser_size())?
+			})
+		})();
+
+		let allocations = match allocations {
+			Ok(allocations) => allocations,
+			Err(e) => {
+				let message = "Couldn't save the flashdisk admin info: ".to_owned() + &e.to_string();
+				ui.set_launcher_state_message(message.into());
+
+				return;
+			}
 		};
 
-		disable_loading(&ui_weak);
+		spawn_flashdisk_task(ui.as_weak(), selected_box, selected_bootrom_index, "Saving Disk Info", "Couldn't save the flashdisk admin info: ", move |ui_weak, file_path| {
+			BuildMeta::open_flashdisk_writable(file_path, Some(BuildIODataCollation::Raw), None, false)?
+				.set_flashdisk_allocations(allocations)?;
+
+			let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+				ui.global::<UIMAMEOptions>().set_editing_admininfo(false);
+				ui.set_launcher_state_message("Saved the flashdisk admin info.".into());
+			});
+
+			let _ = load_config(ui_weak.clone());
+
+			Ok(())
+		});
 	});
 
 	Ok(())
@@ -3467,7 +4308,12 @@ fn spawn_mame_from_command(ui_weak: slint::Weak<MainWindow>, debug_bitb_port: u1
 
 				output_mame_stdio(ui_weak.clone(), mame.stdout, mame.stderr).unwrap_or(false)
 			},
-			Err(_) => false
+			Err(e) => {
+				let _ = LauncherConfig::log_error("Couldn't start MAME", &e);
+				let _ = add_console_text(ui_weak.clone(), "ERROR: Couldn't start MAME: ".to_owned() + &LauncherError::from_io_error(&mame_command.get_program().to_str().unwrap_or("".into()).to_string(), &e).to_string() + "\n", MAMEConsoleScrollMode::ForceScroll, 0);
+
+				false
+			}
 		};
 
 		let _= set_mame_pid(ui_weak.clone(), 0);
@@ -3493,152 +4339,233 @@ fn spawn_mame_from_command(ui_weak: slint::Weak<MainWindow>, debug_bitb_port: u1
 	Ok(())
 }
 
-fn start_mame(ui_weak: slint::Weak<MainWindow>, drx: Receiver<String>) -> Result<(), Box<dyn std::error::Error>> {
+// Custom options MAME doesn't know about would stop it from starting and ones the launcher already sets replace the launcher's setting so we ask before trying.
+fn confirm_custom_options(mame_capabilities: &MAMECapabilities, selected_box: &String, launch_plan: &LaunchPlan) -> bool {
+	let problems = get_custom_option_problems(mame_capabilities, selected_box, launch_plan);
+
+	if problems.len() == 0 {
+		return true;
+	}
+
+	MessageDialog::new()
+		.set_type(MessageType::Warning)
+		.set_title("MAME may not start the way you expect")
+		.set_text(&(problems.join("\n\n") + "\n\nStart MAME anyway?"))
+		.show_confirm()
+		.unwrap_or(false)
+}
+
+fn get_custom_option_problems(mame_capabilities: &MAMECapabilities, selected_box: &String, launch_plan: &LaunchPlan) -> Vec<String> {
+	let mut problems = vec![];
+
+	let mame_config = LauncherConfig::get_cached_mame_config(&launch_plan.program).unwrap_or(MAMEDocument { build: None, debug: None, mameconfig: None, machine: None });
+
+	match mame_config.machine.unwrap_or(vec![]).into_iter().find(|machine| machine.name.clone().unwrap_or("".into()) == *selected_box) {
+		Some(selected_machine) => {
+			let unsupported_options = mame_capabilities.get_unsupported_options(&selected_machine, &launch_plan.custom_arguments);
+
+			if unsupported_options.len() > 0 {
+				problems.push("This MAME doesn't list these options:\n".to_owned() + &unsupported_options.join("\n"));
+			}
+		},
+		_ => { }
+	};
+
+	let conflicting_options = mame_capabilities.get_conflicting_options(&launch_plan.arguments, &launch_plan.custom_arguments);
+
+	if conflicting_options.len() > 0 {
+		problems.push("These options replace ones the launcher sets from your selections (like -bios for the BootROM):\n".to_owned() + &conflicting_options.join("\n"));
+	}
+
+	problems
+}
+
+// The options as they are in the UI. They may not have been saved yet.
+fn get_ui_mame_options(ui: &MainWindow) -> MAMEOptions {
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let selected_box: String = ui_mame.get_selected_box().into();
+
+	MAMEOptions {
+		selected_box: Some(selected_box.clone()),
+		selected_bootroms: Some(HashMap::from([(selected_box.clone(), ui_mame.get_selected_bootrom().into())])),
+		selected_modem_bitb_endpoint: Some(ui_mame.get_selected_modem_bitb_endpoint().into()),
+		selected_debug_bitb_endpoint: Some(ui_mame.get_selected_debug_bitb_endpoint().into()),
+		selected_hdimg_paths: Some(HashMap::from([(selected_box.clone(), ui_mame.get_selected_hdimg_path().into())])),
+		selected_hdimg_enabled: Some(HashMap::from([(selected_box.clone(), ui_mame.get_selected_hdimg_enabled())])),
+		verbose_mode: Some(ui_mame.get_verbose_mode()),
+		windowed_mode: Some(ui_mame.get_windowed_mode()),
+		use_drc: Some(ui_mame.get_use_drc()),
+		debug_mode: Some(ui_mame.get_debug_mode()),
+		skip_info_screen: Some(ui_mame.get_skip_info_screen()),
+		validate_checksums: Some(ui_mame.get_validate_checksums()),
+		console_input: Some(ui_mame.get_console_input()),
+		disable_sound: Some(ui_mame.get_disable_sound()),
+		custom_options: Some(ui_mame.get_custom_options().into())
+	}
+}
+
+fn get_launch_plan(ui: &MainWindow, mame_executable_path: &String, mame_capabilities: &MAMECapabilities) -> Result<LaunchPlan, LaunchError> {
+	let ui_mame = ui.global::<UIMAMEOptions>();
+
+	let launch_slots = LaunchSlots {
+		modem_bitb_startpoint: ui_mame.get_selected_modem_bitb_startpoint().into(),
+		debug_bitb_startpoint: ui_mame.get_selected_debug_bitb_startpoint().into()
+	};
+
+	LaunchPlan::new(
+		mame_executable_path,
+		&get_ui_mame_options(ui),
+		&BoxProfile::for_box(&ui_mame.get_selected_box().to_string()),
+		mame_capabilities,
+		&launch_slots,
+		|| LaunchPlan::reserve_debug_port()
+	)
+}
+
+// Shows the command MAME would be started with and anything wrong with the custom options. The command can be saved as a script.
+fn dry_run_mame(ui_weak: slint::Weak<MainWindow>) -> Result<(), Box<dyn std::error::Error>> {
 	let ui = ui_weak.unwrap();
+	let ui_mame = ui.global::<UIMAMEOptions>();
+	let ui_paths = ui.global::<UIPaths>();
 
-	let mame_executable_path: String = Paths::resolve_mame_path(Some(ui.global::<UIPaths>().get_mame_path().into()));
+	let mame_executable_path: String = Paths::resolve_mame_path(Some(ui_paths.get_mame_path().into()));
 
-	if mame_executable_path != "" && Path::new(&mame_executable_path).exists() {
-		let mame_directory_path = LauncherConfig::get_parent(mame_executable_path.clone()).unwrap_or("".into());
+	if mame_executable_path == "" || !Path::new(&mame_executable_path).exists() {
+		return Err(Box::new(LaunchError::MAMENotFound { mame_path: mame_executable_path }));
+	}
 
-		ui.set_mame_console_enabled(true);
-		ui.set_mame_console_text("".into());
+	let mame_capabilities = MAMECapabilities::probe(&mame_executable_path);
 
-		let ui_mame = ui.global::<UIMAMEOptions>();
+	let launch_plan = get_launch_plan(&ui, &mame_executable_path, &mame_capabilities)?;
 
-		let mut mame_command = Command::new(mame_executable_path.clone());
+	let mut dry_run_text = "MAME would be started in '".to_owned() + &launch_plan.working_directory + "' with:\n\n" + &launch_plan.get_command_line();
 
-		#[cfg(target_os = "windows")]
-		mame_command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+	for task in launch_plan.tasks.iter() {
+		match task {
+			LaunchTask::DebugListener { port } => {
+				dry_run_text += &("\n\nThe debug console would listen on 127.0.0.1:".to_owned() + &port.to_string() + ".");
+			},
+			LaunchTask::WindowInput => {
+				dry_run_text += "\n\nConsole input would be typed into MAME's window.";
+			}
+		};
+	}
 
-		mame_command.current_dir(mame_directory_path);
+	for warning in launch_plan.warnings.iter() {
+		dry_run_text += "\n\n";
+		dry_run_text += &warning.to_string();
+	}
 
-		mame_command.arg(ui_mame.get_selected_box().to_string());
+	for problem in get_custom_option_problems(&mame_capabilities, &ui_mame.get_selected_box().to_string(), &launch_plan).iter() {
+		dry_run_text += "\n\n";
+		dry_run_text += problem;
+	}
 
-		mame_command.arg("-nomouse");
+	let export_script = MessageDialog::new()
+		.set_type(MessageType::Info)
+		.set_title("MAME Dry Run")
+		.set_text(&(dry_run_text + "\n\nSave this as a script?"))
+		.show_confirm()
+		.unwrap_or(false);
 
-		if ui_mame.get_verbose_mode().into() {
-			mame_command.arg("-verbose");
-		}
+	if !export_script {
+		return Ok(());
+	}
 
-		if ui_mame.get_windowed_mode().into() {
-			mame_command.arg("-window");
-			mame_command.arg("-nomaximize");
-		}
+	let mut last_opened_exe_path: String = ui_paths.get_last_opened_exe_path().into();
 
-		if ui_mame.get_verbose_mode().into() {
-			mame_command.arg("-verbose");
-		}
+	if last_opened_exe_path == "" {
+		last_opened_exe_path = "~".into();
+	}
 
-		if ui_mame.get_use_drc().into() {
-			mame_command.arg("-drc");
-		} else {
-			mame_command.arg("-nodrc");
-		}
+	#[cfg(target_os = "windows")]
+	let (script_text, script_extension) = (launch_plan.to_batch_script(), "bat");
+	#[cfg(not(target_os = "windows"))]
+	let (script_text, script_extension) = (launch_plan.to_shell_script(), "sh");
 
-		if ui_mame.get_debug_mode().into() {
-			mame_command.arg("-debug");
-		}
+	let default_script_file_name = ui_mame.get_selected_box().to_string() + "." + script_extension;
 
-		if ui_mame.get_skip_info_screen().into() {
-			mame_command.arg("-skip_gameinfo");
-		}
+	let script_extensions = [script_extension];
 
-		if ui_mame.get_disable_sound().into() {
-			mame_command.arg("-sound").arg("none");
-		}
+	let chooser = 
+		FileDialog::new()
+		.set_location(&last_opened_exe_path)
+		.set_filename(&default_script_file_name)
+		.add_filter("Script", &script_extensions);
 
-		let mut selected_bootrom = ui_mame.get_selected_bootrom().to_string();
-		if selected_bootrom != "" && selected_bootrom != "None" && selected_bootrom != "WinCE" {
-			selected_bootrom = Regex::new(r"\.o$").unwrap().replace_all(&selected_bootrom, "").to_string();
+	let selected_file_pathbuf = chooser.show_save_single_file().unwrap_or(None);
 
-			mame_command.arg("-bios").arg(selected_bootrom);
-		}
+	let mut script_file_path: String = "".into();
+	match selected_file_pathbuf {
+		Some(path) => {
+			match path.to_str() {
+				Some(path_str) => {
+					script_file_path = path_str.into();
+				},
+				_ => { }
+			}
+		},
+		_ => { }
+	}
 
-		let custom_options: String = ui_mame.get_custom_options().to_string();
-		if custom_options != "" {
-			// EMAC: should acocunt for quoted arguments but this is good "for now"
-			mame_command.args(custom_options.split(" "));
+	if script_file_path != "" {
+		std::fs::write(&script_file_path, script_text).map_err(|e| LauncherError::from_io_error(&script_file_path, &e))?;
+
+		#[cfg(not(target_os = "windows"))]
+		{
+			use std::os::unix::fs::PermissionsExt;
+
+			let _ = std::fs::set_permissions(&script_file_path, std::fs::Permissions::from_mode(0o755));
 		}
 
-		let mut debug_bitb_port= 0;
-		if ui_mame.get_console_input().into() {
-			let found_debug_bitb = match Regex::new(r"^(?<slot_select>[^; ]+?)\; (?<bitb_select>.+?)$").unwrap().captures(ui_mame.get_selected_debug_bitb_startpoint().as_str()) {
-				Some(matches) => {
-					mame_command.arg("-".to_owned() + &matches["slot_select"]).arg("null_modem");
+		ui.set_launcher_state_message(("Saved the MAME command to '".to_owned() + &script_file_path + "'.").into());
+	}
 
-					let selected_debug_bitb_endpoint = ui_mame.get_selected_debug_bitb_endpoint();
-					let usable_debug_bitb_endpoint = match selected_debug_bitb_endpoint.as_str() {
-						DEFAULT_DEBUG_ENDPOINT | "127.0.0.1" | "localhost" | "local" => match portpicker::pick_unused_port() {
-							Some(found_port) => {
-								debug_bitb_port = found_port;
+	Ok(())
+}
 
-								&("127.0.0.1:".to_owned() + &debug_bitb_port.to_string())
-							},
-							None => {
-								let _ = add_console_text(ui_weak.clone(), "ERROR: Couldn't find an available port for debug console!\n".into(), MAMEConsoleScrollMode::ForceScroll, 0);
+fn start_mame(ui_weak: slint::Weak<MainWindow>, drx: Receiver<String>) -> Result<(), Box<dyn std::error::Error>> {
+	let ui = ui_weak.unwrap();
 
-								""
-							}
-						},
-						_ => selected_debug_bitb_endpoint.as_str()
-					};
+	let mame_executable_path: String = Paths::resolve_mame_path(Some(ui.global::<UIPaths>().get_mame_path().into()));
 
-					if Regex::new(r"^[^\:]+\:\d+$").unwrap().is_match(usable_debug_bitb_endpoint) {
-						mame_command.arg("-".to_owned() + &matches["bitb_select"]).arg("socket.".to_owned() + &usable_debug_bitb_endpoint.to_string());
-					} else {
-						mame_command.arg("-".to_owned() + &matches["bitb_select"]).arg(usable_debug_bitb_endpoint);
-					}
+	if mame_executable_path != "" && Path::new(&mame_executable_path).exists() {
+		let ui_mame = ui.global::<UIMAMEOptions>();
 
-					true
-				}
-				None => false
-			};
+		let mame_capabilities = MAMECapabilities::probe(&mame_executable_path);
 
-			if !found_debug_bitb {
-				#[cfg(target_os = "windows")]
-				mame_command.arg("-keyboardprovider").arg("win32");
-				mame_command.arg("-background_input");
+		let launch_plan = get_launch_plan(&ui, &mame_executable_path, &mame_capabilities)?;
 
-				#[cfg(target_os = "macos")]
-				unsafe {
-					let options = CFDictionaryCreateMutable(std::ptr::null_mut(), 0, std::ptr::null(), std::ptr::null());
-					if !options.is_null() {
-						CFDictionaryAddValue(options, kAXTrustedCheckOptionPrompt.as_void_ptr(), kCFBooleanTrue.as_void_ptr());
-						if !AXIsProcessTrustedWithOptions(options) {
-							let _ = add_console_text(ui_weak.clone(), " \n \nAccessibility permission not available. Console input wont be available because there's no permission to communicate with the MAME window. Please go into your settings, then select 'Privacy & Security', then select 'Accessibility', then give permission to this application.\n".to_string(), MAMEConsoleScrollMode::ForceScroll, 0);
-						}
-						CFRelease(options as *const _);
-					}
-				}
-			}
+		if launch_plan.custom_arguments.len() > 0 && !confirm_custom_options(&mame_capabilities, &ui_mame.get_selected_box().to_string(), &launch_plan) {
+			return Ok(());
 		}
 
-		let selected_modem_bitb_startpoint: String = ui_mame.get_selected_modem_bitb_startpoint().to_string();
-		let selected_modem_bitb_endpoint: String = ui_mame.get_selected_modem_bitb_endpoint().to_string();
-		if selected_modem_bitb_endpoint != "" && selected_modem_bitb_startpoint != "" {
-			match Regex::new(r"^(?<slot_select>[^; ]+?)\; (?<bitb_select>.+?)$").unwrap().captures(selected_modem_bitb_startpoint.as_str()) {
-				Some(matches) => {
-					mame_command.arg("-".to_owned() + &matches["slot_select"]).arg("null_modem");
-		
-					if Regex::new(r"^[^\:]+\:\d+$").unwrap().is_match(selected_modem_bitb_endpoint.as_str()) {
-						mame_command.arg("-".to_owned() + &matches["bitb_select"]).arg(&("socket.".to_owned() + &selected_modem_bitb_endpoint));
-					} else {
-						mame_command.arg("-".to_owned() + &matches["bitb_select"]).arg(selected_modem_bitb_endpoint);
+		ui.set_mame_console_enabled(true);
+		ui.set_mame_console_text("".into());
+
+		for warning in launch_plan.warnings.iter() {
+			let _ = LauncherConfig::log_error("Couldn't set up the debug console", warning);
+			let _ = add_console_text(ui_weak.clone(), "ERROR: ".to_owned() + &warning.to_string() + "\n", MAMEConsoleScrollMode::ForceScroll, 0);
+		}
+
+		#[cfg(target_os = "macos")]
+		if launch_plan.tasks.contains(&LaunchTask::WindowInput) {
+			unsafe {
+				let options = CFDictionaryCreateMutable(std::ptr::null_mut(), 0, std::ptr::null(), std::ptr::null());
+				if !options.is_null() {
+					CFDictionaryAddValue(options, kAXTrustedCheckOptionPrompt.as_void_ptr(), kCFBooleanTrue.as_void_ptr());
+					if !AXIsProcessTrustedWithOptions(options) {
+						let _ = add_console_text(ui_weak.clone(), " \n \nAccessibility permission not available. Console input wont be available because there's no permission to communicate with the MAME window. Please go into your settings, then select 'Privacy & Security', then select 'Accessibility', then give permission to this application.\n".to_string(), MAMEConsoleScrollMode::ForceScroll, 0);
 					}
-				}
-				None => {
+					CFRelease(options as *const _);
 				}
 			}
 		}
 
-		let selected_hdimg_path: String = ui_mame.get_selected_hdimg_path().to_string();
-		let selected_hdimg_enabled = ui_mame.get_selected_hdimg_enabled();
-		if selected_hdimg_enabled && selected_hdimg_path != "" {
-			mame_command.arg("-hard").arg(selected_hdimg_path);
-		}
-
-		let _ = spawn_mame_from_command(ui_weak, debug_bitb_port, drx, mame_command);
+		let _ = spawn_mame_from_command(ui_weak, launch_plan.get_debug_bitb_port(), drx, launch_plan.to_command());
+	} else {
+		return Err(Box::new(LaunchError::MAMENotFound { mame_path: mame_executable_path }));
 	}
 
 	Ok(())
@@ -3989,6 +4916,41 @@ fn start_ui() -> Result<(), slint::PlatformError> {
 		let _ = revert_approm(ui_weak.clone());
 	});
 
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_repair_admininfo(move || {
+		let _ = repair_flashdisk_admininfo(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_check_flashdisk(move || {
+		let _ = check_flashdisk(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_repair_flashdisk(move || {
+		let _ = repair_flashdisk(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_export_flashdisk(move || {
+		let _ = export_flashdisk_image(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_import_flashdisk(move || {
+		let _ = import_flashdisk_image(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_edit_admininfo(move || {
+		let _ = edit_flashdisk_admininfo(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_save_admininfo(move || {
+		let _ = save_flashdisk_admininfo(ui_weak.clone());
+	});
+
 	ui_weak = ui.as_weak();
 	ui.global::<UIMAMEOptions>().on_import_bootrom(move || {
 		let _ = start_bootrom_import(ui_weak.clone());
@@ -3999,6 +4961,11 @@ fn start_ui() -> Result<(), slint::PlatformError> {
 		let _ = start_approm_import(ui_weak.clone());
 	});
 
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_import_build(move || {
+		let _ = start_build_import(ui_weak.clone());
+	});
+
 	ui_weak = ui.as_weak();
 	ui.global::<UIMAMEOptions>().on_choose_hdimg(move || {
 		let _ = choose_hdimg(ui_weak.clone());
@@ -4009,13 +4976,36 @@ fn start_ui() -> Result<(), slint::PlatformError> {
 		let _ = unset_hdimg(ui_weak.clone());
 	});
 
+	ui_weak = ui.as_weak();
+	ui.global::<UIMAMEOptions>().on_dry_run_mame(move || {
+		match dry_run_mame(ui_weak.clone()) {
+			Err(e) => {
+				let message = e.to_string();
+				let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+					ui.set_launcher_state_message(message.into());
+				});
+			},
+			_ => { }
+		};
+	});
+
 	ui_weak = ui.as_weak();
 	ui.global::<UIMAMEOptions>().on_start_mame(move || {
 		enable_loading(&ui_weak, "Starting MAME".into());
 
 		let _ = check_custom_ssid(ui_weak.clone());
 
-		let _ = start_mame(ui_weak.clone(), drx.clone());
+		match start_mame(ui_weak.clone(), drx.clone()) {
+			Err(e) => {
+				let _ = LauncherConfig::log_error("Couldn't start MAME", e.as_ref());
+
+				let message = e.to_string();
+				let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+					ui.set_launcher_state_message(message.into());
+				});
+			},
+			_ => { }
+		};
 
 		disable_loading(&ui_weak);
 	});
@@ -4054,6 +5044,21 @@ fn start_ui() -> Result<(), slint::PlatformError> {
 		}
 	});
 
+	ui_weak = ui.as_weak();
+	ui.global::<UIPaths>().on_select_mame_installation(move || {
+		let _ = select_mame_installation(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIPaths>().on_save_mame_installation(move || {
+		let _ = save_mame_installation(ui_weak.clone());
+	});
+
+	ui_weak = ui.as_weak();
+	ui.global::<UIPaths>().on_remove_mame_installation(move || {
+		let _ = remove_mame_installation(ui_weak.clone());
+	});
+
 	ui_weak = ui.as_weak();
 	ui.global::<UIPaths>().on_choose_python(move || {
 		match choose_executable_file(ui_weak.clone()) {
@@ -0,0 +1,519 @@
+// By: Eric MacDonald (eMac)
+
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::{
+	write::FileOptions,
+	CompressionMethod,
+	DateTime,
+	ZipArchive,
+	ZipWriter
+};
+use crate::config::MAMEDirectories;
+use crate::wtv::buildio::write_file_atomically;
+
+// Where zipped ROMs are put so they can be looked at like loose files.
+const EXTRACTED_ROMS_DIRECTORY: &'static str = "mame_launcher_romsets";
+// Where imports into a zipped romset are built before they go into the zip.
+const ROMSET_IMPORT_DIRECTORY: &'static str = "mame_launcher_romset_imports";
+
+// Zipped ROMs already extracted. They're only extracted again if the zip changes.
+static EXTRACTED_ROM_FILES: Mutex<Vec<ExtractedRomFile>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq)]
+struct ExtractedRomFile {
+	zip_path: String,
+	file_name: String,
+	zip_modified: Option<SystemTime>,
+	zip_size: u64,
+	extracted_file_path: String
+}
+
+// A MAME romset zip. The zip is read into memory so it isn't held open while it's being replaced.
+#[allow(dead_code)]
+pub struct ZipRomset {
+	file_path: String,
+	archive: ZipArchive<Cursor<Vec<u8>>>,
+	// Names in the zip's order. Names that aren't UTF-8 have been decoded from CP437.
+	member_names: Vec<String>
+}
+
+impl ZipRomset {
+	pub fn open(file_path: &String) -> Result<ZipRomset, Box<dyn std::error::Error>> {
+		let mut archive = ZipArchive::new(Cursor::new(fs::read(file_path)?))?;
+
+		let mut member_names = vec![];
+		for member_index in 0..archive.len() {
+			member_names.push(archive.by_index_raw(member_index)?.name().to_string());
+		}
+
+		Ok(ZipRomset {
+			file_path: file_path.clone(),
+			archive: archive,
+			member_names: member_names
+		})
+	}
+
+	// Folders inside the zip are flattened like MAME does when it searches.
+	fn get_member_file_name(member_name: &String) -> Option<String> {
+		if member_name.ends_with("/") {
+			return None;
+		}
+
+		member_name.rsplit("/").next().map(|file_name| file_name.to_string())
+	}
+
+	// MAME ignores case when it looks in a zip so we do too.
+	fn find_member(&self, file_name: &String) -> Option<usize> {
+		self.member_names.iter().position(|member_name| {
+			match ZipRomset::get_member_file_name(member_name) {
+				Some(member_file_name) => member_file_name.eq_ignore_ascii_case(file_name),
+				_ => false
+			}
+		})
+	}
+
+	fn read_member(&mut self, member_index: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+		let member_name = self.member_names[member_index].clone();
+
+		let mut data = vec![];
+
+		match self.archive.by_index(member_index) {
+			Ok(mut member) => {
+				// The CRC is checked once everything's been read.
+				match member.read_to_end(&mut data) {
+					Ok(_) => { },
+					Err(e) => return Err(("'".to_owned() + &member_name + "' in the zip is corrupt. " + &e.to_string()).into())
+				};
+			},
+			Err(e) => return Err(("Couldn't read '".to_owned() + &member_name + "' in the zip. Try repacking the zip. " + &e.to_string()).into())
+		};
+
+		Ok(data)
+	}
+
+	pub fn contains(&self, file_name: &String) -> bool {
+		self.find_member(file_name).is_some()
+	}
+
+	pub fn read(&mut self, file_name: &String) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+		let member_index = self.find_member(file_name).ok_or(("'".to_owned() + file_name + "' isn't in '" + &self.file_path + "'.").as_str())?;
+
+		self.read_member(member_index)
+	}
+
+	// Replaces the file if it's already in the zip and adds it if it isn't.
+	// The zip is rewritten with everything else copied over without being recompressed.
+	pub fn write(&mut self, file_name: &String, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+		let (modified_time, modified_date) = get_dos_timestamp();
+
+		let options =
+			FileOptions::default()
+			.compression_method(CompressionMethod::Deflated)
+			.compression_level(Some(9))
+			.last_modified_time(DateTime::from_msdos(modified_date, modified_time));
+
+		let replaced_member_index = self.find_member(file_name);
+		let file_path = self.file_path.clone();
+		let archive = &mut self.archive;
+		let member_names = &self.member_names;
+
+		write_file_atomically(&file_path, |dstf| {
+			let mut writer = ZipWriter::new(dstf);
+
+			for member_index in 0..member_names.len() {
+				if Some(member_index) == replaced_member_index {
+					// The file keeps its place and folder in the zip.
+					writer.start_file(member_names[member_index].clone(), options)?;
+					writer.write_all(data)?;
+				} else {
+					writer.raw_copy_file(archive.by_index_raw(member_index)?)?;
+				}
+			}
+
+			if replaced_member_index.is_none() {
+				writer.start_file(file_name.clone(), options)?;
+				writer.write_all(data)?;
+			}
+
+			writer.finish()?;
+
+			Ok(())
+		})?;
+
+		*self = ZipRomset::open(&file_path)?;
+
+		Ok(())
+	}
+
+	// Anything already in the folder is left alone since MAME uses the loose file over the zipped one.
+	pub fn extract_all(&mut self, directory_path: &String) -> Result<(), Box<dyn std::error::Error>> {
+		fs::create_dir_all(directory_path)?;
+
+		for member_index in 0..self.member_names.len() {
+			let file_name = match ZipRomset::get_member_file_name(&self.member_names[member_index]) {
+				Some(file_name) => file_name,
+				_ => continue
+			};
+
+			let file_path = directory_path.clone() + "/" + &file_name;
+
+			if !Path::new(&file_path).exists() {
+				fs::write(&file_path, self.read_member(member_index)?)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// What happened when a file was put into a box's romset.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RomsetWrite {
+	LooseFile,
+	UpdatedZip { zip_path: String },
+	ExtractedZip { zip_path: String, directory_path: String },
+	// We can't write into a 7z. MAME uses the loose file over the one in the 7z.
+	BesideSevenZip { sevenzip_path: String }
+}
+
+impl RomsetWrite {
+	pub fn to_message(&self) -> String {
+		match self {
+			RomsetWrite::LooseFile => "".into(),
+			RomsetWrite::UpdatedZip { zip_path } => {
+				"It was written into '".to_owned() + zip_path + "'."
+			},
+			RomsetWrite::ExtractedZip { zip_path, directory_path } => {
+				"Couldn't update '".to_owned() + zip_path + "' so it was extracted to '" + directory_path + "' and the file was put there."
+			},
+			RomsetWrite::BesideSevenZip { sevenzip_path } => {
+				"7z romsets can't be changed so it was put in a folder next to '".to_owned() + sevenzip_path + "'. MAME will use it over the one in the 7z."
+			}
+		}
+	}
+}
+
+// The box's zip in the first rom path that has one. Only used if there isn't a folder for the box.
+pub fn find_zipped_romset(mame_directories: &MAMEDirectories, box_name: &String) -> Option<String> {
+	find_romset_archive(mame_directories, box_name, ".zip")
+}
+
+// We can't read 7z romsets. This is used to tell the user why their ROMs weren't found.
+pub fn find_sevenzip_romset(mame_directories: &MAMEDirectories, box_name: &String) -> Option<String> {
+	find_romset_archive(mame_directories, box_name, ".7z")
+}
+
+fn find_romset_archive(mame_directories: &MAMEDirectories, box_name: &String, extension: &str) -> Option<String> {
+	for rom_path in mame_directories.rom_paths.iter() {
+		let archive_path = rom_path.clone() + "/" + box_name + extension;
+
+		if Path::new(&archive_path).exists() {
+			return Some(archive_path);
+		}
+	}
+
+	None
+}
+
+// MAME looks for each file separately so a box's ROMs can be split across rom paths.
+// In each rom path the box's folder is checked before its zip. Zipped files are extracted to a temporary file.
+pub fn find_rom_file(mame_directories: &MAMEDirectories, box_name: &String, file_name: &String) -> String {
+	for rom_path in mame_directories.rom_paths.iter() {
+		let rom_file_path = rom_path.clone() + "/" + box_name + "/" + file_name;
+
+		if Path::new(&rom_file_path).exists() {
+			return rom_file_path;
+		}
+
+		let zip_path = rom_path.clone() + "/" + box_name + ".zip";
+
+		if Path::new(&zip_path).exists() {
+			match extract_rom_file(&zip_path, box_name, file_name) {
+				Ok(extracted_file_path) => return extracted_file_path,
+				_ => { }
+			};
+		}
+	}
+
+	mame_directories.get_rom_directory(box_name) + "/" + file_name
+}
+
+// Copies a zipped ROM out to a temporary file so it can be opened like a loose file.
+fn extract_rom_file(zip_path: &String, box_name: &String, file_name: &String) -> Result<String, Box<dyn std::error::Error>> {
+	let zip_metadata = fs::metadata(zip_path)?;
+	let zip_modified = zip_metadata.modified().ok();
+	let zip_size = zip_metadata.len();
+
+	let mut extracted_rom_files = match EXTRACTED_ROM_FILES.lock() {
+		Ok(extracted_rom_files) => extracted_rom_files,
+		Err(_e) => return Err("Couldn't check the extracted ROMs.".into())
+	};
+
+	for extracted_rom_file in extracted_rom_files.iter() {
+		if extracted_rom_file.zip_path == *zip_path && extracted_rom_file.file_name == *file_name {
+			if extracted_rom_file.zip_modified == zip_modified && extracted_rom_file.zip_size == zip_size && Path::new(&extracted_rom_file.extracted_file_path).exists() {
+				return Ok(extracted_rom_file.extracted_file_path.clone());
+			}
+		}
+	}
+
+	let mut romset = ZipRomset::open(zip_path)?;
+
+	if !romset.contains(file_name) {
+		return Err(("'".to_owned() + file_name + "' isn't in '" + zip_path + "'.").into());
+	}
+
+	let extracted_directory_path = std::env::temp_dir().join(EXTRACTED_ROMS_DIRECTORY).join(box_name).to_string_lossy().to_string();
+	fs::create_dir_all(&extracted_directory_path)?;
+
+	let extracted_file_path = extracted_directory_path + "/" + file_name;
+	fs::write(&extracted_file_path, romset.read(file_name)?)?;
+
+	// Another zip with the same box name would extract to the same place so its entry is dropped too.
+	extracted_rom_files.retain(|extracted_rom_file| extracted_rom_file.extracted_file_path != extracted_file_path);
+	extracted_rom_files.push(ExtractedRomFile {
+		zip_path: zip_path.clone(),
+		file_name: file_name.clone(),
+		zip_modified: zip_modified,
+		zip_size: zip_size,
+		extracted_file_path: extracted_file_path.clone()
+	});
+
+	Ok(extracted_file_path)
+}
+
+// Puts a file into the box's romset. write_file is given a folder and file path to write to.
+// Loose romsets are written to directly. Zipped romsets have the file built in a temporary folder then put into the zip.
+// If the zip can't be updated it's extracted to a folder next to it and the file is put there instead.
+pub fn write_rom_file<T, F>(mame_directories: &MAMEDirectories, box_name: &String, file_name: &String, write_file: F) -> Result<(T, RomsetWrite), Box<dyn std::error::Error>>
+where F: FnOnce(String, String) -> Result<T, Box<dyn std::error::Error>> {
+	let rom_directory_path = mame_directories.get_rom_directory(box_name);
+
+	if Path::new(&rom_directory_path).exists() {
+		let result = write_file(rom_directory_path.clone(), rom_directory_path + "/" + file_name)?;
+
+		return Ok((result, RomsetWrite::LooseFile));
+	}
+
+	let zip_path = match find_zipped_romset(mame_directories, box_name) {
+		Some(zip_path) => zip_path,
+		_ => {
+			let result = write_file(rom_directory_path.clone(), rom_directory_path + "/" + file_name)?;
+
+			return match find_sevenzip_romset(mame_directories, box_name) {
+				Some(sevenzip_path) => Ok((result, RomsetWrite::BesideSevenZip { sevenzip_path: sevenzip_path })),
+				_ => Ok((result, RomsetWrite::LooseFile))
+			};
+		}
+	};
+
+	let import_directory_path = std::env::temp_dir().join(ROMSET_IMPORT_DIRECTORY).join(box_name).to_string_lossy().to_string();
+	let import_file_path = import_directory_path.clone() + "/" + file_name;
+
+	let result = write_file(import_directory_path, import_file_path.clone())?;
+
+	let file_data = fs::read(&import_file_path)?;
+	let _ = fs::remove_file(&import_file_path);
+
+	let zip_result = match ZipRomset::open(&zip_path) {
+		Ok(mut romset) => romset.write(file_name, &file_data),
+		Err(e) => Err(e)
+	};
+
+	match zip_result {
+		Ok(_) => Ok((result, RomsetWrite::UpdatedZip { zip_path: zip_path })),
+		Err(_e) => {
+			// The folder goes in the same rom path as the zip so MAME finds it in the same place.
+			let zip_parent = Path::new(&zip_path).parent().map(|parent| parent.to_string_lossy().to_string()).unwrap_or(".".into());
+			let directory_path = zip_parent + "/" + box_name;
+
+			match ZipRomset::open(&zip_path) {
+				Ok(mut romset) => romset.extract_all(&directory_path)?,
+				_ => fs::create_dir_all(&directory_path)?
+			};
+
+			fs::write(directory_path.clone() + "/" + file_name, file_data)?;
+
+			Ok((result, RomsetWrite::ExtractedZip { zip_path: zip_path, directory_path: directory_path }))
+		}
+	}
+}
+
+// Zip times are in DOS format (local time in 2 second steps from 1980). We don't know the timezone so UTC is used.
+fn get_dos_timestamp() -> (u16, u16) {
+	let unix_secs =
+		SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_secs())
+		.unwrap_or(0) as i64;
+
+	let days = unix_secs.div_euclid(86400);
+	let day_secs = unix_secs.rem_euclid(86400);
+
+	// Days since 1970 to a calendar date.
+	let shifted_days = days + 719468;
+	let era = shifted_days.div_euclid(146097);
+	let day_of_era = shifted_days.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	if year < 1980 {
+		return (0x0000, 0x0021);
+	}
+
+	let dos_time = ((day_secs / 3600) << 11) | (((day_secs % 3600) / 60) << 5) | ((day_secs % 60) / 2);
+	let dos_date = ((year - 1980) << 9) | (month << 5) | day;
+
+	(dos_time as u16, dos_date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::wtv::buildio::get_test_file_path;
+
+	fn create_test_zip(file_path: &String, members: &[(&str, &[u8])]) {
+		let mut writer = ZipWriter::new(fs::File::create(file_path).unwrap());
+
+		for (member_name, data) in members.iter() {
+			writer.start_file(member_name.to_string(), FileOptions::default()).unwrap();
+			writer.write_all(data).unwrap();
+		}
+
+		writer.finish().unwrap();
+	}
+
+	fn get_test_mame_directories(rom_path: &String) -> MAMEDirectories {
+		MAMEDirectories {
+			rom_paths: vec![rom_path.clone()],
+			nvram_directory: rom_path.clone() + "/nvram",
+			diff_directory: rom_path.clone() + "/diff"
+		}
+	}
+
+	#[test]
+	fn nested_members_are_found_by_name() {
+		let zip_path = get_test_file_path("nested.zip");
+		let directory_path = get_test_file_path("nested");
+		let _ = fs::remove_dir_all(&directory_path);
+		create_test_zip(&zip_path, &[("sub/ds2401.bin", &[0x01; 8]), ("bootrom.o", &[0x02; 16])]);
+
+		let mut romset = ZipRomset::open(&zip_path).unwrap();
+		assert!(romset.contains(&"DS2401.BIN".into()));
+		assert!(!romset.contains(&"sub".into()));
+		assert_eq!(romset.read(&"ds2401.bin".into()).unwrap(), vec![0x01; 8]);
+
+		romset.extract_all(&directory_path).unwrap();
+		assert_eq!(fs::read(directory_path.clone() + "/ds2401.bin").unwrap(), vec![0x01; 8]);
+		assert_eq!(fs::read(directory_path.clone() + "/bootrom.o").unwrap(), vec![0x02; 16]);
+		assert!(!Path::new(&(directory_path.clone() + "/sub")).exists());
+
+		let _ = fs::remove_dir_all(&directory_path);
+		let _ = fs::remove_file(&zip_path);
+	}
+
+	#[test]
+	fn writes_keep_the_other_members() {
+		let zip_path = get_test_file_path("rewrite.zip");
+		create_test_zip(&zip_path, &[("sub/ds2401.bin", &[0x01; 8]), ("bootrom.o", &[0x02; 16])]);
+
+		let mut romset = ZipRomset::open(&zip_path).unwrap();
+		romset.write(&"ds2401.bin".into(), &[0x03; 8]).unwrap();
+		romset.write(&"approm.o".into(), &[0x04; 32]).unwrap();
+
+		let mut romset = ZipRomset::open(&zip_path).unwrap();
+		assert_eq!(romset.member_names, vec!["sub/ds2401.bin", "bootrom.o", "approm.o"]);
+		assert_eq!(romset.read(&"ds2401.bin".into()).unwrap(), vec![0x03; 8]);
+		assert_eq!(romset.read(&"bootrom.o".into()).unwrap(), vec![0x02; 16]);
+		assert_eq!(romset.read(&"approm.o".into()).unwrap(), vec![0x04; 32]);
+		assert!(!Path::new(&(zip_path.clone() + ".tmp")).exists());
+
+		let _ = fs::remove_file(&zip_path);
+	}
+
+	// Zips without the UTF-8 flag have CP437 names. 0x82 is é in CP437 and isn't valid UTF-8 on its own.
+	#[test]
+	fn cp437_names_survive_a_rewrite() {
+		let zip_path = get_test_file_path("cp437.zip");
+		create_test_zip(&zip_path, &[("Ax.bin", &[0x01; 8])]);
+
+		let mut zip_data = fs::read(&zip_path).unwrap();
+		for offset in 0..(zip_data.len() - 6) {
+			if &zip_data[offset..(offset + 6)] == b"Ax.bin" {
+				zip_data[offset] = 0x82;
+			}
+		}
+		fs::write(&zip_path, zip_data).unwrap();
+
+		let mut romset = ZipRomset::open(&zip_path).unwrap();
+		assert_eq!(romset.member_names, vec!["\u{e9}x.bin"]);
+
+		romset.write(&"other.bin".into(), &[0x02; 8]).unwrap();
+
+		let mut romset = ZipRomset::open(&zip_path).unwrap();
+		assert_eq!(romset.member_names, vec!["\u{e9}x.bin", "other.bin"]);
+		assert_eq!(romset.read(&"\u{e9}x.bin".into()).unwrap(), vec![0x01; 8]);
+
+		let _ = fs::remove_file(&zip_path);
+	}
+
+	#[test]
+	fn zipped_roms_are_extracted_again_when_the_zip_changes() {
+		let rom_path = get_test_file_path("zipped_roms");
+		let _ = fs::remove_dir_all(&rom_path);
+		fs::create_dir_all(&rom_path).unwrap();
+		create_test_zip(&(rom_path.clone() + "/wtvzip.zip"), &[("ds2401.bin", &[0x01; 8])]);
+
+		let mame_directories = get_test_mame_directories(&rom_path);
+
+		let extracted_file_path = find_rom_file(&mame_directories, &"wtvzip".into(), &"ds2401.bin".into());
+		assert_eq!(fs::read(&extracted_file_path).unwrap(), vec![0x01; 8]);
+
+		let (_, romset_write) = write_rom_file(&mame_directories, &"wtvzip".into(), &"ds2401.bin".into(), |directory_path, file_path| {
+			fs::create_dir_all(directory_path)?;
+			fs::write(file_path, [0x02; 16])?;
+
+			Ok(())
+		}).unwrap();
+		assert_eq!(romset_write, RomsetWrite::UpdatedZip { zip_path: rom_path.clone() + "/wtvzip.zip" });
+		assert!(!Path::new(&(rom_path.clone() + "/wtvzip")).exists());
+
+		let extracted_file_path = find_rom_file(&mame_directories, &"wtvzip".into(), &"ds2401.bin".into());
+		assert_eq!(fs::read(&extracted_file_path).unwrap(), vec![0x02; 16]);
+
+		let _ = fs::remove_dir_all(&rom_path);
+	}
+
+	#[test]
+	fn sevenzip_romsets_are_reported() {
+		let rom_path = get_test_file_path("sevenzip_roms");
+		let _ = fs::remove_dir_all(&rom_path);
+		fs::create_dir_all(&rom_path).unwrap();
+		fs::write(rom_path.clone() + "/wtv7z.7z", [0x37, 0x7a, 0xbc, 0xaf, 0x27, 0x1c]).unwrap();
+
+		let mame_directories = get_test_mame_directories(&rom_path);
+
+		assert_eq!(find_sevenzip_romset(&mame_directories, &"wtv7z".into()), Some(rom_path.clone() + "/wtv7z.7z"));
+		assert_eq!(find_zipped_romset(&mame_directories, &"wtv7z".into()), None);
+
+		let (_, romset_write) = write_rom_file(&mame_directories, &"wtv7z".into(), &"ds2401.bin".into(), |directory_path, file_path| {
+			fs::create_dir_all(directory_path)?;
+			fs::write(file_path, [0x01; 8])?;
+
+			Ok(())
+		}).unwrap();
+		assert_eq!(romset_write, RomsetWrite::BesideSevenZip { sevenzip_path: rom_path.clone() + "/wtv7z.7z" });
+		assert_eq!(fs::read(rom_path.clone() + "/wtv7z/ds2401.bin").unwrap(), vec![0x01; 8]);
+
+		let _ = fs::remove_dir_all(&rom_path);
+	}
+}
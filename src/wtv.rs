@@ -2,4 +2,7 @@
 
 pub mod buildio;
 pub mod buildmeta;
+pub mod error;
+pub mod flashdiskformat;
+pub mod romhash;
 pub mod ssid;
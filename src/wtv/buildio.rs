@@ -1,6 +1,10 @@
+use std::fs::File;
+
 pub mod romio;
 pub mod diskio;
 pub mod flashdiskio;
+pub mod mdocgeometry;
+pub mod buildiostream;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -9,13 +13,30 @@ pub enum BuildIODataCollation {
 	Raw,
 	// All bootroms and flash approms with 2 chips.
 	StrippedROMs,
+	// Boards that spread the ROM over 4 16-bit chips.
+	QuadStrippedROMs,
+	// Boards with byte-wide parts. 4 chips make up the 32-bit bus.
+	ByteStrippedROMs,
 	// 16-bit little endien
 	// All HD boxes prior to the UltimateTV. [1234=>2143]
 	ByteSwapped16,
 	// 16-bit little endien step with 32-bit little endien step
 	// Data on the UltimateTV's HD (possibly other WinCE-based HD boxes). [1234=>3412]
-	ByteSwapped1632
+	// This is also the 16-bit word swap some readers produce.
+	ByteSwapped1632,
+	// 32-bit little endien
+	// Dumps from readers that pull the data a dword at a time. [1234=>4321]
+	ByteSwapped32
 }
+
+// Collations an image can be in if we don't know where it came from. Tried in this order when probing an image.
+pub const PROBE_COLLATIONS: [BuildIODataCollation; 4] = [
+	BuildIODataCollation::Raw,
+	BuildIODataCollation::ByteSwapped16,
+	BuildIODataCollation::ByteSwapped1632,
+	BuildIODataCollation::ByteSwapped32
+];
+
 impl BuildIODataCollation {
 	fn convert_raw_data(buf: &mut [u8], collation: BuildIODataCollation) -> Result<(), Box<dyn std::error::Error>> {
 		if collation == BuildIODataCollation::ByteSwapped16 {
@@ -27,17 +48,95 @@ impl BuildIODataCollation {
 				chunk.swap(0, 2);
 				chunk.swap(1, 3);
 			});
+		} else if collation == BuildIODataCollation::ByteSwapped32 {
+			buf.chunks_exact_mut(4).for_each(|chunk| {
+				chunk.reverse();
+			});
 		}
 
 		Ok(())
 	}
 }
 
+// Writes to a temporary file next to the file, syncs it then renames it over the file.
+// The file is left as it was if anything fails so a bad write can't take out the only copy.
+// Handles opened before this still point at the old file so they need to be opened again.
+pub fn write_file_atomically<F>(file_path: &str, write_data: F) -> Result<(), Box<dyn std::error::Error>>
+where F: FnOnce(&mut File) -> Result<(), Box<dyn std::error::Error>> {
+	let temp_file_path = file_path.to_owned() + ".tmp";
+
+	match write_temp_file(&temp_file_path, write_data) {
+		Ok(_) => {
+			match std::fs::rename(&temp_file_path, file_path) {
+				Ok(_) => { },
+				Err(e) => {
+					let _ = std::fs::remove_file(&temp_file_path);
+
+					return Err(e.into());
+				}
+			};
+
+			// Make sure the rename itself is on disk. Directories can't be opened like this everywhere so this is best effort.
+			match std::path::Path::new(file_path).parent() {
+				Some(parent_path) => {
+					match File::open(parent_path) {
+						Ok(parent) => {
+							let _ = parent.sync_all();
+						},
+						_ => { }
+					};
+				},
+				_ => { }
+			};
+
+			Ok(())
+		},
+		Err(e) => {
+			let _ = std::fs::remove_file(&temp_file_path);
+
+			Err(e)
+		}
+	}
+}
+
+fn write_temp_file<F>(temp_file_path: &str, write_data: F) -> Result<(), Box<dyn std::error::Error>>
+where F: FnOnce(&mut File) -> Result<(), Box<dyn std::error::Error>> {
+	let mut file = File::create(temp_file_path)?;
+
+	write_data(&mut file)?;
+
+	file.sync_all()?;
+
+	Ok(())
+}
+
+// Writes aren't in an image until they're committed so they're laid over what was read. Later writes win.
+// The offsets are from the same place, like the start of a page or the start of the file.
+pub fn overlay_pending_write(write_offset: u64, write_data: &[u8], buf_offset: u64, buf: &mut [u8]) {
+	let overlap_start = write_offset.max(buf_offset);
+	let overlap_end = (write_offset + write_data.len() as u64).min(buf_offset + buf.len() as u64);
+
+	if overlap_start < overlap_end {
+		buf[((overlap_start - buf_offset) as usize)..((overlap_end - buf_offset) as usize)]
+			.copy_from_slice(&write_data[((overlap_start - write_offset) as usize)..((overlap_end - write_offset) as usize)]);
+	}
+}
+
+#[cfg(test)]
+pub fn get_test_file_path(file_name: &str) -> String {
+	std::env::temp_dir().join("mame_launcher_".to_owned() + file_name).to_str().unwrap().to_string()
+}
+
+pub const READ_ONLY_ERROR: &str = "The image was opened read-only so it can't be written to.";
+
 pub trait BuildIO {
 	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>>;
 
 	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> where Self: Sized;
 
+	// Writes and commits are rejected. Used when we're only looking at what's in an image.
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> where Self: Sized;
+
 	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> where Self: Sized;
 
 	fn seek(&mut self, pos: u64) -> Result<u64, Box<dyn std::error::Error>>;
@@ -53,4 +152,45 @@ pub trait BuildIO {
 	fn len(&mut self) -> Result<u64, Box<dyn std::error::Error>>;
 
 	fn collation(&mut self) -> Result<BuildIODataCollation, Box<dyn std::error::Error>>;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+
+	#[test]
+	fn failed_write_keeps_the_original() {
+		let file_path = get_test_file_path("failed_write");
+		std::fs::write(&file_path, [0x11; 0x10]).unwrap();
+
+		let result = write_file_atomically(&file_path, |dstf| {
+			dstf.write_all(&[0x22; 0x08])?;
+
+			Err("Simulated write failure.".into())
+		});
+
+		assert!(result.is_err());
+		assert_eq!(std::fs::read(&file_path).unwrap(), [0x11; 0x10]);
+		assert!(!std::path::Path::new(&(file_path.clone() + ".tmp")).exists());
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+
+	#[test]
+	fn successful_write_replaces_the_original() {
+		let file_path = get_test_file_path("successful_write");
+		std::fs::write(&file_path, [0x11; 0x10]).unwrap();
+
+		write_file_atomically(&file_path, |dstf| {
+			dstf.write_all(&[0x22; 0x08])?;
+
+			Ok(())
+		}).unwrap();
+
+		assert_eq!(std::fs::read(&file_path).unwrap(), [0x22; 0x08]);
+		assert!(!std::path::Path::new(&(file_path.clone() + ".tmp")).exists());
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+}
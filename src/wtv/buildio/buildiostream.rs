@@ -0,0 +1,207 @@
+use super::BuildIO;
+use std::io::{Read, Write, Seek, SeekFrom, Error, ErrorKind};
+
+// Reads and writes go through a block at a time so BuildIO only ever sees aligned, even sized buffers.
+const STREAM_BLOCK_SIZE: u64 = 0x00000200;
+
+// Gives any BuildIO the standard Read, Write and Seek traits so images can be handed to other crates.
+// Any buffered write is written when the stream is flushed or dropped. Flushing also commits the BuildIO.
+#[allow(dead_code)]
+pub struct BuildIOStream<'a> {
+	io: &'a mut dyn BuildIO,
+	size: u64,
+	position: u64,
+	block: Vec<u8>,
+	block_offset: u64,
+	block_size: usize,
+	block_loaded: bool,
+	block_dirty: bool
+}
+
+impl<'a> BuildIOStream<'a> {
+	pub fn new(io: &'a mut dyn BuildIO) -> Result<BuildIOStream<'a>, Box<dyn std::error::Error>> {
+		let size = io.len()?;
+
+		Ok(BuildIOStream {
+			io: io,
+			size: size,
+			position: 0,
+			block: vec![0x00; STREAM_BLOCK_SIZE as usize],
+			block_offset: 0,
+			block_size: 0,
+			block_loaded: false,
+			block_dirty: false
+		})
+	}
+
+	fn to_io_error(e: Box<dyn std::error::Error>) -> Error {
+		Error::other(e.to_string())
+	}
+
+	// The last block can be short but BuildIO is still given a multiple of 4.
+	fn get_aligned_size(size: usize) -> usize {
+		((size + 3) & !3).max(4)
+	}
+
+	fn write_block(&mut self) -> Result<(), Error> {
+		if self.block_dirty {
+			let aligned_size = BuildIOStream::get_aligned_size(self.block_size);
+
+			// BuildIO converts the collation in place so it gets a copy to keep the block intact.
+			let mut block_data = self.block[0..aligned_size].to_vec();
+
+			self.io.seek(self.block_offset).map_err(BuildIOStream::to_io_error)?;
+			self.io.write(&mut block_data).map_err(BuildIOStream::to_io_error)?;
+
+			self.block_dirty = false;
+		}
+
+		Ok(())
+	}
+
+	fn load_block(&mut self) -> Result<(), Error> {
+		let block_offset = self.position - (self.position % STREAM_BLOCK_SIZE);
+
+		if self.block_loaded && self.block_offset == block_offset {
+			return Ok(());
+		}
+
+		self.write_block()?;
+
+		self.block_offset = block_offset;
+		self.block_size = STREAM_BLOCK_SIZE.min(self.size - block_offset) as usize;
+		self.block.fill(0x00);
+
+		let aligned_size = BuildIOStream::get_aligned_size(self.block_size);
+
+		self.io.seek(self.block_offset).map_err(BuildIOStream::to_io_error)?;
+		self.io.read(&mut self.block[0..aligned_size]).map_err(BuildIOStream::to_io_error)?;
+
+		self.block_loaded = true;
+
+		Ok(())
+	}
+}
+
+impl Read for BuildIOStream<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+		if self.position >= self.size || buf.len() == 0 {
+			return Ok(0);
+		}
+
+		self.load_block()?;
+
+		let block_index = (self.position - self.block_offset) as usize;
+		let read_size = buf.len().min(self.block_size - block_index);
+
+		buf[0..read_size].copy_from_slice(&self.block[block_index..(block_index + read_size)]);
+		self.position += read_size as u64;
+
+		Ok(read_size)
+	}
+}
+
+impl Write for BuildIOStream<'_> {
+	// Images don't grow so nothing can be written past the end.
+	fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+		if self.position >= self.size || buf.len() == 0 {
+			return Ok(0);
+		}
+
+		self.load_block()?;
+
+		let block_index = (self.position - self.block_offset) as usize;
+		let write_size = buf.len().min(self.block_size - block_index);
+
+		self.block[block_index..(block_index + write_size)].copy_from_slice(&buf[0..write_size]);
+		self.block_dirty = true;
+		self.position += write_size as u64;
+
+		Ok(write_size)
+	}
+
+	fn flush(&mut self) -> Result<(), Error> {
+		self.write_block()?;
+
+		self.io.commit().map_err(BuildIOStream::to_io_error)
+	}
+}
+
+impl Seek for BuildIOStream<'_> {
+	fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+		let position = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+			SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+		};
+
+		match position {
+			Some(position) => {
+				self.position = position;
+
+				Ok(self.position)
+			},
+			_ => Err(Error::new(ErrorKind::InvalidInput, "Can't seek before the start of the image."))
+		}
+	}
+
+	fn stream_position(&mut self) -> Result<u64, Error> {
+		Ok(self.position)
+	}
+}
+
+impl Drop for BuildIOStream<'_> {
+	fn drop(&mut self) {
+		let _ = self.write_block();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{flashdiskio::FlashdiskIO, mdocgeometry::MDOCGeometry, get_test_file_path};
+
+	#[test]
+	fn evicted_blocks_keep_their_writes() {
+		let file_path = get_test_file_path("stream_eviction");
+		let block_a_offset = 0x10;
+		let block_b_offset = STREAM_BLOCK_SIZE * 8;
+
+		{
+			let mut io = FlashdiskIO::create_flashdisk(file_path.clone(), None, MDOCGeometry::from_usr_size(0x800000)).unwrap();
+			let mut stream = BuildIOStream::new(&mut io).unwrap();
+
+			stream.seek(SeekFrom::Start(block_a_offset)).unwrap();
+			stream.write_all(&[0x11; 0x08]).unwrap();
+
+			// Touching another block evicts A before anything is committed.
+			stream.seek(SeekFrom::Start(block_b_offset)).unwrap();
+			stream.write_all(&[0x22; 0x08]).unwrap();
+
+			let mut block_a_data = [0x00; 0x08];
+			stream.seek(SeekFrom::Start(block_a_offset)).unwrap();
+			stream.read_exact(&mut block_a_data).unwrap();
+			assert_eq!(block_a_data, [0x11; 0x08]);
+
+			stream.seek(SeekFrom::Start(block_a_offset + 0x08)).unwrap();
+			stream.write_all(&[0x33; 0x08]).unwrap();
+			stream.flush().unwrap();
+		}
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		let mut stream = BuildIOStream::new(&mut io).unwrap();
+
+		let mut block_a_data = [0x00; 0x10];
+		stream.seek(SeekFrom::Start(block_a_offset)).unwrap();
+		stream.read_exact(&mut block_a_data).unwrap();
+		assert_eq!(block_a_data[0..0x08], [0x11; 0x08]);
+		assert_eq!(block_a_data[0x08..0x10], [0x33; 0x08]);
+
+		let mut block_b_data = [0x00; 0x08];
+		stream.seek(SeekFrom::Start(block_b_offset)).unwrap();
+		stream.read_exact(&mut block_b_data).unwrap();
+		assert_eq!(block_b_data, [0x22; 0x08]);
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+}
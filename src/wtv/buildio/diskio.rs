@@ -1,5 +1,6 @@
 use packbytes::{FromBytes, ToBytes};
-use super::{BuildIO, BuildIODataCollation};
+use super::{BuildIO, BuildIODataCollation, READ_ONLY_ERROR, overlay_pending_write, write_file_atomically};
+use super::super::error::LauncherError;
 use std::{
 	fs::{File, OpenOptions},
 	path::Path
@@ -73,6 +74,7 @@ pub struct CompressedHunkDiskIO {
 	collation: BuildIODataCollation,
 	size: u64,
 	created: bool,
+	read_only: bool,
 	chd: Box<Chd<File>>,
 	current_hunk_index: u32,
 	current_hunk_offset: usize,
@@ -91,8 +93,18 @@ impl CompressedHunkDiskIO {
 		Ok(())
 	}
 
-	pub fn find_diff_file(chd_file_path: String) -> Result<String, Box<dyn std::error::Error>> {
-		let path = Path::new(&chd_file_path);
+	fn overlay_pending_writes(&self, hunk_index: usize, hunk_offset: usize, buf: &mut [u8]) {
+		for hunk_write in self.pending_hunk_writes.iter() {
+			if hunk_write.hunk_index == hunk_index {
+				overlay_pending_write(hunk_write.hunk_offset as u64, &hunk_write.data[0..hunk_write.size], hunk_offset as u64, buf);
+			}
+		}
+	}
+
+	// MAME's CHDs are in <rom path>/<box>/ and their diffs are in MAME's diff directory.
+	// Without MAME's rom paths, a /roms/XXX/ folder is taken to be MAME's.
+	pub fn find_diff_file(chd_file_path: &String, rom_paths: &Vec<String>, diff_directory: &String) -> String {
+		let path = Path::new(chd_file_path);
 
 		let chd_parent = match path.parent() {
 			Some(parent) => parent.to_str().unwrap_or("".into()),
@@ -105,40 +117,39 @@ impl CompressedHunkDiskIO {
 				_ => "".into()
 			};
 
+			if rom_paths.len() > 0 {
+				match Path::new(chd_parent).parent() {
+					Some(box_parent) => {
+						let box_parent = box_parent.canonicalize().unwrap_or(box_parent.to_path_buf());
+
+						for rom_path in rom_paths.iter() {
+							let rom_path = Path::new(rom_path).canonicalize().unwrap_or(Path::new(rom_path).to_path_buf());
+
+							if rom_path == box_parent {
+								return diff_directory.to_owned() + "/" + chd_stem + ".dif";
+							}
+						}
+					},
+					_ => { }
+				};
+
+				// Not one of MAME's CHDs.
+				return "".into();
+			}
+
 			// Only using diff file if this is a CHD for a WebTV preset file inside MAME
-			// Checking if the chd file is in the /roms/XXX/ folder to detect MAME
 			if Path::new(&(chd_parent.to_owned() + "/../../roms")).exists() {
 				// We return the path to where the diff file would exist.
-				return Ok(chd_parent.to_owned() + "/../../diff/" + chd_stem + ".dif");
+				return chd_parent.to_owned() + "/../../diff/" + chd_stem + ".dif";
 			}
 		}
 
-		Ok("".into())
+		"".into()
 	}
-}
-impl BuildIO for CompressedHunkDiskIO {
-	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-		Ok(self.file_path.clone())
-	}
-
-	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
-		let diff_file_path = CompressedHunkDiskIO::find_diff_file(file_path.clone()).unwrap_or("".into());
 
-		let chd;
-		if diff_file_path != "" && Path::new(&diff_file_path).exists() {
-			chd = Box::new(Chd::open(
-					File::open(diff_file_path.clone())?, 
-			Some(Box::new(Chd::open(
-						File::open(file_path.clone())?, 
-						None
-					)?))
-				)?);
-		} else {
-			chd = Box::new(Chd::open(
-					File::open(file_path.clone())?, 
-					None
-				)?);
-		}
+	// Opens the CHD with MAME's diff file for it. An empty diff path opens the CHD without a diff.
+	pub fn open_with_diff(file_path: String, diff_file_path: String, collation: Option<BuildIODataCollation>, read_only: bool) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		let chd = CompressedHunkDiskIO::open_chd_file(&file_path, &diff_file_path)?;
 
 		let mut io = CompressedHunkDiskIO {
 			file_path: file_path.clone(),
@@ -146,6 +157,7 @@ impl BuildIO for CompressedHunkDiskIO {
 			collation: collation.unwrap_or(BuildIODataCollation::Raw),
 			size: 0,
 			created: false,
+			read_only: read_only,
 			chd: chd,
 			current_hunk_index: 0,
 			current_hunk_offset: 0,
@@ -160,13 +172,51 @@ impl BuildIO for CompressedHunkDiskIO {
 		Ok(Box::new(io))
 	}
 
+	// The diff file is used over the CHD if MAME has written one.
+	fn open_chd_file(file_path: &String, diff_file_path: &String) -> Result<Box<Chd<File>>, Box<dyn std::error::Error>> {
+		if *diff_file_path != "" && Path::new(&diff_file_path).exists() {
+			Ok(Box::new(Chd::open(
+					File::open(diff_file_path.clone()).map_err(|e| LauncherError::from_io_error(diff_file_path, &e))?, 
+			Some(Box::new(Chd::open(
+						File::open(file_path.clone()).map_err(|e| LauncherError::from_io_error(file_path, &e))?, 
+						None
+					)?))
+				)?))
+		} else {
+			Ok(Box::new(Chd::open(
+					File::open(file_path.clone()).map_err(|e| LauncherError::from_io_error(file_path, &e))?, 
+					None
+				)?))
+		}
+	}
+
+	fn open_chd(file_path: String, collation: Option<BuildIODataCollation>, read_only: bool) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		let diff_file_path = CompressedHunkDiskIO::find_diff_file(&file_path, &vec![], &"".into());
+
+		CompressedHunkDiskIO::open_with_diff(file_path, diff_file_path, collation, read_only)
+	}
+}
+impl BuildIO for CompressedHunkDiskIO {
+	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+		Ok(self.file_path.clone())
+	}
+
+	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		CompressedHunkDiskIO::open_chd(file_path, collation, false)
+	}
+
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		CompressedHunkDiskIO::open_chd(file_path, collation, true)
+	}
+
 	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
 		let mut io = CompressedHunkDiskIO {
 			file_path: file_path.clone(),
-			diff_path: CompressedHunkDiskIO::find_diff_file(file_path.clone()).unwrap_or("".into()).clone(),
+			diff_path: CompressedHunkDiskIO::find_diff_file(&file_path, &vec![], &"".into()),
 			collation: collation.unwrap_or(BuildIODataCollation::Raw),
 			size: size,
 			created: true,
+			read_only: false,
 			chd: Box::new(Chd::open(File::open(file_path.clone())?, None)?),
 			current_hunk_index: 0,
 			current_hunk_offset: 0,
@@ -228,6 +278,9 @@ impl BuildIO for CompressedHunkDiskIO {
 					};
 				}
 	
+				let read_hunk_index = self.current_hunk_index as usize;
+				let read_hunk_offset = self.current_hunk_offset;
+
 				let mut current_read_size = need_total_size - read_total_size;
 				if current_read_size > (hunk_size - self.current_hunk_offset) {
 					current_read_size = hunk_size - self.current_hunk_offset;
@@ -242,6 +295,8 @@ impl BuildIO for CompressedHunkDiskIO {
 				buf[current_buf_index as usize..(current_buf_index + current_read_size) as usize]
 					.copy_from_slice(&self.current_hunk[self.current_hunk_offset as usize..(self.current_hunk_offset + current_read_size) as usize]);
 
+				self.overlay_pending_writes(read_hunk_index, read_hunk_offset, &mut buf[current_buf_index as usize..(current_buf_index + current_read_size) as usize]);
+
 				current_buf_index += current_read_size;
 				read_total_size += current_read_size;
 				self.current_hunk_offset += current_read_size;
@@ -258,7 +313,9 @@ impl BuildIO for CompressedHunkDiskIO {
 	}
 
 	fn write(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
-		if buf.len() < 0x4 {
+		if self.read_only {
+			return Err(READ_ONLY_ERROR.into());
+		} else if buf.len() < 0x4 {
 			return Err("Buffer length needs to be 4 bytes or greater.".into());
 		} else if (buf.len() & 1) == 1 {
 			return Err("Buffer length needs to be a multiple of 2.".into());
@@ -280,6 +337,7 @@ impl BuildIO for CompressedHunkDiskIO {
 					current_write_size = hunk_size - self.current_hunk_offset;
 					self.current_hunk_index += 1;
 					self.current_hunk_offset = 0;
+					self.current_hunk_read = false;
 				} else {
 					self.current_hunk_offset += current_write_size;
 				}
@@ -301,6 +359,10 @@ impl BuildIO for CompressedHunkDiskIO {
 
 	fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
 		if self.pending_hunk_writes.len() > 0 {
+			if self.diff_path == "" {
+				return Err("Changes to a CHD can only be saved to a diff file inside MAME's folder.".into());
+			}
+
 			let hunk_size = self.chd.header().hunk_size() as usize;
 			let hunk_count = self.chd.header().hunk_count() as usize;
 
@@ -325,140 +387,141 @@ impl BuildIO for CompressedHunkDiskIO {
 				let _ = std::fs::copy(&self.diff_path, self.diff_path.clone() + ".bak");
 			}
 
+			let parent_sha1 = match self.chd.header().has_parent() {
+				true => self.chd.header().parent_sha1().unwrap_or([0x00; 20]),
+				false => self.chd.header().sha1().unwrap_or([0x00; 20]),
+			};
+
+			let diff_header = CHDHeaderV5 {
+				magic: CHD_MAGIC,
+				header_size: CHD_HEADER_SIZE,
+				header_version: CHD_HEADER_VERSION,
+				compressor: [0; 4],
+				uncompressed_size: self.len().unwrap_or(0),
+				hunk_map_offset: CHD_HEADER_SIZE as u64,
+				disk_metadata_offset: metadata_offset as u64,
+				hunk_size_bytes: hunk_size as u32,
+				sector_size_bytes: self.chd.header().unit_bytes(),
+				sha1: [
+					Sha1Hash { hash: [0x00; 20] },
+					Sha1Hash { hash: [0x00; 20] },
+					Sha1Hash { hash: parent_sha1 }
+				],
+			}.to_be_bytes();
+
 			let mut current_file_hunk_index = start_file_hunk_index;
-			match File::create(&self.diff_path) {
-				Ok(mut dstf) => {
-					let _ = dstf.seek(SeekFrom::Start(0));
 
-					let parent_sha1 = match self.chd.header().has_parent() {
-						true => self.chd.header().parent_sha1().unwrap_or([0x00; 20]),
-						false => self.chd.header().sha1().unwrap_or([0x00; 20]),
-					};
+			let mut hunks: Vec<Vec<u8>> = vec![];
+			for hwi in self.pending_hunk_writes.iter() {
+				// Several writes can land in the same hunk so they're all applied to one copy of it.
+				if hunk_map[hwi.hunk_index] != 0x00000000 {
+					let current_hunk = &mut hunks[hunk_map[hwi.hunk_index] as usize - start_file_hunk_index];
 
-					let _ = dstf.write(&CHDHeaderV5 {
-						magic: CHD_MAGIC,
-						header_size: CHD_HEADER_SIZE,
-						header_version: CHD_HEADER_VERSION,
-						compressor: [0; 4],
-						uncompressed_size: self.len().unwrap_or(0),
-						hunk_map_offset: CHD_HEADER_SIZE as u64,
-						disk_metadata_offset: metadata_offset as u64,
-						hunk_size_bytes: hunk_size as u32,
-						sector_size_bytes: self.chd.header().unit_bytes(),
-						sha1: [
-							Sha1Hash { hash: [0x00; 20] },
-							Sha1Hash { hash: [0x00; 20] },
-							Sha1Hash { hash: parent_sha1 }
-						],
-					}.to_be_bytes());
-
-					let mut hunks = vec![];
-					for hwi in self.pending_hunk_writes.iter() {
-						let mut current_hunk = self.chd.get_hunksized_buffer();
-
-						if hwi.hunk_offset > 0 || hwi.size < hunk_size {
-							let mut hunk = self.chd.hunk(hwi.hunk_index as u32)?;
-
-							hunk.read_hunk_in(&mut Vec::new(), &mut current_hunk)?;
-						}
+					current_hunk[hwi.hunk_offset as usize..(hwi.hunk_offset + hwi.size) as usize]
+						.copy_from_slice(&hwi.data);
 
-						current_hunk[hwi.hunk_offset as usize..(hwi.hunk_offset + hwi.size) as usize]
-							.copy_from_slice(&hwi.data);
+					continue;
+				}
 
-						hunk_map[hwi.hunk_index] = current_file_hunk_index as u32;
+				let mut current_hunk = self.chd.get_hunksized_buffer();
 
-						current_file_hunk_index += 1;
+				if hwi.hunk_offset > 0 || hwi.size < hunk_size {
+					let mut hunk = self.chd.hunk(hwi.hunk_index as u32)?;
 
-						hunks.push(current_hunk);
-					}
+					hunk.read_hunk_in(&mut Vec::new(), &mut current_hunk)?;
+				}
+
+				current_hunk[hwi.hunk_offset as usize..(hwi.hunk_offset + hwi.size) as usize]
+					.copy_from_slice(&hwi.data);
+
+				hunk_map[hwi.hunk_index] = current_file_hunk_index as u32;
+
+				current_file_hunk_index += 1;
 
-					let mut missing_hunk_offsets = vec![];
-					// Write back hunks that weren't changed from the old diff.
-					if has_diff {
-						match File::open(&(self.diff_path.clone() + ".bak")) {
-							Ok(mut old_diff) => {
-								let _ = old_diff.seek(SeekFrom::Start(0))?;
-								match CHDHeaderV5::read_packed(&mut old_diff) {
-									Ok(old_dif_header) => {
-										if old_dif_header.header_version == CHD_HEADER_VERSION {
-											// Read in the hunk offsets from the original diff file that need to be moved.
-
-											for hunk_map_index in 0..hunk_map.len() {
-												// Only move hunks that don't have data in the new diff file
-												if hunk_map[hunk_map_index] == 0x00000000 {
-													let mut file_hunk_index_buff = [0x00 as u8; 4];
-													let _ = old_diff.seek(SeekFrom::Start(old_dif_header.hunk_map_offset + (hunk_map_index * 4) as u64));
-													let _ = old_diff.read(&mut file_hunk_index_buff);
-													let file_hunk_index = u32::from_be_bytes(file_hunk_index_buff) as u64;
-
-													// If the old diff has data then move it.
-													if file_hunk_index != 0x00000000 {
-														hunk_map[hunk_map_index] = current_file_hunk_index as u32;
-														current_file_hunk_index += 1;
-
-														missing_hunk_offsets.push(file_hunk_index * hunk_size as u64);
-													}
-												}
-											}
-										}
-									},
-									_ => {
-										//
+				hunks.push(current_hunk);
+			}
+
+			let mut missing_hunk_offsets = vec![];
+			// Write back hunks that weren't changed from the old diff. The old diff is still in place until the new one replaces it.
+			if has_diff {
+				let mut old_diff = File::open(&self.diff_path)?;
+				let _ = old_diff.seek(SeekFrom::Start(0))?;
+				match CHDHeaderV5::read_packed(&mut old_diff) {
+					Ok(old_dif_header) => {
+						if old_dif_header.header_version == CHD_HEADER_VERSION {
+							// Read in the hunk offsets from the original diff file that need to be moved.
+
+							for hunk_map_index in 0..hunk_map.len() {
+								// Only move hunks that don't have data in the new diff file
+								if hunk_map[hunk_map_index] == 0x00000000 {
+									let mut file_hunk_index_buff = [0x00 as u8; 4];
+									let _ = old_diff.seek(SeekFrom::Start(old_dif_header.hunk_map_offset + (hunk_map_index * 4) as u64))?;
+									old_diff.read_exact(&mut file_hunk_index_buff)?;
+									let file_hunk_index = u32::from_be_bytes(file_hunk_index_buff) as u64;
+
+									// If the old diff has data then move it.
+									if file_hunk_index != 0x00000000 {
+										hunk_map[hunk_map_index] = current_file_hunk_index as u32;
+										current_file_hunk_index += 1;
+
+										missing_hunk_offsets.push(file_hunk_index * hunk_size as u64);
 									}
-								};
-							},
-							_ => {
-								//
+								}
 							}
 						}
+					},
+					_ => {
+						//
 					}
+				};
+			}
 
-					let mut hunk_map_block = vec![];
-					for hunk_map_entry in hunk_map.iter() {
-						hunk_map_block.extend_from_slice(&hunk_map_entry.to_be_bytes());
-					}
-					let _ = dstf.write(&hunk_map_block);
+			let mut hunk_map_block = vec![];
+			for hunk_map_entry in hunk_map.iter() {
+				hunk_map_block.extend_from_slice(&hunk_map_entry.to_be_bytes());
+			}
 
-					let _ = dstf.write(&CHDChunkMetadata {
-						chunk_id: CHD_METADATA_CHUNK_ID,
-						flags: 1,
-						size: DataU24 { ms: 0, ls: metadata.len() as u16 + 1 },
-						next_offset: 0,
-					}.to_be_bytes());
+			let metadata_chunk = CHDChunkMetadata {
+				chunk_id: CHD_METADATA_CHUNK_ID,
+				flags: 1,
+				size: DataU24 { ms: 0, ls: metadata.len() as u16 + 1 },
+				next_offset: 0,
+			}.to_be_bytes();
+
+			let diff_path = self.diff_path.clone();
+			write_file_atomically(&diff_path, |dstf| {
+				dstf.write_all(&diff_header)?;
+				dstf.write_all(&hunk_map_block)?;
+				dstf.write_all(&metadata_chunk)?;
+				dstf.write_all(metadata.as_bytes())?;
+				dstf.write_all(&[0x00; 1])?;
+
+				// Padding so the first hunk is correctly aligned.
+				dstf.write_all(&vec![0x00 as u8; hunk_data_offset - metadata_end_offset])?;
+
+				for hunk in hunks.iter() {
+					dstf.write_all(&hunk)?;
+				}
 
-					let _ = dstf.write(&mut metadata.as_bytes());
-					let _ = dstf.write(&[0x00; 1]);
+				// Move the hunks from the old to the new diff file.
+				if missing_hunk_offsets.len() > 0 {
+					let mut old_diff = File::open(&diff_path)?;
 
-					// Padding so the first hunk is correctly aligned.
-					let _ = dstf.write(&vec![0x00 as u8; hunk_data_offset - metadata_end_offset]);
+					for hunk_offset in missing_hunk_offsets.iter() {
+						let mut missing_hunk = vec![0x00 as u8; hunk_size];
+						let _ = old_diff.seek(SeekFrom::Start(*hunk_offset))?;
+						old_diff.read_exact(&mut missing_hunk)?;
 
-					for hunk in hunks.iter() {
-						let _ = dstf.write(&hunk);
+						dstf.write_all(&missing_hunk)?;
 					}
+				}
 
-					// Move the hunks from the old to the new diff file.
-
-					if has_diff && missing_hunk_offsets.len() > 0 {
-						match File::open(&(self.diff_path.clone() + ".bak")) {
-							Ok(mut old_diff) => {
-								for hunk_offset in missing_hunk_offsets.iter() {
-									let mut missing_hunk = self.chd.get_hunksized_buffer();
-									let _ = old_diff.seek(SeekFrom::Start(*hunk_offset))?;
-									let _ = old_diff.read(&mut missing_hunk);
+				Ok(())
+			})?;
 
-									let _ = dstf.write(&missing_hunk);
-								}
-							}
-							_ => {
-								//
-							}
-						}
-					}
-				},
-				_ => {
-					//
-				}
-			};
+			// Reads need to come from the new diff file.
+			self.chd = CompressedHunkDiskIO::open_chd_file(&self.file_path, &self.diff_path)?;
+			self.current_hunk_read = false;
 
 			self.pending_hunk_writes.clear();
 		}
@@ -476,25 +539,33 @@ impl BuildIO for CompressedHunkDiskIO {
 }
 
 #[allow(dead_code)]
-struct RawDiskIO {
+pub struct RawWriteInfo {
+	pub offset: u64,
+	pub data: Vec<u8>
+}
+
+#[allow(dead_code)]
+pub struct RawDiskIO {
 	file_path: String,
 	collation: BuildIODataCollation,
 	size: u64,
 	created: bool,
+	read_only: bool,
+	position: u64,
 	file: File,
+	pending_writes: Vec<RawWriteInfo>
 }
-impl BuildIO for RawDiskIO {
-	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-		Ok(self.file_path.clone())
-	}
-
-	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+impl RawDiskIO {
+	fn open_raw(file_path: String, collation: Option<BuildIODataCollation>, read_only: bool) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
 		let mut io = RawDiskIO {
 			file_path: file_path.clone(),
 			collation: collation.unwrap_or(BuildIODataCollation::Raw),
 			size: 0,
 			created: false,
-			file: OpenOptions::new().read(true).write(true).open(file_path.clone())?
+			read_only: read_only,
+			position: 0,
+			file: OpenOptions::new().read(true).open(file_path.clone()).map_err(|e| LauncherError::from_io_error(&file_path, &e))?,
+			pending_writes: vec![]
 		};
 
 		io.size = io.file.metadata().unwrap().len();
@@ -502,24 +573,48 @@ impl BuildIO for RawDiskIO {
 		Ok(Box::new(io))
 	}
 
+	fn overlay_pending_writes(&self, offset: u64, buf: &mut [u8]) {
+		for raw_write in self.pending_writes.iter() {
+			overlay_pending_write(raw_write.offset, &raw_write.data, offset, buf);
+		}
+	}
+}
+impl BuildIO for RawDiskIO {
+	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+		Ok(self.file_path.clone())
+	}
+
+	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		RawDiskIO::open_raw(file_path, collation, false)
+	}
+
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		RawDiskIO::open_raw(file_path, collation, true)
+	}
+
 	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
 		let io = RawDiskIO {
 			file_path: file_path.clone(),
 			collation: collation.unwrap_or(BuildIODataCollation::Raw),
 			size: size,
 			created: true,
-			file: OpenOptions::new().read(true).write(true).create(true).open(file_path.clone())?,
+			read_only: false,
+			position: 0,
+			file: OpenOptions::new().read(true).write(true).create(true).open(file_path.clone()).map_err(|e| LauncherError::from_io_error(&file_path, &e))?,
+			pending_writes: vec![]
 		};
 
 		Ok(Box::new(io))
 	}
 
 	fn seek(&mut self, pos: u64) -> Result<u64, Box<dyn std::error::Error>>  {
-		Ok(self.file.seek(SeekFrom::Start(pos))?)
+		self.position = pos;
+
+		Ok(self.position)
 	}
 
 	fn stream_position(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
-		Ok(self.file.stream_position()?)
+		Ok(self.position)
 	}
 
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
@@ -528,21 +623,71 @@ impl BuildIO for RawDiskIO {
 		} else if (buf.len() & 1) == 1 {
 			Err("Buffer length needs to be a multiple of 2.".into())
 		} else {
-			let result = self.file.read(buf)?;
+			let read_size = (buf.len() as u64).min(self.size.saturating_sub(self.position)) as usize;
+
+			// Anything past the end of what's in the file so far reads as zeros, same as it will be once committed.
+			buf[0..read_size].fill(0x00);
+
+			let _ = self.file.seek(SeekFrom::Start(self.position))?;
+			let mut file_read_size = 0;
+			while file_read_size < read_size {
+				match self.file.read(&mut buf[file_read_size..read_size])? {
+					0 => break,
+					current_read_size => file_read_size += current_read_size
+				};
+			}
 
-			let _ = BuildIODataCollation::convert_raw_data(buf, self.collation);
+			self.overlay_pending_writes(self.position, &mut buf[0..read_size]);
+
+			self.position += read_size as u64;
 
-			Ok(result)
+			let _ = BuildIODataCollation::convert_raw_data(&mut buf[0..read_size], self.collation);
+
+			Ok(read_size)
 		}
 	}
 
 	fn write(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+		if self.read_only {
+			return Err(READ_ONLY_ERROR.into());
+		}
+
 		let _ = BuildIODataCollation::convert_raw_data(buf, self.collation);
 
-		Ok(self.file.write(buf)?)
+		self.pending_writes.push(RawWriteInfo {
+			offset: self.position,
+			data: buf.to_vec()
+		});
+
+		self.position += buf.len() as u64;
+		self.size = self.size.max(self.position);
+
+		Ok(buf.len())
 	}
 
 	fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		if self.pending_writes.len() > 0 {
+			let size = self.size;
+			let file = &mut self.file;
+			let pending_writes = &self.pending_writes;
+
+			write_file_atomically(&self.file_path, |dstf| {
+				let _ = file.seek(SeekFrom::Start(0))?;
+				std::io::copy(file, dstf)?;
+				dstf.set_len(size)?;
+
+				for raw_write in pending_writes.iter() {
+					let _ = dstf.seek(SeekFrom::Start(raw_write.offset))?;
+					dstf.write_all(&raw_write.data)?;
+				}
+
+				Ok(())
+			})?;
+
+			self.file = OpenOptions::new().read(true).open(&self.file_path)?;
+			self.pending_writes.clear();
+		}
+
 		Ok(())
 	}
 
@@ -557,6 +702,18 @@ impl BuildIO for RawDiskIO {
 
 #[allow(dead_code)]
 pub struct DiskIO;
+impl DiskIO {
+	// Same as open but CHDs use the diff file given rather than looking for one.
+	pub fn open_with_diff(file_path: String, diff_file_path: String, collation: Option<BuildIODataCollation>, read_only: bool) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		if Regex::new(r"\.(chd|dif)$")?.is_match(file_path.as_str()) {
+			CompressedHunkDiskIO::open_with_diff(file_path.clone(), diff_file_path, collation, read_only)
+		} else if read_only {
+			RawDiskIO::open_read_only(file_path.clone(), collation)
+		} else {
+			RawDiskIO::open(file_path.clone(), collation)
+		}
+	}
+}
 impl BuildIO for DiskIO {
 	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
 		Ok("".into())
@@ -570,6 +727,14 @@ impl BuildIO for DiskIO {
 		}
 	}
 
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		if Regex::new(r"\.(chd|dif)$")?.is_match(file_path.as_str()) {
+			CompressedHunkDiskIO::open_read_only(file_path.clone(), collation)
+		} else {
+			RawDiskIO::open_read_only(file_path.clone(), collation)
+		}
+	}
+
 	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
 		if Regex::new(r"\.(chd|dif)$")?.is_match(file_path.as_str()) {
 			CompressedHunkDiskIO::create(file_path.clone(), collation, size)
@@ -605,4 +770,136 @@ impl BuildIO for DiskIO {
 	fn collation(&mut self) -> Result<BuildIODataCollation, Box<dyn std::error::Error>> {
 		Ok(BuildIODataCollation::Raw)
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::get_test_file_path;
+
+	// An uncompressed CHD with no hunks written reads back as zeros, which is all a diff needs as a parent.
+	fn create_test_chd(file_path: &String, hunk_size: u32, hunk_count: u32) {
+		let header = CHDHeaderV5 {
+			magic: CHD_MAGIC,
+			header_size: CHD_HEADER_SIZE,
+			header_version: CHD_HEADER_VERSION,
+			compressor: [0; 4],
+			uncompressed_size: (hunk_size * hunk_count) as u64,
+			hunk_map_offset: CHD_HEADER_SIZE as u64,
+			disk_metadata_offset: 0,
+			hunk_size_bytes: hunk_size,
+			sector_size_bytes: 0x200,
+			sha1: [
+				Sha1Hash { hash: [0x00; 20] },
+				Sha1Hash { hash: [0x5a; 20] },
+				Sha1Hash { hash: [0x00; 20] }
+			],
+		}.to_be_bytes();
+
+		let mut chd_data = header.to_vec();
+		chd_data.extend_from_slice(&vec![0x00 as u8; (hunk_count * 4) as usize]);
+
+		std::fs::write(file_path, chd_data).unwrap();
+	}
+
+	#[test]
+	fn raw_writes_wait_for_commit() {
+		let file_path = get_test_file_path("raw_commit");
+		std::fs::write(&file_path, [0x11; 0x40]).unwrap();
+
+		let mut io = RawDiskIO::open(file_path.clone(), None).unwrap();
+
+		io.seek(0x10).unwrap();
+		io.write(&mut [0x22; 0x08]).unwrap();
+		io.seek(0x3c).unwrap();
+		io.write(&mut [0x33; 0x08]).unwrap();
+
+		assert_eq!(std::fs::read(&file_path).unwrap(), [0x11; 0x40]);
+
+		let mut data = [0x00; 0x08];
+		io.seek(0x0c).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data, [0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+
+		io.commit().unwrap();
+
+		let file_data = std::fs::read(&file_path).unwrap();
+		assert_eq!(file_data.len(), 0x44);
+		assert_eq!(file_data[0x00..0x10], [0x11; 0x10]);
+		assert_eq!(file_data[0x10..0x18], [0x22; 0x08]);
+		assert_eq!(file_data[0x18..0x3c], [0x11; 0x24]);
+		assert_eq!(file_data[0x3c..0x44], [0x33; 0x08]);
+		assert!(!Path::new(&(file_path.clone() + ".tmp")).exists());
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+
+	#[test]
+	fn chd_writes_go_to_the_diff() {
+		let mame_path = get_test_file_path("chd_commit");
+		let _ = std::fs::remove_dir_all(&mame_path);
+		std::fs::create_dir_all(mame_path.clone() + "/roms/wtv1").unwrap();
+		std::fs::create_dir_all(mame_path.clone() + "/diff").unwrap();
+
+		let file_path = mame_path.clone() + "/roms/wtv1/disk.chd";
+		create_test_chd(&file_path, 0x1000, 4);
+		let chd_data = std::fs::read(&file_path).unwrap();
+
+		{
+			let mut io = CompressedHunkDiskIO::open(file_path.clone(), None).unwrap();
+
+			// Two writes to one hunk and one that crosses into the next.
+			io.seek(0x10).unwrap();
+			io.write(&mut [0x11; 0x08]).unwrap();
+			io.seek(0x20).unwrap();
+			io.write(&mut [0x22; 0x08]).unwrap();
+			io.seek(0xffc).unwrap();
+			io.write(&mut [0x33; 0x08]).unwrap();
+
+			assert!(!Path::new(&(mame_path.clone() + "/diff/disk.dif")).exists());
+
+			io.commit().unwrap();
+		}
+
+		assert_eq!(std::fs::read(&file_path).unwrap(), chd_data);
+		assert!(Path::new(&(mame_path.clone() + "/diff/disk.dif")).exists());
+		assert!(!Path::new(&(mame_path.clone() + "/diff/disk.dif.tmp")).exists());
+
+		let mut io = CompressedHunkDiskIO::open_read_only(file_path.clone(), None).unwrap();
+
+		let mut data = [0x00; 0x20];
+		io.seek(0x10).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data[0x00..0x08], [0x11; 0x08]);
+		assert_eq!(data[0x08..0x10], [0x00; 0x08]);
+		assert_eq!(data[0x10..0x18], [0x22; 0x08]);
+
+		let mut data = [0x00; 0x08];
+		io.seek(0xffc).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data, [0x33; 0x08]);
+
+		let _ = std::fs::remove_dir_all(&mame_path);
+	}
+
+	#[test]
+	fn diff_file_is_in_the_diff_directory() {
+		let mame_path = get_test_file_path("diff_directory");
+		let _ = std::fs::remove_dir_all(&mame_path);
+		std::fs::create_dir_all(mame_path.clone() + "/media/wtv1").unwrap();
+		std::fs::create_dir_all(mame_path.clone() + "/other/wtv1").unwrap();
+
+		let rom_paths = vec![mame_path.clone() + "/media"];
+		let diff_directory = mame_path.clone() + "/changes";
+
+		assert_eq!(
+			CompressedHunkDiskIO::find_diff_file(&(mame_path.clone() + "/media/wtv1/disk.chd"), &rom_paths, &diff_directory),
+			diff_directory.clone() + "/disk.dif"
+		);
+		assert_eq!(
+			CompressedHunkDiskIO::find_diff_file(&(mame_path.clone() + "/other/wtv1/disk.chd"), &rom_paths, &diff_directory),
+			""
+		);
+
+		let _ = std::fs::remove_dir_all(&mame_path);
+	}
+}
@@ -1,18 +1,13 @@
 use packbytes::{FromBytes, ToBytes};
 
-use super::{BuildIO, BuildIODataCollation};
+use super::{BuildIO, BuildIODataCollation, READ_ONLY_ERROR, overlay_pending_write, write_file_atomically, mdocgeometry::MDOCGeometry, buildiostream::BuildIOStream, diskio::RawDiskIO};
+use super::super::error::LauncherError;
 use std::{
 	fs::{File, OpenOptions},
 	io::{Read, Write, Seek, SeekFrom},
 	path::Path
 };
 
-// These values can change depending on what MDOC chip is used.
-// I've only seen these values used with the MDOC chips WebTV supports 
-const USR_PAGE_SIZE: u64 = 0x00000200;
-const SPR_PAGE_SIZE: u64 = 0x00000010;
-const DISKINFO_UNITS: u64 = 0x00000002;
-
 const DISK_MAGIC: [u8; 6] = [b'A', b'N', b'A', b'N', b'D', 0x00];
 
 const WRITTEN_MARK: i16 = 0x5555;
@@ -21,6 +16,9 @@ const ERASED_MARK: i16 = 0x3c69;
 
 const EMPTY_PAGE: u64 = 0xffffffff;
 
+// Virtual and replace unit numbers are 0xffff when not in use.
+const FREE_UNIT: i16 = -1;
+
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone, FromBytes, ToBytes)]
 #[packbytes(le)]
@@ -88,6 +86,49 @@ pub struct UserControlInformation {
 	pub fold: UnitFoldInformation,
 }
 
+// Problems found while walking the unit chains. Units and pages are indexes into the flashdisk file.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlashdiskIssue {
+	UnreadableSpare { physical_unit: u64 },
+	// The user and spare erase marks disagree or a unit with data was never erased.
+	EraseMarkAnomaly { physical_unit: u64 },
+	VirtualUnitOutOfRange { physical_unit: u64, virtual_unit: i16 },
+	ReplaceUnitOutOfRange { physical_unit: u64, replace_unit: i16 },
+	ChainLoop { physical_unit: u64 },
+	// More than one chain claims the same virtual unit. The chain found last wins.
+	DuplicateVirtualUnit { virtual_unit: u64, physical_unit: u64 },
+	// A page was written by more than one chain rather than replaced within its own chain.
+	PageWrittenTwice { virtual_page: u64, physical_unit: u64 }
+}
+impl std::fmt::Display for FlashdiskIssue {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			FlashdiskIssue::UnreadableSpare { physical_unit } => {
+				write!(f, "Couldn't read the spare data for unit {}.", physical_unit)
+			},
+			FlashdiskIssue::EraseMarkAnomaly { physical_unit } => {
+				write!(f, "Unit {} has a bad erase mark.", physical_unit)
+			},
+			FlashdiskIssue::VirtualUnitOutOfRange { physical_unit, virtual_unit } => {
+				write!(f, "Unit {} points to virtual unit {} which is out of range.", physical_unit, virtual_unit)
+			},
+			FlashdiskIssue::ReplaceUnitOutOfRange { physical_unit, replace_unit } => {
+				write!(f, "Unit {} is replaced by unit {} which is out of range.", physical_unit, replace_unit)
+			},
+			FlashdiskIssue::ChainLoop { physical_unit } => {
+				write!(f, "The unit chain loops back to unit {}.", physical_unit)
+			},
+			FlashdiskIssue::DuplicateVirtualUnit { virtual_unit, physical_unit } => {
+				write!(f, "Unit {} is a second copy of virtual unit {}.", physical_unit, virtual_unit)
+			},
+			FlashdiskIssue::PageWrittenTwice { virtual_page, physical_unit } => {
+				write!(f, "Page {} is written again in unit {}.", virtual_page, physical_unit)
+			}
+		}
+	}
+}
+
 #[allow(dead_code)]
 pub struct PageWriteInfo {
 	pub page_index: usize,
@@ -102,7 +143,9 @@ pub struct FlashdiskIO {
 	collation: BuildIODataCollation,
 	size: u64,
 	created: bool,
+	read_only: bool,
 	file: File,
+	geometry: MDOCGeometry,
 	total_usr_size: u64,
 	total_spr_size: u64,
 	total_units: u64,
@@ -117,46 +160,71 @@ pub struct FlashdiskIO {
 }
 
 impl FlashdiskIO {
-	fn calculate_pages_per_unit(total_usr_size: u64) -> Result<u64, Box<dyn std::error::Error>> {
-		Ok(
-			match total_usr_size {
-				0x1000000 => 0x20,
-				0x800000  => 0x10,
-				0x400000  => 0x05,
-				0x200000  => 0x05,
-				_         => 0x10
-			}
-		)
+	// The size the OS sees for a chip with this much user data. See MDOCGeometry::formatted_size.
+	pub fn calculate_formatted_size(total_usr_size: u64) -> u64 {
+		MDOCGeometry::from_usr_size(total_usr_size).formatted_size()
 	}
 
-	// Size detection can easily be done since the page sizes remain the same across all of WebTV's MDOC chips
-	// If there's anything different we should be able to use the device name from MAME's XML.
-	fn detect_mdoc_config(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-		let file_size = self.file.metadata().unwrap().len();
+	pub fn set_mdoc_config(&mut self, geometry: MDOCGeometry) -> Result<(), Box<dyn std::error::Error>> {
+		if geometry.page_size == 0 || geometry.pages_per_unit == 0 {
+			return Err("The MDOC geometry needs a page size and pages per unit.".into());
+		}
 
-		// usr_size  = (spr_size / SPR_PAGE_SIZE) * USR_PAGE_SIZE
-		// spr_size  = (usr_size / USR_PAGE_SIZE) * SPR_PAGE_SIZE
-		// file_size = usr_size + spr_size
+		self.geometry = geometry;
+		self.total_usr_size = geometry.usr_size();
+		self.total_spr_size = geometry.spare_area_size();
+		self.pages_per_unit = geometry.pages_per_unit;
+		self.total_units = geometry.total_units;
+		self.usr_page_offsets = vec![EMPTY_PAGE; geometry.total_pages as usize];
+		self.current_page = vec![0xff; geometry.page_size as usize];
 
-		let total_usr_size = ((file_size as f64) / (1.0 + ((SPR_PAGE_SIZE as f64) / (USR_PAGE_SIZE as f64)))) as u64;
-		let total_spr_size = file_size - total_usr_size;
-		let pages_per_unit = FlashdiskIO::calculate_pages_per_unit(total_usr_size).unwrap_or(0);
+		Ok(())
+	}
 
-		let _ = self.set_mdoc_config(total_usr_size, total_spr_size, pages_per_unit);
+	fn overlay_pending_writes(&self, page_index: usize, page_offset: usize, buf: &mut [u8]) {
+		for page_write in self.pending_page_writes.iter() {
+			if page_write.page_index == page_index {
+				overlay_pending_write(page_write.page_offset as u64, &page_write.data[0..page_write.size], page_offset as u64, buf);
+			}
+		}
+	}
 
-		Ok(())
+	fn read_unit_spare(&mut self, physical_unit_index: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+		let unit_offset = physical_unit_index * (self.geometry.spare_size * self.pages_per_unit);
+		let mut unit_spare = vec![0xff; (self.geometry.spare_size * self.pages_per_unit) as usize];
+
+		let _ = self.file.seek(SeekFrom::Start(self.total_usr_size + unit_offset))?;
+		self.file.read_exact(&mut unit_spare)?;
+
+		Ok(unit_spare)
 	}
 
-	pub fn set_mdoc_config(&mut self, total_usr_size: u64, total_spr_size: u64, pages_per_unit: u64) -> Result<(), Box<dyn std::error::Error>> {
-		self.total_usr_size = total_usr_size;
-		self.total_spr_size = total_spr_size;
-		self.pages_per_unit = pages_per_unit;
+	// Returns the virtual unit and replace unit if this unit holds data, reporting anything that doesn't look right.
+	fn get_unit_order(&self, physical_unit_index: u64, uci: &UserControlInformation, issues: &mut Vec<FlashdiskIssue>) -> Option<(u64, Option<u64>)> {
+		if uci.erase.usr_erase_status != uci.erase.spr_erase_status
+		|| (uci.erase.usr_erase_status != ERASED_MARK && uci.order.usr_virtual_unit_number != FREE_UNIT) {
+			issues.push(FlashdiskIssue::EraseMarkAnomaly { physical_unit: physical_unit_index });
+		}
 
-		let total_pages = self.total_usr_size / USR_PAGE_SIZE;
-		self.total_units = total_pages / self.pages_per_unit;
-		self.usr_page_offsets = vec![EMPTY_PAGE; total_pages as usize];
+		if uci.erase.usr_erase_status != ERASED_MARK || uci.order.usr_virtual_unit_number == FREE_UNIT {
+			return None;
+		} else if uci.order.usr_virtual_unit_number < 0 || uci.order.usr_virtual_unit_number as u64 >= self.total_units {
+			issues.push(FlashdiskIssue::VirtualUnitOutOfRange { physical_unit: physical_unit_index, virtual_unit: uci.order.usr_virtual_unit_number });
 
-		Ok(())
+			return None;
+		}
+
+		let replace_unit_index = if uci.order.usr_replace_unit_number == FREE_UNIT {
+			None
+		} else if uci.order.usr_replace_unit_number < 0 || uci.order.usr_replace_unit_number as u64 >= self.total_units {
+			issues.push(FlashdiskIssue::ReplaceUnitOutOfRange { physical_unit: physical_unit_index, replace_unit: uci.order.usr_replace_unit_number });
+
+			None
+		} else {
+			Some(uci.order.usr_replace_unit_number as u64)
+		};
+
+		Some((uci.order.usr_virtual_unit_number as u64, replace_unit_index))
 	}
 
 	/*
@@ -216,77 +284,160 @@ impl FlashdiskIO {
 	 * correctly to the OS even though it's out of order on the NAND flash.
 	 *
 	 * enumerate_pages reads in the spare area of the M-Systems DiskOnChip file and does the translation
-	 * so page indexes point to the correct data.
+	 * so page indexes point to the correct data. Each chain is walked from the unit no other unit replaces
+	 * so later units in the chain win. Anything odd found on the way is returned so it can be reported.
 	 * 
 	 */
 
-	fn enumerate_pages(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-		let mut logical_unit_index = 0;
-		while logical_unit_index < self.total_units {
-			let mut physical_unit_index = logical_unit_index;
-
-
-			let mut chain_index = 0;
-			while chain_index < self.total_units {
-				let unit_offset = physical_unit_index * (SPR_PAGE_SIZE * self.pages_per_unit);
-				let _ = self.file.seek(SeekFrom::Start(self.total_usr_size + unit_offset))?;
-				match UserControlInformation::read_packed(&mut self.file) {
-					Ok(uci) => {
-						if uci.erase.usr_erase_status == ERASED_MARK && uci.order.usr_virtual_unit_number > -1 {
-							let mut page_index = 0;
-							while page_index < self.pages_per_unit {
-								let page_offset = unit_offset + (page_index * SPR_PAGE_SIZE);
-								let _ = self.file.seek(SeekFrom::Start(self.total_usr_size + page_offset))?;
-								match PageInformation::read_packed(&mut self.file) {
-									Ok(pi) => {
-										if pi.usr_data_status == WRITTEN_MARK {
-											let virtual_page_index = ((uci.order.usr_virtual_unit_number as u64 * self.pages_per_unit) + page_index) as usize;
-											let usr_page_offset = ((physical_unit_index * self.pages_per_unit) + page_index) * USR_PAGE_SIZE;
-
-											if virtual_page_index < self.usr_page_offsets.iter().len() {
-												self.usr_page_offsets[virtual_page_index] = usr_page_offset;
-											}
-										}
-									},
-									_ => {
-										//
-									}
-								};
-								page_index += 1;
-							}
-							if uci.order.usr_replace_unit_number != -1 {
-								physical_unit_index = uci.order.usr_replace_unit_number as u64;
-							} else {
-								break;
+	fn enumerate_pages(&mut self) -> Result<Vec<FlashdiskIssue>, Box<dyn std::error::Error>> {
+		let mut issues: Vec<FlashdiskIssue> = vec![];
+
+		self.usr_page_offsets.fill(EMPTY_PAGE);
+
+		if self.pages_per_unit == 0 {
+			return Ok(issues);
+		}
+
+		let mut unit_spares: Vec<Vec<u8>> = vec![];
+		let mut unit_orders: Vec<Option<(u64, Option<u64>)>> = vec![];
+		for physical_unit_index in 0..self.total_units {
+			let unit_spare = self.read_unit_spare(physical_unit_index).unwrap_or(vec![]);
+
+			match UserControlInformation::read_packed(&mut &unit_spare[..]) {
+				Ok(uci) => {
+					unit_orders.push(self.get_unit_order(physical_unit_index, &uci, &mut issues));
+				},
+				_ => {
+					issues.push(FlashdiskIssue::UnreadableSpare { physical_unit: physical_unit_index });
+					unit_orders.push(None);
+				}
+			};
+
+			unit_spares.push(unit_spare);
+		}
+
+		let mut replaced_units = vec![false; self.total_units as usize];
+		for unit_order in unit_orders.iter() {
+			if let Some((_, Some(replace_unit_index))) = unit_order {
+				replaced_units[*replace_unit_index as usize] = true;
+			}
+		}
+
+		// Start with units at the head of a chain then pick up anything only reachable from a loop.
+		let mut chain_heads: Vec<u64> = (0..self.total_units)
+			.filter(|&unit_index| unit_orders[unit_index as usize].is_some() && !replaced_units[unit_index as usize])
+			.collect();
+		chain_heads.extend((0..self.total_units).filter(|&unit_index| unit_orders[unit_index as usize].is_some() && replaced_units[unit_index as usize]));
+
+		let mut visited_units = vec![false; self.total_units as usize];
+		let mut virtual_unit_heads: Vec<Option<u64>> = vec![None; self.total_units as usize];
+		let mut page_chain_heads: Vec<Option<u64>> = vec![None; self.usr_page_offsets.len()];
+		for chain_head_index in chain_heads {
+			if visited_units[chain_head_index as usize] {
+				continue;
+			}
+
+			let mut chain: Vec<u64> = vec![];
+			let mut physical_unit_index = chain_head_index;
+			while let Some((virtual_unit_index, replace_unit_index)) = unit_orders[physical_unit_index as usize] {
+				if chain.contains(&physical_unit_index) {
+					issues.push(FlashdiskIssue::ChainLoop { physical_unit: physical_unit_index });
+					break;
+				}
+
+				if chain.is_empty() {
+					match virtual_unit_heads[virtual_unit_index as usize] {
+						Some(_) => {
+							issues.push(FlashdiskIssue::DuplicateVirtualUnit { virtual_unit: virtual_unit_index, physical_unit: physical_unit_index });
+						},
+						_ => {
+							virtual_unit_heads[virtual_unit_index as usize] = Some(physical_unit_index);
+						}
+					};
+				}
+
+				chain.push(physical_unit_index);
+				visited_units[physical_unit_index as usize] = true;
+
+				let mut page_index = 0;
+				while page_index < self.pages_per_unit {
+					let page_spare_offset = (page_index * self.geometry.spare_size) as usize;
+
+					match PageInformation::read_packed(&mut &unit_spares[physical_unit_index as usize][page_spare_offset..]) {
+						Ok(pi) => {
+							if pi.usr_data_status == WRITTEN_MARK {
+								let virtual_page_index = ((virtual_unit_index * self.pages_per_unit) + page_index) as usize;
+								let usr_page_offset = ((physical_unit_index * self.pages_per_unit) + page_index) * self.geometry.page_size;
+
+								if virtual_page_index < self.usr_page_offsets.len() {
+									match page_chain_heads[virtual_page_index] {
+										Some(page_chain_head_index) if page_chain_head_index != chain_head_index => {
+											issues.push(FlashdiskIssue::PageWrittenTwice { virtual_page: virtual_page_index as u64, physical_unit: physical_unit_index });
+										},
+										_ => { }
+									};
+
+									page_chain_heads[virtual_page_index] = Some(chain_head_index);
+									self.usr_page_offsets[virtual_page_index] = usr_page_offset;
+								}
 							}
-						} else {
-							break;
+						},
+						_ => {
+							//
 						}
-					}
+					};
+					page_index += 1;
+				}
+
+				match replace_unit_index {
+					Some(replace_unit_index) => {
+						physical_unit_index = replace_unit_index;
+					},
 					_ => {
-						//
+						break;
 					}
 				};
-				chain_index += 1;
 			}
-			logical_unit_index += 1;
 		}
 
-		Ok(())
+		Ok(issues)
 	}
-}
-impl BuildIO for FlashdiskIO {
-	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-		Ok(self.file_path.clone())
+
+	// Opens the flashdisk without boxing it so it can be checked or repaired.
+	// The geometry is worked out from the file size unless one is forced.
+	pub fn open_flashdisk(file_path: String, collation: Option<BuildIODataCollation>, geometry: Option<MDOCGeometry>, read_only: bool) -> Result<FlashdiskIO, Box<dyn std::error::Error>> {
+		let file = OpenOptions::new().read(true).write(!read_only).open(file_path.clone()).map_err(|e| LauncherError::from_io_error(&file_path, &e))?;
+		let size = file.metadata()?.len();
+
+		let mut io = FlashdiskIO::new_flashdisk(file_path, collation, file, false);
+		io.read_only = read_only;
+		io.size = size;
+		io.set_mdoc_config(geometry.unwrap_or(MDOCGeometry::from_file_size(size)))?;
+
+		let _ = io.enumerate_pages();
+
+		Ok(io)
 	}
 
-	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
-		let mut io = FlashdiskIO {
-			file_path: file_path.clone(),
+	pub fn create_flashdisk(file_path: String, collation: Option<BuildIODataCollation>, geometry: MDOCGeometry) -> Result<FlashdiskIO, Box<dyn std::error::Error>> {
+		let file = OpenOptions::new().read(true).write(true).create(true).open(file_path.clone()).map_err(|e| LauncherError::from_io_error(&file_path, &e))?;
+
+		let mut io = FlashdiskIO::new_flashdisk(file_path, collation, file, true);
+		io.size = geometry.usr_size();
+		io.set_mdoc_config(geometry)?;
+
+		Ok(io)
+	}
+
+	fn new_flashdisk(file_path: String, collation: Option<BuildIODataCollation>, file: File, created: bool) -> FlashdiskIO {
+		FlashdiskIO {
+			file_path: file_path,
 			collation: collation.unwrap_or(BuildIODataCollation::Raw),
 			size: 0,
-			created: false,
-			file: OpenOptions::new().read(true).write(true).open(file_path.clone())?,
+			created: created,
+			read_only: false,
+			file: file,
+			geometry: MDOCGeometry::from_usr_size(0),
 			total_usr_size: 0,
 			total_spr_size: 0,
 			total_units: 0,
@@ -295,50 +446,260 @@ impl BuildIO for FlashdiskIO {
 			current_page_index: 0,
 			current_page_offset: 0,
 			current_page_read: false,
-			current_page: vec![0xff; USR_PAGE_SIZE as usize],
+			current_page: vec![],
 			pending_page_writes: vec![]
-		};
+		}
+	}
 
-		io.size = io.file.metadata().unwrap().len();
+	// Size of the disk the OS sees. See MDOCGeometry::logical_size.
+	pub fn logical_size(&self) -> u64 {
+		self.geometry.logical_size()
+	}
 
-		let _= io.detect_mdoc_config();
+	// Raw DiskOnChip dumps keep each page's spare data right after the page instead of at the end like MAME.
+	pub fn is_interleaved_dump_size(dump_size: u64, geometry: &MDOCGeometry) -> bool {
+		dump_size > 0 && dump_size == geometry.total_pages * (geometry.page_size + geometry.spare_size)
+	}
 
-		let _ = io.enumerate_pages();
+	// Writes the disk as the OS sees it out to a plain linear image.
+	pub fn export_linear_image(&mut self, image_file_path: String) -> Result<u64, Box<dyn std::error::Error>> {
+		let logical_size = self.logical_size();
 
-		Ok(Box::new(io))
+		if logical_size == 0 {
+			return Err("There's no data on the flashdisk to export.".into());
+		}
+
+		let mut dstf = File::create(&image_file_path)?;
+		let exported_size = std::io::copy(&mut BuildIOStream::new(self)?.take(logical_size), &mut dstf)?;
+
+		Ok(exported_size)
 	}
 
-	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
-		let mut io = FlashdiskIO {
-			file_path: file_path.clone(),
-			collation: collation.unwrap_or(BuildIODataCollation::Raw),
-			size: size,
-			created: true,
-			file: OpenOptions::new().read(true).write(true).create(true).open(file_path.clone())?,
-			total_usr_size: 0,
-			total_spr_size: 0,
-			total_units: 0,
-			pages_per_unit: 0,
-			usr_page_offsets: vec![],
-			current_page_index: 0,
-			current_page_offset: 0,
-			current_page_read: false,
-			current_page: vec![0xff; USR_PAGE_SIZE as usize],
-			pending_page_writes: vec![]
-		};
+	// Creates a new MAME mdoc file with a linear image's data behind a clean NFTL. The image is read in the collation it was dumped in.
+	pub fn import_linear_image(file_path: String, image_file_path: String, image_collation: Option<BuildIODataCollation>, geometry: MDOCGeometry) -> Result<FlashdiskIO, Box<dyn std::error::Error>> {
+		let mut image_io = RawDiskIO::open_read_only(image_file_path.clone(), image_collation)?;
+		let image_size = image_io.len()?;
+
+		// Checked before the mdoc file is created so a rejected image doesn't leave an empty one behind.
+		if image_size == 0 {
+			return Err("The image is empty.".into());
+		} else if image_size > geometry.logical_size() {
+			return Err(Box::new(LauncherError::WrongSize { file_path: image_file_path, expected_size: geometry.logical_size(), found_size: image_size }));
+		}
+
+		let mut io = FlashdiskIO::create_flashdisk(file_path, None, geometry)?;
+
+		{
+			// Flushing the stream commits the NFTL.
+			let mut srcf = BuildIOStream::new(image_io.as_mut())?;
+			let mut dstf = BuildIOStream::new(&mut io)?;
+			std::io::copy(&mut srcf, &mut dstf)?;
+			dstf.flush()?;
+		}
+
+		Ok(io)
+	}
+
+	// Splits a raw DiskOnChip dump into MAME's layout. The NFTL is kept as-is so it can be checked afterwards.
+	pub fn import_interleaved_dump(file_path: String, dump_file_path: String, geometry: MDOCGeometry) -> Result<FlashdiskIO, Box<dyn std::error::Error>> {
+		let dump_data = std::fs::read(&dump_file_path).map_err(|e| LauncherError::from_io_error(&dump_file_path, &e))?;
+
+		if !FlashdiskIO::is_interleaved_dump_size(dump_data.len() as u64, &geometry) {
+			return Err(Box::new(LauncherError::WrongSize {
+				file_path: dump_file_path,
+				expected_size: geometry.total_pages * (geometry.page_size + geometry.spare_size),
+				found_size: dump_data.len() as u64
+			}));
+		}
+
+		let dump_page_size = (geometry.page_size + geometry.spare_size) as usize;
+		let mut usr_data: Vec<u8> = Vec::with_capacity(geometry.usr_size() as usize);
+		let mut spr_data: Vec<u8> = Vec::with_capacity(geometry.spare_area_size() as usize);
+		for dump_page in dump_data.chunks_exact(dump_page_size) {
+			usr_data.extend_from_slice(&dump_page[0..(geometry.page_size as usize)]);
+			spr_data.extend_from_slice(&dump_page[(geometry.page_size as usize)..]);
+		}
+
+		// Create a backup
+		if Path::new(&file_path).exists() {
+			let _ = std::fs::copy(&file_path, file_path.clone() + ".bak");
+		}
+
+		write_file_atomically(&file_path, |dstf| {
+			dstf.write_all(&usr_data)?;
+			dstf.write_all(&spr_data)?;
+
+			Ok(())
+		})?;
+
+		FlashdiskIO::open_flashdisk(file_path, None, Some(geometry), false)
+	}
+
+	// Walks every unit and reports problems with the NFTL. Nothing is written.
+	pub fn check(&mut self) -> Result<Vec<FlashdiskIssue>, Box<dyn std::error::Error>> {
+		if self.pages_per_unit == 0 {
+			return Err("Couldn't work out the MDOC geometry from the file size.".into());
+		}
+
+		self.enumerate_pages()
+	}
+
+	// Writes the data back out the way it currently reads with a clean NFTL so any broken chains are gone.
+	pub fn repair(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		if self.pages_per_unit == 0 {
+			return Err("Couldn't work out the MDOC geometry from the file size.".into());
+		}
+
+		self.write_nftl()
+	}
+
+	// We read in the entire disk, recreate the spare table then write the disk back to a file (saving a backup)
+	// This is less complex than trying to write to disk normally since we don't need to keep track of replace units, wear leveling etc...
+	// and is possible since this isn't real hardware where keeping track of those things matter.
+	fn write_nftl(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		if self.read_only {
+			return Err(READ_ONLY_ERROR.into());
+		}
+
+		let mut usr_data = vec![0xff as u8; self.total_usr_size as usize];
+		let mut spr_data = vec![0xff as u8; self.total_spr_size as usize];
+
+		if usr_data.len() > 0 {
+			let usr_start_index = (self.geometry.header_units * (self.geometry.page_size * self.pages_per_unit)) as usize;
+
+			let _ = self.seek(0);
+			self.read(&mut usr_data[usr_start_index..])?;
+
+			for pri in self.pending_page_writes.iter() {
+				let usr_index = usr_start_index + (pri.page_index * self.geometry.page_size as usize) + pri.page_offset;
+
+				usr_data[usr_index..(usr_index + pri.size)]
+					.copy_from_slice(&pri.data[0..pri.size]);
+			}
+
+			for header_index in 0..self.geometry.header_units {
+				let usr_index = (header_index * (self.geometry.page_size * self.pages_per_unit)) as usize;
+
+				let disk_information = DiskInformation {
+					magic: DISK_MAGIC,
+					total_usable_units: (self.total_usr_size / (self.geometry.page_size * self.pages_per_unit)) as i16,
+					frist_usable_unit: 0,
+					usable_size: (self.total_usr_size as usize - usr_start_index) as i32
+				}.to_le_bytes();
+
+				usr_data[usr_index..(usr_index + disk_information.len()) as usize]
+					.copy_from_slice(&disk_information);
+			}
+
+			if spr_data.len() > 0 {
+				let mut unit_index = 0;
+				let mut usable_unit_index = 0;
+				let mut page_index = 0;
+				let mut unit_spr_index = 0;
+				let mut page_spr_index = 0;
+				let mut unit_written_to = false;
+				for start_index in (0..usr_data.len()).step_by(self.geometry.page_size as usize) {
+					let end_index = (start_index + self.geometry.page_size as usize).min(usr_data.len());
+
+					let page_written_to = match usr_data[start_index..end_index].iter().find(|&&b| b != 0xff) {
+						Some(_) => true,
+						_ => false
+					};
+
+					if page_written_to {
+						unit_written_to = true;
+
+						let page_info = PageInformation {
+							usr_ecc_data: [0x00; 6],
+							usr_data_status: WRITTEN_MARK
+						}.to_le_bytes();
+
+
+						spr_data[page_spr_index as usize..(page_spr_index + (self.geometry.spare_size / 2)) as usize]
+							.copy_from_slice(&page_info);
+					}
+
+					page_spr_index += self.geometry.spare_size;
+
+					page_index += 1;
+					if page_index == self.pages_per_unit {
+						if unit_index >= self.geometry.header_units {
+							if unit_written_to {
+								spr_data[(unit_spr_index + (self.geometry.spare_size / 2)) as usize..(unit_spr_index + self.geometry.spare_size) as usize]
+								.copy_from_slice(&UnitOrderInformation {
+									usr_virtual_unit_number: usable_unit_index as i16,
+									usr_replace_unit_number: -1,
+									spr_virtual_unit_number: usable_unit_index as i16,
+									spr_replace_unit_number: -1
+								}.to_le_bytes());
+							}
+
+							usable_unit_index += 1;
+						}
+
+						unit_spr_index += self.geometry.spare_size;
+
+						// All units should be at least erased even if nothing was written to them.
+
+						spr_data[(unit_spr_index + (self.geometry.spare_size / 2)) as usize..(unit_spr_index + self.geometry.spare_size) as usize]
+						.copy_from_slice(&UnitEraseInformation {
+							wear_info: 1,
+							usr_erase_status: ERASED_MARK,
+							spr_erase_status: ERASED_MARK
+						}.to_le_bytes());
+
+						unit_index += 1;
+						page_index = 0;
+						unit_spr_index = page_spr_index;
+						unit_written_to = false;
+					}
+				}
+			}
+
+			// Create a backup
+			if Path::new(&self.file_path).exists() {
+				let _ = std::fs::copy(&self.file_path, self.file_path.clone() + ".bak");
+			}
+
+			write_file_atomically(&self.file_path, |dstf| {
+				dstf.write_all(&usr_data)?;
+				dstf.write_all(&spr_data)?;
+
+				Ok(())
+			})?;
+
+			self.file = OpenOptions::new().read(true).write(true).open(self.file_path.clone())?;
+			self.pending_page_writes.clear();
 
-		let pages_per_unit = FlashdiskIO::calculate_pages_per_unit(size).unwrap_or(0);
+			// Page offsets need to point to where the pages were just written.
+			let _ = self.enumerate_pages();
+		}
 
-		let _ = io.set_mdoc_config(size, (size / USR_PAGE_SIZE) * SPR_PAGE_SIZE, pages_per_unit);
+		Ok(())
+	}
+}
+impl BuildIO for FlashdiskIO {
+	fn file_path(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+		Ok(self.file_path.clone())
+	}
 
-		Ok(Box::new(io))
+	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		Ok(Box::new(FlashdiskIO::open_flashdisk(file_path, collation, None, false)?))
+	}
+
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		Ok(Box::new(FlashdiskIO::open_flashdisk(file_path, collation, None, true)?))
+	}
+
+	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		Ok(Box::new(FlashdiskIO::create_flashdisk(file_path, collation, MDOCGeometry::from_usr_size(size))?))
 	}
 
 	fn seek(&mut self, pos: u64) -> Result<u64, Box<dyn std::error::Error>>  {
-		self.current_page_index = (pos / USR_PAGE_SIZE) as usize;
+		self.current_page_index = (pos / self.geometry.page_size) as usize;
 
 		if self.current_page_index < self.usr_page_offsets.iter().len() {
-			self.current_page_offset = (pos % USR_PAGE_SIZE) as usize;
+			self.current_page_offset = (pos % self.geometry.page_size) as usize;
 			self.current_page_read = false;
 
 			Ok(pos)
@@ -352,7 +713,7 @@ impl BuildIO for FlashdiskIO {
 	}
 
 	fn stream_position(&mut self) -> Result<u64, Box<dyn std::error::Error>>  {
-		Ok((self.current_page_index as u64 * USR_PAGE_SIZE) + self.current_page_offset as u64)
+		Ok((self.current_page_index as u64 * self.geometry.page_size) + self.current_page_offset as u64)
 	}
 
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
@@ -370,6 +731,7 @@ impl BuildIO for FlashdiskIO {
 					let page_offset = self.usr_page_offsets[self.current_page_index];
 					if page_offset == EMPTY_PAGE {
 						self.current_page.fill(0xff);
+						self.current_page_read = true;
 					} else {
 						self.file.seek(SeekFrom::Start(page_offset))?;
 
@@ -384,9 +746,12 @@ impl BuildIO for FlashdiskIO {
 					}
 				}
 	
+				let read_page_index = self.current_page_index;
+				let read_page_offset = self.current_page_offset;
+
 				let mut current_read_size = need_total_size - read_total_size;
-				if current_read_size > (USR_PAGE_SIZE as usize - self.current_page_offset) {
-					current_read_size = USR_PAGE_SIZE as usize - self.current_page_offset;
+				if current_read_size > (self.geometry.page_size as usize - self.current_page_offset) {
+					current_read_size = self.geometry.page_size as usize - self.current_page_offset;
 					self.current_page_read = false;
 					self.current_page_index += 1;
 
@@ -398,6 +763,8 @@ impl BuildIO for FlashdiskIO {
 				buf[current_buf_index as usize..(current_buf_index + current_read_size) as usize]
 					.copy_from_slice(&self.current_page[self.current_page_offset as usize..(self.current_page_offset + current_read_size) as usize]);
 
+				self.overlay_pending_writes(read_page_index, read_page_offset, &mut buf[current_buf_index as usize..(current_buf_index + current_read_size) as usize]);
+
 				current_buf_index += current_read_size;
 				read_total_size += current_read_size;
 
@@ -415,7 +782,9 @@ impl BuildIO for FlashdiskIO {
 	}
 
 	fn write(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
-		if buf.len() < 0x4 {
+		if self.read_only {
+			return Err(READ_ONLY_ERROR.into());
+		} else if buf.len() < 0x4 {
 			return Err("Buffer length needs to be 4 bytes or greater.".into());
 		} else if (buf.len() & 1) == 1 {
 			return Err("Buffer length needs to be a multiple of 2.".into());
@@ -429,10 +798,11 @@ impl BuildIO for FlashdiskIO {
 				let write_page_index = self.current_page_index;
 				let write_page_offset = self.current_page_offset;
 
-				if current_write_size > (USR_PAGE_SIZE as usize - self.current_page_offset) {
-					current_write_size = USR_PAGE_SIZE as usize - self.current_page_offset;
+				if current_write_size > (self.geometry.page_size as usize - self.current_page_offset) {
+					current_write_size = self.geometry.page_size as usize - self.current_page_offset;
 					self.current_page_index += 1;
 					self.current_page_offset = 0;
+					self.current_page_read = false;
 				} else {
 					self.current_page_offset += current_write_size;
 				}
@@ -454,134 +824,380 @@ impl BuildIO for FlashdiskIO {
 		//Ok(self.file.write(buf)?)
 	}
 
-	// We read in the entire disk, recreate the spare table then write the disk back to a file (saving a backup)
-	// This is less complex than trying to write to disk normally since we don't need to keep track of replace units, wear leveling etc...
-	// and is possible since this isn't real hardware where keeping track of those things matter.
 	fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
 		if self.pending_page_writes.len() > 0 {
-			let mut usr_data = vec![0xff as u8; self.total_usr_size as usize];
-			let mut spr_data = vec![0xff as u8; self.total_spr_size as usize];
+			self.write_nftl()?;
+		}
 
-			if usr_data.len() > 0 {
-				let usr_start_index = (DISKINFO_UNITS * (USR_PAGE_SIZE * self.pages_per_unit)) as usize;
+		Ok(())
+	}
 
-				let _ = self.seek(0);
-				let _ = self.read(&mut usr_data[usr_start_index..]);
+	fn len(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+		Ok(self.total_usr_size)
+	}
 
-				for pri in self.pending_page_writes.iter() {
-					let usr_index = usr_start_index + (pri.page_index * USR_PAGE_SIZE as usize) + pri.page_offset;
+	fn collation(&mut self) -> Result<BuildIODataCollation, Box<dyn std::error::Error>> {
+		Ok(self.collation)
+	}
+}
 
-					usr_data[usr_index..(usr_index + pri.size)]
-						.copy_from_slice(&pri.data[0..pri.size]);
-				}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::get_test_file_path;
 
-				self.pending_page_writes.clear();
+	// Eight units of 16 pages. Units 0 and 1 hold the disk info so data starts at unit 2.
+	const TEST_USR_SIZE: u64 = 0x10000;
 
-				for header_index in 0..DISKINFO_UNITS {
-					let usr_index = (header_index * (USR_PAGE_SIZE * self.pages_per_unit)) as usize;
+	fn get_test_geometry() -> MDOCGeometry {
+		MDOCGeometry::from_usr_size(TEST_USR_SIZE)
+	}
 
-					let disk_information = DiskInformation {
-						magic: DISK_MAGIC,
-						total_usable_units: (self.total_usr_size / (USR_PAGE_SIZE * self.pages_per_unit)) as i16,
-						frist_usable_unit: 0,
-						usable_size: (self.total_usr_size as usize - usr_start_index) as i32
-					}.to_le_bytes();
+	fn get_unit_spare_offset(geometry: &MDOCGeometry, physical_unit: u64, page_index: u64) -> usize {
+		(geometry.usr_size() + (((physical_unit * geometry.pages_per_unit) + page_index) * geometry.spare_size)) as usize
+	}
 
-					usr_data[usr_index..(usr_index + disk_information.len()) as usize]
-						.copy_from_slice(&disk_information);
-				}
+	fn set_test_unit(data: &mut Vec<u8>, geometry: &MDOCGeometry, physical_unit: u64, virtual_unit: i16, replace_unit: i16) {
+		let order_offset = get_unit_spare_offset(geometry, physical_unit, 0) + 8;
+		data[order_offset..(order_offset + 8)].copy_from_slice(&UnitOrderInformation {
+			usr_virtual_unit_number: virtual_unit,
+			usr_replace_unit_number: replace_unit,
+			spr_virtual_unit_number: virtual_unit,
+			spr_replace_unit_number: replace_unit
+		}.to_le_bytes());
 
-				if spr_data.len() > 0 {
-					let mut unit_index = 0;
-					let mut usable_unit_index = 0;
-					let mut page_index = 0;
-					let mut unit_spr_index = 0;
-					let mut page_spr_index = 0;
-					let mut unit_written_to = false;
-					for start_index in (0..usr_data.len()).step_by(USR_PAGE_SIZE as usize) {
-						let end_index = (start_index + USR_PAGE_SIZE as usize).min(usr_data.len());
-
-						let page_written_to = match usr_data[start_index..end_index].iter().find(|&&b| b != 0xff) {
-							Some(_) => true,
-							_ => false
-						};
+		set_test_erase_marks(data, geometry, physical_unit, ERASED_MARK, ERASED_MARK);
+	}
 
-						if page_written_to {
-							unit_written_to = true;
+	fn set_test_erase_marks(data: &mut Vec<u8>, geometry: &MDOCGeometry, physical_unit: u64, usr_erase_status: i16, spr_erase_status: i16) {
+		let erase_offset = get_unit_spare_offset(geometry, physical_unit, 1) + 8;
+		data[erase_offset..(erase_offset + 8)].copy_from_slice(&UnitEraseInformation {
+			wear_info: 1,
+			usr_erase_status: usr_erase_status,
+			spr_erase_status: spr_erase_status
+		}.to_le_bytes());
+	}
 
-							let page_info = PageInformation {
-								usr_ecc_data: [0x00; 6],
-								usr_data_status: WRITTEN_MARK
-							}.to_le_bytes();
+	fn set_test_page(data: &mut Vec<u8>, geometry: &MDOCGeometry, physical_unit: u64, page_index: u64, fill: u8) {
+		let page_offset = (((physical_unit * geometry.pages_per_unit) + page_index) * geometry.page_size) as usize;
+		data[page_offset..(page_offset + geometry.page_size as usize)].fill(fill);
 
+		let page_info_offset = get_unit_spare_offset(geometry, physical_unit, page_index);
+		data[page_info_offset..(page_info_offset + 8)].copy_from_slice(&PageInformation {
+			usr_ecc_data: [0x00; 6],
+			usr_data_status: WRITTEN_MARK
+		}.to_le_bytes());
+	}
 
-							spr_data[page_spr_index as usize..(page_spr_index + (SPR_PAGE_SIZE / 2)) as usize]
-								.copy_from_slice(&page_info);
-						}
+	// Virtual units 0 and 1 in physical units 2 and 3 with a couple of pages written to each.
+	fn get_test_flashdisk_data() -> Vec<u8> {
+		let geometry = get_test_geometry();
+		let mut data = vec![0xff; geometry.file_size() as usize];
 
-						page_spr_index += SPR_PAGE_SIZE;
-
-						page_index += 1;
-						if page_index == self.pages_per_unit {
-							if unit_index >= DISKINFO_UNITS {
-								if unit_written_to {
-									spr_data[(unit_spr_index + (SPR_PAGE_SIZE / 2)) as usize..(unit_spr_index + SPR_PAGE_SIZE) as usize]
-									.copy_from_slice(&UnitOrderInformation {
-										usr_virtual_unit_number: usable_unit_index as i16,
-										usr_replace_unit_number: -1,
-										spr_virtual_unit_number: usable_unit_index as i16,
-										spr_replace_unit_number: -1
-									}.to_le_bytes());
-								}
+		set_test_unit(&mut data, &geometry, 2, 0, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 2, 0, 0x20);
+		set_test_page(&mut data, &geometry, 2, 1, 0x21);
 
-								usable_unit_index += 1;
-							}
+		set_test_unit(&mut data, &geometry, 3, 1, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 3, 0, 0x30);
+		set_test_page(&mut data, &geometry, 3, 5, 0x35);
 
-							unit_spr_index += SPR_PAGE_SIZE;
+		data
+	}
 
-							// All units should be at least erased even if nothing was written to them.
+	// Each flashdisk has one fault in its spare area along with the issues check should report for it.
+	fn get_faulty_flashdisks() -> Vec<(&'static str, Vec<u8>, Vec<FlashdiskIssue>)> {
+		let geometry = get_test_geometry();
+		let mut faulty_flashdisks = vec![];
+
+		// Unit 4 replaces unit 2 and unit 2 replaces unit 4.
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 2, 0, 4);
+		set_test_unit(&mut data, &geometry, 4, 0, 2);
+		set_test_page(&mut data, &geometry, 4, 1, 0x41);
+		faulty_flashdisks.push(("chain_loop", data, vec![
+			FlashdiskIssue::ChainLoop { physical_unit: 2 }
+		]));
+
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 4, 0, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 4, 2, 0x42);
+		faulty_flashdisks.push(("duplicate_virtual_unit", data, vec![
+			FlashdiskIssue::DuplicateVirtualUnit { virtual_unit: 0, physical_unit: 4 }
+		]));
+
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 4, 0x64, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 4, 0, 0x40);
+		faulty_flashdisks.push(("virtual_unit_out_of_range", data, vec![
+			FlashdiskIssue::VirtualUnitOutOfRange { physical_unit: 4, virtual_unit: 0x64 }
+		]));
+
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 3, 1, 0x32);
+		faulty_flashdisks.push(("replace_unit_out_of_range", data, vec![
+			FlashdiskIssue::ReplaceUnitOutOfRange { physical_unit: 3, replace_unit: 0x32 }
+		]));
+
+		// Unit 4 starts its own chain for virtual unit 0 and writes the same page as unit 2.
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 4, 0, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 4, 1, 0x41);
+		faulty_flashdisks.push(("page_written_twice", data, vec![
+			FlashdiskIssue::DuplicateVirtualUnit { virtual_unit: 0, physical_unit: 4 },
+			FlashdiskIssue::PageWrittenTwice { virtual_page: 1, physical_unit: 4 }
+		]));
+
+		let mut data = get_test_flashdisk_data();
+		set_test_erase_marks(&mut data, &geometry, 3, ERASED_MARK, 0x1234);
+		faulty_flashdisks.push(("erase_mark_anomaly", data, vec![
+			FlashdiskIssue::EraseMarkAnomaly { physical_unit: 3 }
+		]));
+
+		faulty_flashdisks
+	}
 
-							spr_data[(unit_spr_index + (SPR_PAGE_SIZE / 2)) as usize..(unit_spr_index + SPR_PAGE_SIZE) as usize]
-							.copy_from_slice(&UnitEraseInformation {
-								wear_info: 1,
-								usr_erase_status: ERASED_MARK,
-								spr_erase_status: ERASED_MARK
-							}.to_le_bytes());
+	fn read_logical_data(io: &mut FlashdiskIO) -> Vec<u8> {
+		let mut data = vec![0x00; io.logical_size() as usize];
 
-							unit_index += 1;
-							page_index = 0;
-							unit_spr_index = page_spr_index;
-							unit_written_to = false;
-						}
-					}
-				}
+		io.seek(0x00).unwrap();
+		io.read(&mut data).unwrap();
 
-				// Create a backup
-				if Path::new(&self.file_path).exists() {
-					let _ = std::fs::copy(&self.file_path, self.file_path.clone() + ".bak");
-				}
+		data
+	}
 
-				match File::create(&self.file_path) {
-					Ok(mut dstf) => {
-						let _ = dstf.seek(SeekFrom::Start(0));
-						let _ = dstf.write(&usr_data);
-						let _ = dstf.write(&spr_data);
-					},
-					_ => {
-						//
-					}
-				};
-			}
+	#[test]
+	fn check_finds_nothing_wrong_with_a_clean_nftl() {
+		let file_path = get_test_file_path("flashdisk_clean");
+		std::fs::write(&file_path, get_test_flashdisk_data()).unwrap();
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		assert_eq!(io.check().unwrap(), vec![]);
+
+		let data = read_logical_data(&mut io);
+		assert_eq!(data[0x0000..0x0200], [0x20; 0x200]);
+		assert_eq!(data[0x0200..0x0400], [0x21; 0x200]);
+		assert_eq!(data[0x2000..0x2200], [0x30; 0x200]);
+		assert_eq!(data[0x2a00..0x2c00], [0x35; 0x200]);
+		assert!(data[0x0400..0x2000].iter().all(|&b| b == 0xff));
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+
+	#[test]
+	fn check_reports_each_fault() {
+		for (name, data, expected_issues) in get_faulty_flashdisks() {
+			let file_path = get_test_file_path(&("flashdisk_check_".to_owned() + name));
+			std::fs::write(&file_path, data).unwrap();
+
+			let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+			assert_eq!(io.check().unwrap(), expected_issues, "{}", name);
+
+			let _ = std::fs::remove_file(&file_path);
 		}
+	}
 
-		Ok(())
+	#[test]
+	fn check_follows_the_replace_chain() {
+		let geometry = get_test_geometry();
+		let file_path = get_test_file_path("flashdisk_replace_chain");
+
+		// Unit 4 replaces page 1 of unit 2.
+		let mut data = get_test_flashdisk_data();
+		set_test_unit(&mut data, &geometry, 2, 0, 4);
+		set_test_unit(&mut data, &geometry, 4, 0, FREE_UNIT);
+		set_test_page(&mut data, &geometry, 4, 1, 0x41);
+		std::fs::write(&file_path, data).unwrap();
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		assert_eq!(io.check().unwrap(), vec![]);
+
+		let data = read_logical_data(&mut io);
+		assert_eq!(data[0x0000..0x0200], [0x20; 0x200]);
+		assert_eq!(data[0x0200..0x0400], [0x41; 0x200]);
+
+		let _ = std::fs::remove_file(&file_path);
 	}
 
-	fn len(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
-		Ok(self.total_usr_size)
+	#[test]
+	fn repair_keeps_the_data_and_clears_every_fault() {
+		for (name, data, _) in get_faulty_flashdisks() {
+			let file_path = get_test_file_path(&("flashdisk_repair_".to_owned() + name));
+			std::fs::write(&file_path, data).unwrap();
+
+			let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, false).unwrap();
+			let logical_data = read_logical_data(&mut io);
+
+			io.repair().unwrap();
+			assert_eq!(io.check().unwrap(), vec![], "{}", name);
+			assert!(read_logical_data(&mut io) == logical_data, "{}", name);
+
+			let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+			assert_eq!(io.check().unwrap(), vec![], "{}", name);
+			assert!(read_logical_data(&mut io) == logical_data, "{}", name);
+
+			let _ = std::fs::remove_file(&file_path);
+			let _ = std::fs::remove_file(file_path.clone() + ".bak");
+		}
 	}
 
-	fn collation(&mut self) -> Result<BuildIODataCollation, Box<dyn std::error::Error>> {
-		Ok(self.collation)
+	#[test]
+	fn export_writes_the_logical_data() {
+		let file_path = get_test_file_path("flashdisk_export");
+		let image_file_path = get_test_file_path("flashdisk_export.img");
+		std::fs::write(&file_path, get_test_flashdisk_data()).unwrap();
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		let logical_data = read_logical_data(&mut io);
+
+		assert_eq!(io.export_linear_image(image_file_path.clone()).unwrap(), 0xc000);
+		assert!(std::fs::read(&image_file_path).unwrap() == logical_data);
+
+		let _ = std::fs::remove_file(&file_path);
+		let _ = std::fs::remove_file(&image_file_path);
+	}
+
+	#[test]
+	fn linear_import_reads_back_the_image() {
+		let file_path = get_test_file_path("flashdisk_linear_import");
+		let image_file_path = get_test_file_path("flashdisk_linear_import.img");
+		let image_data: Vec<u8> = (0..0x3000).map(|i| (i % 0xfb) as u8).collect();
+		std::fs::write(&image_file_path, &image_data).unwrap();
+
+		let mut io = FlashdiskIO::import_linear_image(file_path.clone(), image_file_path.clone(), None, get_test_geometry()).unwrap();
+		assert_eq!(io.check().unwrap(), vec![]);
+
+		let logical_data = read_logical_data(&mut io);
+		assert!(logical_data[0x0000..0x3000] == image_data[..]);
+		assert!(logical_data[0x3000..].iter().all(|&b| b == 0xff));
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		assert_eq!(std::fs::metadata(&file_path).unwrap().len(), get_test_geometry().file_size());
+		assert!(read_logical_data(&mut io) == logical_data);
+
+		let export_file_path = get_test_file_path("flashdisk_linear_import_export.img");
+		io.export_linear_image(export_file_path.clone()).unwrap();
+		assert!(std::fs::read(&export_file_path).unwrap() == logical_data);
+
+		let _ = std::fs::remove_file(&file_path);
+		let _ = std::fs::remove_file(file_path.clone() + ".bak");
+		let _ = std::fs::remove_file(&image_file_path);
+		let _ = std::fs::remove_file(&export_file_path);
+	}
+
+	#[test]
+	fn rejected_linear_import_leaves_no_file() {
+		let file_path = get_test_file_path("flashdisk_rejected_import");
+		let image_file_path = get_test_file_path("flashdisk_rejected_import.img");
+		let _ = std::fs::remove_file(&file_path);
+
+		std::fs::write(&image_file_path, []).unwrap();
+		assert!(FlashdiskIO::import_linear_image(file_path.clone(), image_file_path.clone(), None, get_test_geometry()).is_err());
+		assert!(!Path::new(&file_path).exists());
+
+		std::fs::write(&image_file_path, vec![0x00; 0xc200]).unwrap();
+		assert!(FlashdiskIO::import_linear_image(file_path.clone(), image_file_path.clone(), None, get_test_geometry()).is_err());
+		assert!(!Path::new(&file_path).exists());
+
+		let _ = std::fs::remove_file(&image_file_path);
+	}
+
+	#[test]
+	fn interleaved_dump_import_keeps_the_nftl() {
+		let geometry = get_test_geometry();
+		let file_path = get_test_file_path("flashdisk_dump_import");
+		let dump_file_path = get_test_file_path("flashdisk_dump_import.bin");
+		let _ = std::fs::remove_file(&file_path);
+
+		let flashdisk_data = get_test_flashdisk_data();
+		let (usr_data, spr_data) = flashdisk_data.split_at(geometry.usr_size() as usize);
+		let mut dump_data = vec![];
+		for (page, spare) in usr_data.chunks_exact(geometry.page_size as usize).zip(spr_data.chunks_exact(geometry.spare_size as usize)) {
+			dump_data.extend_from_slice(page);
+			dump_data.extend_from_slice(spare);
+		}
+		std::fs::write(&dump_file_path, &dump_data).unwrap();
+
+		let mut io = FlashdiskIO::import_interleaved_dump(file_path.clone(), dump_file_path.clone(), geometry).unwrap();
+		assert_eq!(io.check().unwrap(), vec![]);
+		assert!(std::fs::read(&file_path).unwrap() == flashdisk_data);
+
+		let file_io_path = get_test_file_path("flashdisk_dump_import_mame");
+		std::fs::write(&file_io_path, &flashdisk_data).unwrap();
+		let mut file_io = FlashdiskIO::open_flashdisk(file_io_path.clone(), None, None, true).unwrap();
+		assert!(read_logical_data(&mut io) == read_logical_data(&mut file_io));
+
+		let _ = std::fs::remove_file(&file_path);
+		let _ = std::fs::remove_file(&file_io_path);
+		let _ = std::fs::remove_file(&dump_file_path);
+	}
+
+	#[test]
+	fn interleaved_dump_import_splits_pages_from_spares() {
+		let geometry = get_test_geometry();
+		let file_path = get_test_file_path("flashdisk_dump_split");
+		let dump_file_path = get_test_file_path("flashdisk_dump_split.bin");
+		let _ = std::fs::remove_file(&file_path);
+
+		let mut dump_data = vec![];
+		for page_index in 0..geometry.total_pages {
+			dump_data.extend(vec![(page_index & 0x7f) as u8; geometry.page_size as usize]);
+			dump_data.extend(vec![(page_index & 0x7f) as u8 | 0x80; geometry.spare_size as usize]);
+		}
+		std::fs::write(&dump_file_path, &dump_data).unwrap();
+
+		FlashdiskIO::import_interleaved_dump(file_path.clone(), dump_file_path.clone(), geometry).unwrap();
+
+		let flashdisk_data = std::fs::read(&file_path).unwrap();
+		let (usr_data, spr_data) = flashdisk_data.split_at(geometry.usr_size() as usize);
+		assert_eq!(spr_data.len() as u64, geometry.spare_area_size());
+		for page_index in 0..geometry.total_pages as usize {
+			let page = &usr_data[(page_index * geometry.page_size as usize)..((page_index + 1) * geometry.page_size as usize)];
+			let spare = &spr_data[(page_index * geometry.spare_size as usize)..((page_index + 1) * geometry.spare_size as usize)];
+
+			assert!(page.iter().all(|&b| b == (page_index & 0x7f) as u8));
+			assert!(spare.iter().all(|&b| b == (page_index & 0x7f) as u8 | 0x80));
+		}
+
+		std::fs::write(&dump_file_path, &dump_data[0..(dump_data.len() - 1)]).unwrap();
+		assert!(FlashdiskIO::import_interleaved_dump(file_path.clone(), dump_file_path.clone(), geometry).is_err());
+
+		let _ = std::fs::remove_file(&file_path);
+		let _ = std::fs::remove_file(&dump_file_path);
+	}
+
+	#[test]
+	fn page_writes_wait_for_commit() {
+		let file_path = get_test_file_path("flashdisk_commit");
+
+		{
+			let mut io = FlashdiskIO::create_flashdisk(file_path.clone(), None, MDOCGeometry::from_usr_size(0x800000)).unwrap();
+			io.seek(0x00).unwrap();
+			io.write(&mut [0x11; 0x10]).unwrap();
+			io.commit().unwrap();
+		}
+
+		let flashdisk_data = std::fs::read(&file_path).unwrap();
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, false).unwrap();
+		io.seek(0x08).unwrap();
+		io.write(&mut [0x22; 0x08]).unwrap();
+
+		assert_eq!(std::fs::read(&file_path).unwrap(), flashdisk_data);
+
+		io.commit().unwrap();
+
+		assert!(std::fs::read(&file_path).unwrap() != flashdisk_data);
+		assert!(!Path::new(&(file_path.clone() + ".tmp")).exists());
+
+		let mut io = FlashdiskIO::open_flashdisk(file_path.clone(), None, None, true).unwrap();
+		let mut data = [0x00; 0x10];
+		io.seek(0x00).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data[0x00..0x08], [0x11; 0x08]);
+		assert_eq!(data[0x08..0x10], [0x22; 0x08]);
+
+		let _ = std::fs::remove_file(&file_path);
+		let _ = std::fs::remove_file(file_path.clone() + ".bak");
 	}
 }
@@ -0,0 +1,179 @@
+// Layout of the M-Systems DiskOnChip (MDOC) parts MAME emulates for WebTV boxes, keyed by MAME's device name.
+// The launcher picks a geometry from the machine's device list and FlashdiskIO uses it to find the spare area and units.
+
+const DEFAULT_PAGE_SIZE: u64 = 0x00000200;
+const DEFAULT_SPARE_SIZE: u64 = 0x00000010;
+const DEFAULT_PAGES_PER_UNIT: u64 = 0x00000010;
+const DEFAULT_HEADER_UNITS: u64 = 0x00000002;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MDOCGeometry {
+	pub device_name: &'static str,
+	pub page_size: u64,
+	pub spare_size: u64,
+	pub pages_per_unit: u64,
+	// The smaller chips don't divide into whole units so total_units * pages_per_unit can come up short of total_pages.
+	pub total_units: u64,
+	pub total_pages: u64,
+	// Units at the start of the chip that hold the disk info rather than data.
+	pub header_units: u64
+}
+
+pub const MDOC_GEOMETRIES: [MDOCGeometry; 4] = [
+	MDOCGeometry {
+		device_name: "mdoc_2810_0016",
+		page_size: DEFAULT_PAGE_SIZE,
+		spare_size: DEFAULT_SPARE_SIZE,
+		pages_per_unit: 0x20,
+		total_units: 0x400,
+		total_pages: 0x8000,
+		header_units: DEFAULT_HEADER_UNITS
+	},
+	MDOCGeometry {
+		device_name: "mdoc_2810_0008",
+		page_size: DEFAULT_PAGE_SIZE,
+		spare_size: DEFAULT_SPARE_SIZE,
+		pages_per_unit: 0x10,
+		total_units: 0x400,
+		total_pages: 0x4000,
+		header_units: DEFAULT_HEADER_UNITS
+	},
+	MDOCGeometry {
+		device_name: "mdoc_2810_0004",
+		page_size: DEFAULT_PAGE_SIZE,
+		spare_size: DEFAULT_SPARE_SIZE,
+		pages_per_unit: 0x05,
+		total_units: 0x666,
+		total_pages: 0x2000,
+		header_units: DEFAULT_HEADER_UNITS
+	},
+	MDOCGeometry {
+		device_name: "mdoc_2810_0002",
+		page_size: DEFAULT_PAGE_SIZE,
+		spare_size: DEFAULT_SPARE_SIZE,
+		pages_per_unit: 0x05,
+		total_units: 0x333,
+		total_pages: 0x1000,
+		header_units: DEFAULT_HEADER_UNITS
+	}
+];
+
+impl MDOCGeometry {
+	pub fn from_device_name(device_name: &str) -> Option<MDOCGeometry> {
+		MDOC_GEOMETRIES.iter().find(|geometry| geometry.device_name == device_name).copied()
+	}
+
+	// Chips we don't know about are assumed to use the usual page and spare sizes.
+	pub fn from_usr_size(usr_size: u64) -> MDOCGeometry {
+		match MDOC_GEOMETRIES.iter().find(|geometry| geometry.usr_size() == usr_size) {
+			Some(geometry) => *geometry,
+			_ => MDOCGeometry::generic(usr_size / DEFAULT_PAGE_SIZE)
+		}
+	}
+
+	// MAME's files are the user data followed by the spare data.
+	pub fn from_file_size(file_size: u64) -> MDOCGeometry {
+		match MDOC_GEOMETRIES.iter().find(|geometry| geometry.file_size() == file_size) {
+			Some(geometry) => *geometry,
+			_ => MDOCGeometry::generic(file_size / (DEFAULT_PAGE_SIZE + DEFAULT_SPARE_SIZE))
+		}
+	}
+
+	fn generic(total_pages: u64) -> MDOCGeometry {
+		MDOCGeometry {
+			device_name: "",
+			page_size: DEFAULT_PAGE_SIZE,
+			spare_size: DEFAULT_SPARE_SIZE,
+			pages_per_unit: DEFAULT_PAGES_PER_UNIT,
+			total_units: total_pages / DEFAULT_PAGES_PER_UNIT,
+			total_pages: total_pages,
+			header_units: DEFAULT_HEADER_UNITS
+		}
+	}
+
+	pub fn unit_size(&self) -> u64 {
+		self.page_size * self.pages_per_unit
+	}
+
+	pub fn usr_size(&self) -> u64 {
+		self.total_pages * self.page_size
+	}
+
+	pub fn spare_area_size(&self) -> u64 {
+		self.total_pages * self.spare_size
+	}
+
+	pub fn file_size(&self) -> u64 {
+		self.usr_size() + self.spare_area_size()
+	}
+
+	// Size of the disk the NFTL presents. The disk info units at the start of the flash aren't part of it.
+	pub fn logical_size(&self) -> u64 {
+		self.usr_size().saturating_sub(self.header_units * self.unit_size())
+	}
+
+	// The size the OS sees after the disk info units and the spare units are taken out.
+	// The M-Systems format keeps about 2% of the units as spares for bad block replacement.
+	pub fn formatted_size(&self) -> u64 {
+		let usable_units = (self.total_units.saturating_sub(self.header_units) * 98) / 100;
+
+		usable_units * self.unit_size()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sizes_pick_the_matching_chip() {
+		let sizes = [
+			("mdoc_2810_0016", 0x1000000, 0x1080000, 0xfa4000),
+			("mdoc_2810_0008", 0x0800000, 0x0840000, 0x7d2000),
+			("mdoc_2810_0004", 0x0400000, 0x0420000, 0x3e9e00),
+			("mdoc_2810_0002", 0x0200000, 0x0210000, 0x1f4000)
+		];
+
+		for (device_name, usr_size, file_size, formatted_size) in sizes {
+			let geometry = MDOCGeometry::from_device_name(device_name).unwrap();
+
+			assert_eq!(geometry.usr_size(), usr_size, "{}", device_name);
+			assert_eq!(geometry.file_size(), file_size, "{}", device_name);
+			assert_eq!(geometry.formatted_size(), formatted_size, "{}", device_name);
+			assert_eq!(MDOCGeometry::from_usr_size(usr_size), geometry);
+			assert_eq!(MDOCGeometry::from_file_size(file_size), geometry);
+		}
+	}
+
+	#[test]
+	fn five_page_units_round_down() {
+		let geometry = MDOCGeometry::from_device_name("mdoc_2810_0004").unwrap();
+		assert_eq!(geometry.pages_per_unit, 5);
+		assert_eq!(geometry.total_units, 0x666);
+		assert_eq!(geometry.total_units, geometry.total_pages / geometry.pages_per_unit);
+		assert_eq!(geometry.unit_size(), 0xa00);
+
+		let geometry = MDOCGeometry::from_device_name("mdoc_2810_0002").unwrap();
+		assert_eq!(geometry.pages_per_unit, 5);
+		assert_eq!(geometry.total_units, 0x333);
+		assert_eq!(geometry.total_units, geometry.total_pages / geometry.pages_per_unit);
+		assert_eq!(geometry.unit_size(), 0xa00);
+	}
+
+	#[test]
+	fn unknown_sizes_fall_back_to_a_generic_chip() {
+		let geometry = MDOCGeometry::from_usr_size(0x10000);
+		assert_eq!(geometry.device_name, "");
+		assert_eq!(geometry.page_size, 0x200);
+		assert_eq!(geometry.spare_size, 0x10);
+		assert_eq!(geometry.pages_per_unit, 0x10);
+		assert_eq!(geometry.total_pages, 0x80);
+		assert_eq!(geometry.total_units, 0x08);
+		assert_eq!(geometry.header_units, 0x02);
+		assert_eq!(geometry.logical_size(), 0xc000);
+
+		assert_eq!(MDOCGeometry::from_file_size(0x10800), geometry);
+		assert_eq!(MDOCGeometry::from_device_name("mdoc_unknown"), None);
+	}
+}
@@ -1,15 +1,172 @@
-use super::{BuildIO, BuildIODataCollation};
+use super::{BuildIO, BuildIODataCollation, READ_ONLY_ERROR, write_file_atomically};
+use super::super::error::LauncherError;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, Seek, SeekFrom};
 
+// How a ROM image is split across its chips. Each chip takes width bytes in turn, so two chips with
+// a width of 2 is the usual 16-bit stripe and four chips with a width of 1 is byte-wide parts.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ROMInterleave {
+	pub chip_count: usize,
+	pub width: usize
+}
+
+impl ROMInterleave {
+	pub fn for_collation(collation: BuildIODataCollation) -> ROMInterleave {
+		match collation {
+			BuildIODataCollation::StrippedROMs => ROMInterleave { chip_count: 2, width: 2 },
+			BuildIODataCollation::QuadStrippedROMs => ROMInterleave { chip_count: 4, width: 2 },
+			BuildIODataCollation::ByteStrippedROMs => ROMInterleave { chip_count: 4, width: 1 },
+			_ => ROMInterleave { chip_count: 1, width: 1 }
+		}
+	}
+
+	pub fn stride(&self) -> usize {
+		self.chip_count * self.width
+	}
+
+	// Which chip a logical offset lands on and where it is on that chip.
+	pub fn locate(&self, pos: u64) -> (usize, u64) {
+		let stride = self.stride() as u64;
+		let width = self.width as u64;
+		let stride_offset = pos % stride;
+
+		(
+			(stride_offset / width) as usize,
+			((pos / stride) * width) + (stride_offset % width)
+		)
+	}
+
+	fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+		if self.chip_count == 0 {
+			Err("A ROM needs at least one chip.".into())
+		} else if self.width != 1 && self.width != 2 && self.width != 4 {
+			Err("The ROM interleave needs to be 8, 16 or 32 bits wide.".into())
+		} else {
+			Ok(())
+		}
+	}
+
+	// Every chip has to hold the same whole number of words or offsets past the end of a chip would be used.
+	fn check_size(&self, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+		if size % self.stride() as u64 != 0 {
+			Err(format!("The ROM size (0x{:x}) needs to be a multiple of 0x{:x} to split it over {} chips.", size, self.stride(), self.chip_count).into())
+		} else {
+			Ok(())
+		}
+	}
+
+	// A single chip is the file itself, otherwise each chip is the file path with the chip index on the end.
+	pub fn get_chip_file_paths(&self, file_path: &String) -> Vec<String> {
+		if self.chip_count == 1 {
+			vec![file_path.clone()]
+		} else {
+			(0..self.chip_count).map(|chip_index| file_path.clone() + &chip_index.to_string()).collect()
+		}
+	}
+}
+
 #[allow(dead_code)]
 pub struct ROMIO {
 	file_path: String,
 	collation: BuildIODataCollation,
+	interleave: ROMInterleave,
 	size: u64,
 	created: bool,
-	f0: File,
-	f1: Option<File>
+	read_only: bool,
+	position: u64,
+	files: Vec<File>,
+	// Chips that have been written to are held here until they're committed. Empty if the chip hasn't been changed.
+	pending_chip_data: Vec<Vec<u8>>
+}
+
+impl ROMIO {
+	pub fn open_interleaved(file_path: String, collation: Option<BuildIODataCollation>, interleave: ROMInterleave, read_only: bool) -> Result<ROMIO, Box<dyn std::error::Error>> {
+		interleave.check()?;
+
+		let mut files: Vec<File> = vec![];
+		for chip_file_path in interleave.get_chip_file_paths(&file_path) {
+			files.push(OpenOptions::new().read(true).write(!read_only).open(&chip_file_path).map_err(|e| LauncherError::from_io_error(&chip_file_path, &e))?);
+		}
+
+		// A short or odd sized chip means the dump is bad or it's split some other way.
+		let chip_size = files[0].metadata()?.len();
+		for file in files.iter() {
+			if file.metadata()?.len() != chip_size {
+				return Err("The ROM's chip files aren't all the same size.".into());
+			}
+		}
+		interleave.check_size(chip_size * interleave.chip_count as u64)?;
+
+		Ok(ROMIO {
+			file_path: file_path,
+			collation: collation.unwrap_or(BuildIODataCollation::Raw),
+			interleave: interleave,
+			size: chip_size * interleave.chip_count as u64,
+			created: false,
+			read_only: read_only,
+			position: 0,
+			files: files,
+			pending_chip_data: vec![vec![]; interleave.chip_count]
+		})
+	}
+
+	pub fn create_interleaved(file_path: String, collation: Option<BuildIODataCollation>, interleave: ROMInterleave, size: u64) -> Result<ROMIO, Box<dyn std::error::Error>> {
+		interleave.check()?;
+		interleave.check_size(size)?;
+
+		let mut files: Vec<File> = vec![];
+		for chip_file_path in interleave.get_chip_file_paths(&file_path) {
+			files.push(OpenOptions::new().read(true).write(true).create(true).open(&chip_file_path).map_err(|e| LauncherError::from_io_error(&chip_file_path, &e))?);
+		}
+
+		Ok(ROMIO {
+			file_path: file_path,
+			collation: collation.unwrap_or(BuildIODataCollation::Raw),
+			interleave: interleave,
+			size: size,
+			created: true,
+			read_only: false,
+			position: 0,
+			files: files,
+			pending_chip_data: vec![vec![]; interleave.chip_count]
+		})
+	}
+
+	fn load_pending_chip_data(&mut self, chip_index: usize) -> Result<(), Box<dyn std::error::Error>> {
+		if self.pending_chip_data[chip_index].len() == 0 {
+			let chip_size = (self.size / self.interleave.chip_count as u64) as usize;
+
+			let mut chip_data = vec![];
+			let _ = self.files[chip_index].seek(SeekFrom::Start(0))?;
+			self.files[chip_index].read_to_end(&mut chip_data)?;
+
+			if chip_data.len() < chip_size {
+				chip_data.resize(chip_size, 0x00);
+			}
+
+			self.pending_chip_data[chip_index] = chip_data;
+		}
+
+		Ok(())
+	}
+
+	// Each chip's share of a logical range is one run of bytes on that chip. Returns where each run starts and how long it is.
+	fn get_chip_runs(&self, size: usize) -> Vec<(u64, usize)> {
+		let mut chip_runs: Vec<(u64, usize)> = vec![(0, 0); self.interleave.chip_count];
+
+		for buf_index in 0..size {
+			let (chip_index, chip_offset) = self.interleave.locate(self.position + buf_index as u64);
+
+			if chip_runs[chip_index].1 == 0 {
+				chip_runs[chip_index].0 = chip_offset;
+			}
+			chip_runs[chip_index].1 += 1;
+		}
+
+		chip_runs
+	}
 }
 
 impl BuildIO for ROMIO {
@@ -18,149 +175,119 @@ impl BuildIO for ROMIO {
 	}
 
 	fn open(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
-		let mut io;
-
-		if collation.unwrap_or(BuildIODataCollation::Raw) == BuildIODataCollation::StrippedROMs {
-			io = ROMIO {
-				file_path: file_path.clone(),
-				collation: collation.unwrap_or(BuildIODataCollation::Raw),
-				size: 0,
-				created: false,
-				f0: OpenOptions::new().read(true).write(true).open(file_path.clone() + "0")?,
-				f1: Some(OpenOptions::new().read(true).write(true).open(file_path.clone() + "1")?)
-			};
-
-			io.size = io.f0.metadata().unwrap().len() * 2;
-		} else {
-			io = ROMIO {
-				file_path: file_path.clone(),
-				collation: collation.unwrap_or(BuildIODataCollation::Raw),
-				size: 0,
-				created: false,
-				f0: OpenOptions::new().read(true).write(true).open(file_path.clone())?,
-				f1: None
-			};
+		let interleave = ROMInterleave::for_collation(collation.unwrap_or(BuildIODataCollation::Raw));
 
-			io.size = io.f0.metadata().unwrap().len();
-		}
+		Ok(Box::new(ROMIO::open_interleaved(file_path, collation, interleave, false)?))
+	}
 
-		Ok(Box::new(io))
+	fn open_read_only(file_path: String, collation: Option<BuildIODataCollation>) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
+		let interleave = ROMInterleave::for_collation(collation.unwrap_or(BuildIODataCollation::Raw));
+
+		Ok(Box::new(ROMIO::open_interleaved(file_path, collation, interleave, true)?))
 	}
 
 	fn create(file_path: String, collation: Option<BuildIODataCollation>, size: u64) -> Result<Box<dyn BuildIO>, Box<dyn std::error::Error>> {
-		let io;
-
-		if collation.unwrap_or(BuildIODataCollation::Raw) == BuildIODataCollation::StrippedROMs {
-			io = ROMIO {
-				file_path: file_path.clone(),
-				collation: collation.unwrap_or(BuildIODataCollation::Raw),
-				size: size,
-				created: true,
-				f0: OpenOptions::new().read(true).write(true).create(true).open(file_path.clone() + "0")?,
-				f1: Some(OpenOptions::new().read(true).write(true).create(true).open(file_path.clone() + "1")?)
-			};
-		} else {
-			io = ROMIO {
-				file_path: file_path.clone(),
-				collation: collation.unwrap_or(BuildIODataCollation::Raw),
-				size: size,
-				created: true,
-				f0: OpenOptions::new().read(true).write(true).create(true).open(file_path.clone())?,
-				f1: None
-			};
-		}
+		let interleave = ROMInterleave::for_collation(collation.unwrap_or(BuildIODataCollation::Raw));
 
-		Ok(Box::new(io))
+		Ok(Box::new(ROMIO::create_interleaved(file_path, collation, interleave, size)?))
 	}
 
 	fn seek(&mut self, pos: u64) -> Result<u64, Box<dyn std::error::Error>>  {
-		if self.collation == BuildIODataCollation::StrippedROMs {
-			//let wanted_pos: i64 = pos.into();
-
-			let f0_seek = self.f0.seek(SeekFrom::Start(pos / 2))?;
-			let f1_seek = self.f1.as_ref().unwrap().seek(SeekFrom::Start(pos / 2))?;
+		self.position = pos;
 
-			if f0_seek == f1_seek {
-				Ok(f0_seek * 2)
-			} else {
-				Ok(0)
-			}
-		} else {
-			Ok(self.f0.seek(SeekFrom::Start(pos))?)
-		}
+		Ok(self.position)
 	}
 
 	fn stream_position(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
-		Ok(self.f0.stream_position()?)
+		Ok(self.position)
 	}
 
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
-		let corrected_size = buf.len().min((self.len().unwrap_or(0) - self.stream_position().unwrap_or(0)) as usize);
-
-		if self.collation == BuildIODataCollation::StrippedROMs {
-			if buf.len() < 0x4 {
-				Err("Buffer needs to be greater than 4 bytes.".into())
-			} else if (buf.len() & 1) == 1 {
-				Err("Buffer needs to be a multiple of 2.".into())
-			} else {
-				let mut rsize: usize = 0x0;
-
-				if self.collation == BuildIODataCollation::StrippedROMs {
-					for index in (0..corrected_size).step_by(4) {
-						rsize += self.f0.read(&mut buf[(index + 0)..(index + 2)])?;
-
-						// Stop reading if the buffer is a miltiple of 2 but not a multiple of 4. For example, like reading into a 62 byte buffer.
-						if (index + 4) <= corrected_size {
-							rsize += self.f1.as_ref().unwrap().read(&mut buf[(index + 2)..(index + 4)])?;
-						}
-					}
-				} else {
-					rsize += self.f0.read(buf)?;
-				}
+		let corrected_size = buf.len().min(self.len()?.saturating_sub(self.stream_position()?) as usize);
+
+		let mut chip_data: Vec<Vec<u8>> = vec![];
+		for (chip_index, (chip_offset, chip_size)) in self.get_chip_runs(corrected_size).into_iter().enumerate() {
+			let mut chip_run = vec![0x00 as u8; chip_size];
+
+			if chip_size > 0 && self.pending_chip_data[chip_index].len() > 0 {
+				let chip_offset = chip_offset as usize;
 
-				Ok(rsize)
+				chip_run.copy_from_slice(&self.pending_chip_data[chip_index][chip_offset..(chip_offset + chip_size)]);
+			} else if chip_size > 0 {
+				let _ = self.files[chip_index].seek(SeekFrom::Start(chip_offset))?;
+
+				let mut read_size = 0;
+				while read_size < chip_size {
+					match self.files[chip_index].read(&mut chip_run[read_size..])? {
+						0 => break,
+						chunk_size => read_size += chunk_size
+					};
+				}
 			}
-		} else {
-			Ok(self.f0.read(&mut buf[0..corrected_size])?)
+
+			chip_data.push(chip_run);
 		}
+
+		let mut chip_data_indexes = vec![0; self.interleave.chip_count];
+		for buf_index in 0..corrected_size {
+			let (chip_index, _) = self.interleave.locate(self.position + buf_index as u64);
+
+			buf[buf_index] = chip_data[chip_index][chip_data_indexes[chip_index]];
+			chip_data_indexes[chip_index] += 1;
+		}
+
+		self.position += corrected_size as u64;
+
+		Ok(corrected_size)
 	}
 
 	fn write(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
-		let corrected_size = buf.len().min((self.len().unwrap_or(0) - self.stream_position().unwrap_or(0)) as usize);
-
-		if self.collation == BuildIODataCollation::StrippedROMs {
-			if buf.len() < 0x4 {
-				Err("Buffer needs to be greater than 4 bytes.".into())
-			} else if (buf.len() & 1) == 1 {
-				Err("Buffer needs to be a multiple of 2.".into())
-			} else {
-				let mut rsize: usize = 0x0;
-
-				let mut buf0 = vec![0x00 as u8; corrected_size/2];
-				let mut buf1 = vec![0x00 as u8; corrected_size/2];
-
-				let mut stripped_bufindex = 0;
-				for whole_bufindex in (0..corrected_size).step_by(4) {
-					buf0[stripped_bufindex + 0] = buf[whole_bufindex + 0];
-					buf0[stripped_bufindex + 1] = buf[whole_bufindex + 1];
-
-					buf1[stripped_bufindex + 0] = buf[whole_bufindex + 2];
-					buf1[stripped_bufindex + 1] = buf[whole_bufindex + 3];
-					
-					stripped_bufindex += 2;
-				}
+		if self.read_only {
+			return Err(READ_ONLY_ERROR.into());
+		}
+
+		let corrected_size = buf.len().min(self.len()?.saturating_sub(self.stream_position()?) as usize);
+
+		let chip_runs = self.get_chip_runs(corrected_size);
 
-				rsize += self.f0.write(&buf0)?;
-				rsize += self.f1.as_ref().unwrap().write(&buf1)?;
+		let mut chip_data: Vec<Vec<u8>> = chip_runs.iter().map(|(_, chip_size)| Vec::with_capacity(*chip_size)).collect();
+		for buf_index in 0..corrected_size {
+			let (chip_index, _) = self.interleave.locate(self.position + buf_index as u64);
 
-				Ok(rsize)
+			chip_data[chip_index].push(buf[buf_index]);
+		}
+
+		for (chip_index, (chip_offset, chip_size)) in chip_runs.into_iter().enumerate() {
+			if chip_size > 0 {
+				self.load_pending_chip_data(chip_index)?;
+
+				let chip_offset = chip_offset as usize;
+
+				self.pending_chip_data[chip_index][chip_offset..(chip_offset + chip_size)].copy_from_slice(&chip_data[chip_index]);
 			}
-		} else {
-			Ok(self.f0.write(&buf[0..corrected_size])?)
 		}
+
+		self.position += corrected_size as u64;
+
+		Ok(corrected_size)
 	}
 
 	fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		let chip_file_paths = self.interleave.get_chip_file_paths(&self.file_path);
+
+		for (chip_index, chip_file_path) in chip_file_paths.iter().enumerate() {
+			if self.pending_chip_data[chip_index].len() > 0 {
+				write_file_atomically(chip_file_path, |dstf| {
+					dstf.write_all(&self.pending_chip_data[chip_index])?;
+
+					Ok(())
+				})?;
+
+				self.files[chip_index] = OpenOptions::new().read(true).write(true).open(chip_file_path)?;
+				self.pending_chip_data[chip_index].clear();
+			}
+		}
+
 		Ok(())
 	}
 
@@ -171,4 +298,185 @@ impl BuildIO for ROMIO {
 	fn collation(&mut self) -> Result<BuildIODataCollation, Box<dyn std::error::Error>> {
 		Ok(self.collation)
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::get_test_file_path;
+
+	// Each chip's bytes are its index in the top nibble and its offset in the bottom nibble.
+	fn create_test_chips(file_path: &String, interleave: ROMInterleave) {
+		for (chip_index, chip_file_path) in interleave.get_chip_file_paths(file_path).iter().enumerate() {
+			let chip_data: Vec<u8> = (0..0x10).map(|chip_offset| ((chip_index << 4) | chip_offset) as u8).collect();
+
+			std::fs::write(chip_file_path, chip_data).unwrap();
+		}
+	}
+
+	fn remove_test_chips(file_path: &String, interleave: ROMInterleave) {
+		for chip_file_path in interleave.get_chip_file_paths(file_path) {
+			let _ = std::fs::remove_file(chip_file_path);
+		}
+	}
+
+	#[test]
+	fn locate_finds_the_chip_and_offset() {
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::StrippedROMs);
+		assert_eq!(interleave.locate(0x00), (0, 0x00));
+		assert_eq!(interleave.locate(0x01), (0, 0x01));
+		assert_eq!(interleave.locate(0x02), (1, 0x00));
+		assert_eq!(interleave.locate(0x03), (1, 0x01));
+		assert_eq!(interleave.locate(0x04), (0, 0x02));
+		assert_eq!(interleave.locate(0x0b), (1, 0x05));
+
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::QuadStrippedROMs);
+		assert_eq!(interleave.stride(), 8);
+		assert_eq!(interleave.locate(0x00), (0, 0x00));
+		assert_eq!(interleave.locate(0x03), (1, 0x01));
+		assert_eq!(interleave.locate(0x06), (3, 0x00));
+		assert_eq!(interleave.locate(0x08), (0, 0x02));
+		assert_eq!(interleave.locate(0x1d), (2, 0x07));
+
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::ByteStrippedROMs);
+		assert_eq!(interleave.stride(), 4);
+		assert_eq!(interleave.locate(0x00), (0, 0x00));
+		assert_eq!(interleave.locate(0x03), (3, 0x00));
+		assert_eq!(interleave.locate(0x04), (0, 0x01));
+		assert_eq!(interleave.locate(0x1e), (2, 0x07));
+
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::Raw);
+		assert_eq!(interleave.locate(0x1e), (0, 0x1e));
+	}
+
+	#[test]
+	fn quad_stripped_reads_and_writes() {
+		let file_path = get_test_file_path("rom_quad");
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::QuadStrippedROMs);
+		create_test_chips(&file_path, interleave);
+
+		let mut io = ROMIO::open_interleaved(file_path.clone(), None, interleave, false).unwrap();
+		assert_eq!(io.len().unwrap(), 0x40);
+
+		let mut data = [0x00; 0x10];
+		assert_eq!(io.read(&mut data).unwrap(), 0x10);
+		assert_eq!(data, [
+			0x00, 0x01, 0x10, 0x11, 0x20, 0x21, 0x30, 0x31,
+			0x02, 0x03, 0x12, 0x13, 0x22, 0x23, 0x32, 0x33
+		]);
+
+		io.seek(0x0b).unwrap();
+		io.write(&mut [0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5]).unwrap();
+		io.commit().unwrap();
+
+		let mut data = [0x00; 0x08];
+		io.seek(0x0a).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data, [0x12, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0x05]);
+
+		assert_eq!(std::fs::read(file_path.clone() + "0").unwrap()[0x00..0x06], [0x00, 0x01, 0x02, 0x03, 0xa5, 0x05]);
+		assert_eq!(std::fs::read(file_path.clone() + "1").unwrap()[0x00..0x04], [0x10, 0x11, 0x12, 0xa0]);
+		assert_eq!(std::fs::read(file_path.clone() + "2").unwrap()[0x00..0x04], [0x20, 0x21, 0xa1, 0xa2]);
+		assert_eq!(std::fs::read(file_path.clone() + "3").unwrap()[0x00..0x04], [0x30, 0x31, 0xa3, 0xa4]);
+
+		remove_test_chips(&file_path, interleave);
+	}
+
+	#[test]
+	fn byte_stripped_reads_and_writes() {
+		let file_path = get_test_file_path("rom_byte");
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::ByteStrippedROMs);
+		create_test_chips(&file_path, interleave);
+
+		let mut io = ROMIO::open_interleaved(file_path.clone(), None, interleave, false).unwrap();
+		assert_eq!(io.len().unwrap(), 0x40);
+
+		let mut data = [0x00; 0x08];
+		assert_eq!(io.read(&mut data).unwrap(), 0x08);
+		assert_eq!(data, [0x00, 0x10, 0x20, 0x30, 0x01, 0x11, 0x21, 0x31]);
+
+		io.seek(0x03).unwrap();
+		io.write(&mut [0xa0, 0xa1, 0xa2, 0xa3, 0xa4]).unwrap();
+		io.commit().unwrap();
+
+		let mut data = [0x00; 0x08];
+		io.seek(0x00).unwrap();
+		io.read(&mut data).unwrap();
+		assert_eq!(data, [0x00, 0x10, 0x20, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4]);
+
+		assert_eq!(std::fs::read(file_path.clone() + "0").unwrap()[0x00..0x03], [0x00, 0xa1, 0x02]);
+		assert_eq!(std::fs::read(file_path.clone() + "1").unwrap()[0x00..0x03], [0x10, 0xa2, 0x12]);
+		assert_eq!(std::fs::read(file_path.clone() + "2").unwrap()[0x00..0x03], [0x20, 0xa3, 0x22]);
+		assert_eq!(std::fs::read(file_path.clone() + "3").unwrap()[0x00..0x03], [0xa0, 0xa4, 0x32]);
+
+		remove_test_chips(&file_path, interleave);
+	}
+
+	#[test]
+	fn short_and_odd_reads_move_the_position() {
+		let file_path = get_test_file_path("rom_odd_reads");
+		let interleave = ROMInterleave::for_collation(BuildIODataCollation::StrippedROMs);
+		create_test_chips(&file_path, interleave);
+
+		let mut io = ROMIO::open_interleaved(file_path.clone(), None, interleave, true).unwrap();
+
+		let mut data = [0x00; 0x01];
+		io.seek(0x01).unwrap();
+		assert_eq!(io.read(&mut data).unwrap(), 1);
+		assert_eq!(data, [0x01]);
+		assert_eq!(io.stream_position().unwrap(), 0x02);
+
+		let mut data = [0x00; 0x02];
+		assert_eq!(io.read(&mut data).unwrap(), 2);
+		assert_eq!(data, [0x10, 0x11]);
+		assert_eq!(io.stream_position().unwrap(), 0x04);
+
+		let mut data = [0x00; 0x03];
+		io.seek(0x03).unwrap();
+		assert_eq!(io.read(&mut data).unwrap(), 3);
+		assert_eq!(data, [0x11, 0x02, 0x03]);
+		assert_eq!(io.stream_position().unwrap(), 0x06);
+
+		let mut data = [0x00; 0x05];
+		assert_eq!(io.read(&mut data).unwrap(), 5);
+		assert_eq!(data, [0x12, 0x13, 0x04, 0x05, 0x14]);
+		assert_eq!(io.stream_position().unwrap(), 0x0b);
+
+		// Reads stop at the end of the ROM.
+		let mut data = [0x00; 0x04];
+		io.seek(0x1d).unwrap();
+		assert_eq!(io.read(&mut data).unwrap(), 3);
+		assert_eq!(data[0x00..0x03], [0x0f, 0x1e, 0x1f]);
+		assert_eq!(io.stream_position().unwrap(), 0x20);
+		assert_eq!(io.read(&mut data).unwrap(), 0);
+
+		remove_test_chips(&file_path, interleave);
+	}
+
+	#[test]
+	fn chip_writes_wait_for_commit() {
+		let file_path = get_test_file_path("rom_commit");
+		std::fs::write(file_path.clone() + "0", [0x11; 0x20]).unwrap();
+		std::fs::write(file_path.clone() + "1", [0x11; 0x20]).unwrap();
+
+		let mut io = ROMIO::open_interleaved(file_path.clone(), None, ROMInterleave::for_collation(BuildIODataCollation::StrippedROMs), false).unwrap();
+
+		// Only the first chip is written to.
+		io.seek(0x10).unwrap();
+		io.write(&mut [0x22, 0x22]).unwrap();
+
+		assert_eq!(std::fs::read(file_path.clone() + "0").unwrap(), [0x11; 0x20]);
+
+		io.commit().unwrap();
+
+		let chip0_data = std::fs::read(file_path.clone() + "0").unwrap();
+		assert_eq!(chip0_data[0x00..0x08], [0x11; 0x08]);
+		assert_eq!(chip0_data[0x08..0x0a], [0x22; 0x02]);
+		assert_eq!(chip0_data[0x0a..0x20], [0x11; 0x16]);
+		assert_eq!(std::fs::read(file_path.clone() + "1").unwrap(), [0x11; 0x20]);
+		assert!(!std::path::Path::new(&(file_path.clone() + "0.tmp")).exists());
+
+		let _ = std::fs::remove_file(file_path.clone() + "0");
+		let _ = std::fs::remove_file(file_path.clone() + "1");
+	}
+}
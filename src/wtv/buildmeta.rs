@@ -1,11 +1,24 @@
-use packbytes::FromBytes;
+use packbytes::{FromBytes, ToBytes};
+use std::io::{Read, Seek, SeekFrom};
 
 use super::buildio::{
 	BuildIO,
 	BuildIODataCollation,
+	PROBE_COLLATIONS,
+	buildiostream::BuildIOStream,
 	romio::ROMIO,
-	diskio::DiskIO,
-	flashdiskio::FlashdiskIO
+	diskio::{DiskIO, RawDiskIO},
+	flashdiskio::FlashdiskIO,
+	mdocgeometry::MDOCGeometry
+};
+use super::error::LauncherError;
+use super::flashdiskformat::{
+	FlashdiskAllocations,
+	FlashdiskFormat,
+	FLASHDISK_PARTITION_SECTOR_OFFSET,
+	FLASHDISK_BPB_OFFSET,
+	FLASHDISK_ADMININFO_OFFSET,
+	ADMININFO_MARKER
 };
 
 const WEBTV_BLOCK_SIZE: u64 = 0x00000200;
@@ -18,113 +31,8 @@ const RAW_BUILD_OFFSET0: u64 = 0x00000000;
 const PARTITION_TABLE_MAGIC: u32 = 0x74696d6e; // timn
 const PARTITION_TABLE_MAGIC_OFFSET: u64 = 0x00000008;
 
-const FLASHDISK_ADMININFO_OFFSET: u64 = 0x00000600;
 const FLASHDISK_NVRAM_SIZE_CHECK_VALUE: u32 = 0x00004000;
 const FLASHDISK_BUILD_HEADER_OFFSET: u64 = 0x00000800;
-const FLASHDISK_DEFAULT_BUILD_OFFSET: u64 = 0x00020800;
-const FLASHDISK_DEFAULT_BLOCK0: [u8; WEBTV_BLOCK_SIZE as usize] = [
-	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01,
-    0x01, 0x00, 0x04, 0x0F, 0xC2, 0xE8, 0x02, 0x00, 0x00, 0x00, 0x1E, 0x7D, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x55, 0xAA 
-];
-const FLASHDISK_DEFAULT_BLOCK2: [u8; WEBTV_BLOCK_SIZE as usize] = [
-    0xE9, 0x54, 0x76, 0x57, 0x65, 0x62, 0x54, 0x56, 0x00, 0x00, 0x00, 0x00, 0x02, 0x04, 0x01, 0x00,
-    0x01, 0xF0, 0x00, 0x1E, 0x7D, 0xF8, 0x20, 0x00, 0x02, 0x00, 0x10, 0x00, 0x02, 0x00, 0x00, 0x00,
-    0x1E, 0x7D, 0x00, 0x00, 0x80, 0x00, 0x29, 0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x20, 0x20, 0x20,
-    0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x46, 0x41, 0x54, 0x31, 0x36, 0x20, 0x20, 0x20, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x55, 0xAA 
-];
-const FLASHDISK_DEFAULT_BLOCK3: [u8; WEBTV_BLOCK_SIZE as usize] = [ // this is the admin info block
-    0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00,
-    0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x20, 0x00, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x20,
-    0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x44, 0x00, 0x00, 0x00, 0x40,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x40,
-    0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x44,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x11, 0xF7, 0x83, 0x82, 0x54, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00 
-];
-
 
 const LC2_PARTITION_TABLE_OFFSET: u64 = 0x014c1000;
 const LC2_BUILD_SELECT_OFFSET: u64 = 0x01080600;
@@ -143,8 +51,18 @@ const UTV_BUILD_OFFSET1: u64 = 0x15480600;
 
 const NO_ROMFS_FLAG: u32 = 0x4e6f4653; // NoFS
 
+// Where the ROMFS lands tells us what an older build is when it doesn't have a base address.
+const BOOTROM_ADDRESS_MIN: u32 = 0x9fc00000;
+const BOOTROM_ADDRESS_MAX: u32 = 0x9fe00000;
+const FLASH_ADDRESS_MIN: u32 = 0x9f000000;
+const FLASH_ADDRESS_MAX: u32 = 0xa0000000;
+const DISK_ADDRESS_MIN: u32 = 0x80000000;
+const DISK_ADDRESS_MAX: u32 = 0x84400000;
+// Anything larger than the biggest flash approm has to be loaded from a disk.
+const FLASH_APPROM_SIZE_MAX: u64 = 0x00400000;
+
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone, FromBytes)]
+#[derive(Debug, Copy, Clone, FromBytes, ToBytes)]
 #[packbytes(be)]
 pub struct DiskAdminInfo {
 	pub primary_nv_alloc_bytes: u32,
@@ -184,6 +102,63 @@ pub struct DiskAdminInfo {
 	pub unknown_marker: u32 // No idea what this is. Possibly a phone number when converted to decimal. Always 0xf7838254
 }
 
+impl DiskAdminInfo {
+	pub fn calculate_checksum(&self) -> u32 {
+		FlashdiskFormat::calculate_admininfo_checksum(&self.to_be_bytes())
+	}
+
+	pub fn update_checksum(&mut self) {
+		self.checksum = self.calculate_checksum();
+	}
+
+	pub fn is_valid(&self) -> bool {
+		self.unknown_marker == ADMININFO_MARKER && self.checksum == self.calculate_checksum()
+	}
+
+	// Each area's first block and block count. browser1 doesn't have a size of its own so it runs up to browser0.
+	fn get_areas(&self) -> [(&'static str, u64, u64); 7] {
+		[
+			("primary NV", self.primary_nv0_block as u64, self.primary_nv_size as u64),
+			("secondary NV", self.secondary_nv0_block as u64, self.secondary_nv_size as u64),
+			("diag", self.diag_block as u64, self.diag_size as u64),
+			("tier3", self.tier3_block as u64, self.tier3_size as u64),
+			("select", self.browser_select_block as u64, self.unknown5 as u64),
+			("browser1", self.browser1_block as u64, (self.browser0_block as u64).saturating_sub(self.browser1_block as u64)),
+			("browser", self.browser0_block as u64, (self.browser_alloc_bytes as u64 + (WEBTV_BLOCK_SIZE - 1)) / WEBTV_BLOCK_SIZE)
+		]
+	}
+
+	// The areas have to fit on the disk without running into each other and there has to be room for a build.
+	pub fn check_allocations(&self, disk_size: u64) -> Result<(), Box<dyn std::error::Error>> {
+		if self.browser_alloc_bytes == 0 {
+			return Err("There's no room for a build on the flashdisk.".into());
+		} else if self.browser_size > self.browser_alloc_bytes {
+			return Err("The browser size is larger than its allocation.".into());
+		}
+
+		let areas = self.get_areas();
+
+		for (area_index, (area_name, area_block, area_blocks)) in areas.iter().enumerate() {
+			if *area_blocks == 0 {
+				continue;
+			}
+
+			let area_end = FLASHDISK_BUILD_HEADER_OFFSET + ((area_block + area_blocks) * WEBTV_BLOCK_SIZE);
+			if area_end > disk_size {
+				return Err(format!("The {} area runs past the end of the flashdisk.", area_name).into());
+			}
+
+			for (other_area_name, other_area_block, other_area_blocks) in areas[(area_index + 1)..].iter() {
+				if *other_area_blocks > 0 && *area_block < (other_area_block + other_area_blocks) && *other_area_block < (area_block + area_blocks) {
+					return Err(format!("The {} and {} areas overlap.", area_name, other_area_name).into());
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BuildMetaLayout {
@@ -195,6 +170,48 @@ pub enum BuildMetaLayout {
 	UTVDiskLayout
 }
 
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildKind {
+	UnknownBuild,
+	// Older bootroms that don't fill in the base address. These came with the classic boxes.
+	ClassicBootROM,
+	BootROM,
+	FlashAppROM,
+	DiskAppROM,
+	// Flashdisk (MDOC) builds. Builds at an address disk and flashdisk boxes both use are classed as disk builds.
+	FlashdiskAppROM,
+	WinCEBuild
+}
+
+// Where each kind of build runs from as (min, max) addresses. These come from the box profiles.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct BuildAddressRanges {
+	pub bootrom: Vec<(u32, u32)>,
+	pub flash_approm: Vec<(u32, u32)>,
+	pub disk_approm: Vec<(u32, u32)>,
+	pub flashdisk_approm: Vec<(u32, u32)>
+}
+
+impl BuildAddressRanges {
+	fn any_contains(ranges: &Vec<(u32, u32)>, address: u32) -> bool {
+		ranges.iter().any(|&(min, max)| address >= min && address <= max)
+	}
+}
+
+// What a build file looks like before it's imported anywhere.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct BuildClassification {
+	pub kind: BuildKind,
+	pub build_header: BuildHeader,
+	pub file_size: u64,
+	pub satellite: bool,
+	// The byte order the file was found to be in.
+	pub collation: BuildIODataCollation
+}
+
 #[allow(non_snake_case, dead_code)]
 pub mod BuildFlag {
 	#[allow(non_snake_case, non_upper_case_globals)]
@@ -218,6 +235,7 @@ pub struct BuildMeta {
 	pub build_count: u8,
 	pub selected_build_index: u8,
 	pub admin_info: DiskAdminInfo,
+	pub admin_info_valid: bool,
 	pub build_info: [BuildInfo; 2],
 	io: Box<dyn BuildIO>
 }
@@ -257,6 +275,13 @@ pub struct BuildHeader {
 	pub compressed_bootrom_address: u32
 }
 
+impl BuildHeader {
+	// Builds start by branching over their header. Anything else likely isn't a build.
+	pub fn starts_with_branch(&self) -> bool {
+		((self.branch_and_delay_instructions >> 32) as u32) & RAW_LAYOUT_CHECK_MASK == RAW_LAYOUT_CHECK_VALUE
+	}
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone, FromBytes)]
 #[packbytes(be)]
@@ -266,37 +291,119 @@ pub struct ROMFSHeader {
 }
 
 impl BuildMeta {
+	// Images are opened read-only so they can be looked at without any chance of changing them. Anything that writes to them fails.
 	pub fn open_rom(file_path: String, collation: Option<BuildIODataCollation>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
-		match ROMIO::open(file_path.clone(), collation) {
-			Ok(srcf) => {
-				BuildMeta::new(srcf, None, calculate_checksums)
-			},
-			Err(e) => {
-				Err(e)
-			}
-		}
+		BuildMeta::new(ROMIO::open_read_only(file_path, collation)?, None, calculate_checksums)
 	}
 
 	pub fn open_disk(file_path: String, collation: Option<BuildIODataCollation>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
-		match DiskIO::open(file_path.clone(), collation) {
-			Ok(srcf) => {
-				BuildMeta::new(srcf, None, calculate_checksums)
-			},
-			Err(e) => {
-				Err(e)
-			}
+		BuildMeta::new(DiskIO::open_read_only(file_path, collation)?, None, calculate_checksums)
+	}
+
+	// Disk images in MAME are opened with the diff file MAME keeps for them.
+	pub fn open_disk_image(file_path: String, diff_file_path: String, collation: Option<BuildIODataCollation>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
+		BuildMeta::new(DiskIO::open_with_diff(file_path, diff_file_path, collation, true)?, None, calculate_checksums)
+	}
+
+	pub fn open_disk_image_writable(file_path: String, diff_file_path: String, collation: Option<BuildIODataCollation>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
+		BuildMeta::new(DiskIO::open_with_diff(file_path, diff_file_path, collation, false)?, None, calculate_checksums)
+	}
+
+	// The geometry is worked out from the file size if it isn't given.
+	pub fn open_flashdisk(file_path: String, collation: Option<BuildIODataCollation>, geometry: Option<MDOCGeometry>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
+		BuildMeta::new(Box::new(FlashdiskIO::open_flashdisk(file_path, collation, geometry, true)?), Some(BuildMetaLayout::FlashdiskLayout), calculate_checksums)
+	}
+
+	pub fn open_flashdisk_writable(file_path: String, collation: Option<BuildIODataCollation>, geometry: Option<MDOCGeometry>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
+		BuildMeta::new(Box::new(FlashdiskIO::open_flashdisk(file_path, collation, geometry, false)?), Some(BuildMetaLayout::FlashdiskLayout), calculate_checksums)
+	}
+
+	// Works out what kind of build a file is so it can be sent to the right storage.
+	pub fn classify_build(file_path: String, address_ranges: &BuildAddressRanges) -> Result<BuildClassification, Box<dyn std::error::Error>> {
+		let collation = BuildMeta::detect_collation(file_path.clone(), BuildIODataCollation::Raw);
+		let mut build_meta = BuildMeta::open_disk(file_path, Some(collation), false)?;
+		let file_size = build_meta.io.len()?;
+
+		let mut classification = BuildClassification {
+			kind: BuildKind::UnknownBuild,
+			build_header: BuildMeta::default_build_header(),
+			file_size: file_size,
+			satellite: false,
+			collation: collation
+		};
+
+		if build_meta.layout != BuildMetaLayout::RawLayout || build_meta.build_count == 0 {
+			return Ok(classification);
 		}
+
+		let build_header = build_meta.build_info[0].build_header;
+		classification.build_header = build_header;
+		classification.satellite = (build_header.build_flags & BuildFlag::Satellite) != 0x00;
+
+		let base_address = build_header.build_base_address;
+		let romfs_address = build_header.romfs_address;
+
+		classification.kind = if !build_header.starts_with_branch() {
+			BuildKind::UnknownBuild
+		} else if (build_header.build_flags & BuildFlag::WinCE) != 0x00 {
+			BuildKind::WinCEBuild
+		} else if BuildAddressRanges::any_contains(&address_ranges.bootrom, base_address) {
+			BuildKind::BootROM
+		} else if BuildAddressRanges::any_contains(&address_ranges.flash_approm, base_address) {
+			BuildKind::FlashAppROM
+		} else if BuildAddressRanges::any_contains(&address_ranges.disk_approm, base_address) {
+			BuildKind::DiskAppROM
+		} else if BuildAddressRanges::any_contains(&address_ranges.flashdisk_approm, base_address) {
+			BuildKind::FlashdiskAppROM
+		} else if romfs_address >= BOOTROM_ADDRESS_MIN && romfs_address < BOOTROM_ADDRESS_MAX {
+			BuildKind::ClassicBootROM
+		} else if romfs_address >= FLASH_ADDRESS_MIN && romfs_address < FLASH_ADDRESS_MAX {
+			BuildKind::FlashAppROM
+		} else if (romfs_address >= DISK_ADDRESS_MIN && romfs_address <= DISK_ADDRESS_MAX) || file_size > FLASH_APPROM_SIZE_MAX {
+			BuildKind::DiskAppROM
+		} else {
+			BuildKind::UnknownBuild
+		};
+
+		Ok(classification)
 	}
 
-	pub fn open_flashdisk(file_path: String, collation: Option<BuildIODataCollation>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
-		match FlashdiskIO::open(file_path.clone(), collation) {
-			Ok(srcf) => {
-				BuildMeta::new(srcf, Some(BuildMetaLayout::FlashdiskLayout), calculate_checksums)
-			},
-			Err(e) => {
-				Err(e)
+	// Opens an image with each collation it could be in and picks the one where the image makes the most sense.
+	// Works on disk images, linear flashdisk images and build files. A collation the image can't be opened with scores nothing.
+	// The preferred collation is tried first so it wins a tie. It's also used if nothing makes sense.
+	pub fn detect_collation(file_path: String, preferred_collation: BuildIODataCollation) -> BuildIODataCollation {
+		let mut collations = vec![preferred_collation];
+		for collation in PROBE_COLLATIONS {
+			if collation != preferred_collation {
+				collations.push(collation);
 			}
 		}
+
+		let mut best_collation = preferred_collation;
+		let mut best_score = 0;
+		for collation in collations {
+			let score = match BuildMeta::open_disk(file_path.clone(), Some(collation), false) {
+				Ok(build_meta) => build_meta.collation_score(),
+				Err(_) => 0
+			};
+
+			if score > best_score {
+				best_collation = collation;
+				best_score = score;
+			}
+		}
+
+		best_collation
+	}
+
+	// Reads a whole file in a collation. Builds from readers that swap bytes come back in the order the box uses.
+	pub fn read_file(file_path: String, collation: BuildIODataCollation) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+		let mut io = RawDiskIO::open_read_only(file_path, Some(collation))?;
+
+		let mut file_data: Vec<u8> = vec![];
+		BuildIOStream::new(io.as_mut())?.read_to_end(&mut file_data)?;
+
+		Ok(file_data)
 	}
 
 	pub fn new(build_io: Box<dyn BuildIO>, layout: Option<BuildMetaLayout>, calculate_checksums: bool) -> Result<BuildMeta, Box<dyn std::error::Error>>  {
@@ -322,18 +429,16 @@ impl BuildMeta {
 	}
 
 	pub fn write_build(&mut self, buf: &mut Vec<u8>) -> Result<usize, Box<dyn std::error::Error>> {
+		// There's nowhere safe to put a build on an image we don't understand.
+		if self.layout == BuildMetaLayout::UnknownLayout {
+			return Err(Box::new(LauncherError::WrongLayout { file_path: self.file_path.clone(), expected_layout: "a WebTV disk or ROM image".into() }));
+		}
+
 		let build_offset = match self.layout {
 			BuildMetaLayout::FlashdiskLayout => {
 				if self.build_count == 0 {
-					// Write the default data so a build can load.
-					let _ = self.io.seek(0x00000000);
-					let _ = self.io.write(&mut FLASHDISK_DEFAULT_BLOCK0.clone())?;
-					let _ = self.io.seek(0x00000400);
-					let _ = self.io.write(&mut FLASHDISK_DEFAULT_BLOCK2.clone())?;
-					let _ = self.io.seek(0x00000600);
-					let _ = self.io.write(&mut FLASHDISK_DEFAULT_BLOCK3.clone())?;
-
-					(FLASHDISK_DEFAULT_BUILD_OFFSET + FLASHDISK_BUILD_HEADER_OFFSET) as u64
+					// Format the disk so a build can load.
+					self.format_flashdisk(None)?.build_offset()
 				} else {
 					self.build_info[self.selected_build_index as usize].build_offset
 				}
@@ -345,20 +450,181 @@ impl BuildMeta {
 
 		let result = self.io.write(buf)?;
 
-		let _ = self.io.commit();
+		self.io.commit()?;
 
 		Ok(result)
 	}
 
+	// Reads the selected build back and checks it's the same as what was written. The build found is returned so its version and checksums can be shown.
+	pub fn verify_build(&mut self, source_data: &[u8]) -> Result<BuildInfo, Box<dyn std::error::Error>> {
+		if self.build_count == 0 {
+			return Err("No build was found where it was written.".into());
+		} else if source_data.len() < 0x40 {
+			return Err("The build is too small to have a build header.".into());
+		}
+
+		let build_info = self.build_info[self.selected_build_index as usize];
+
+		let mut source_build_header = [0x00; 0x40];
+		source_build_header.copy_from_slice(&source_data[0..0x40]);
+		let source_build_header = BuildHeader::from_bytes(source_build_header);
+
+		if build_info.build_header.build_version != source_build_header.build_version {
+			return Err("The build header that was read back doesn't match the build that was written.".into());
+		} else if build_info.build_header.code_checksum != source_build_header.code_checksum {
+			return Err(Box::new(LauncherError::ChecksumMismatch {
+				checksum_name: "code".into(),
+				expected_checksum: source_build_header.code_checksum,
+				found_checksum: build_info.build_header.code_checksum
+			}));
+		}
+
+		let verify_size = (source_data.len() as u64).min(self.io.len()?.saturating_sub(build_info.build_offset)) as usize;
+		let mut written_data = vec![0x00 as u8; verify_size];
+
+		let mut build_stream = BuildIOStream::new(self.io.as_mut())?;
+		let _ = build_stream.seek(SeekFrom::Start(build_info.build_offset))?;
+		build_stream.read_exact(&mut written_data)?;
+
+		match written_data.iter().zip(source_data.iter()).position(|(written_byte, source_byte)| written_byte != source_byte) {
+			Some(mismatch_offset) => {
+				Err(format!("The build that was read back is different at offset 0x{:x}.", mismatch_offset).into())
+			},
+			_ => {
+				if verify_size < source_data.len() {
+					Err("The build didn't fit where it was written.".into())
+				} else {
+					Ok(build_info)
+				}
+			}
+		}
+	}
+
+	// Writes the partition sector, BPB and admin info to the flashdisk. Allocations default to what fits the disk size.
+	pub fn format_flashdisk(&mut self, allocations: Option<FlashdiskAllocations>) -> Result<FlashdiskFormat, Box<dyn std::error::Error>> {
+		let disk_size = self.flashdisk_size()?;
+
+		let format = FlashdiskFormat::new(
+			disk_size,
+			allocations.unwrap_or(FlashdiskAllocations::default_for_size(disk_size))
+		)?;
+
+		let _ = self.io.seek(FLASHDISK_PARTITION_SECTOR_OFFSET)?;
+		let _ = self.io.write(&mut format.partition_sector())?;
+		let _ = self.io.seek(FLASHDISK_BPB_OFFSET)?;
+		let _ = self.io.write(&mut format.bpb_sector())?;
+		let _ = self.io.seek(FLASHDISK_ADMININFO_OFFSET)?;
+		let _ = self.io.write(&mut format.admininfo_sector())?;
+
+		self.admin_info = format.admininfo();
+		self.admin_info_valid = true;
+
+		Ok(format)
+	}
+
+	// Size of the flashdisk the admin info's blocks are in.
+	pub fn flashdisk_size(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+		Ok(FlashdiskIO::calculate_formatted_size(self.io.len()?))
+	}
+
+	// Writes an edited admin info back to the flashdisk. Allocations that overlap or don't fit are refused. The checksum and marker are always corrected.
+	pub fn set_admininfo(&mut self, admin_info: DiskAdminInfo) -> Result<(), Box<dyn std::error::Error>> {
+		if self.layout != BuildMetaLayout::FlashdiskLayout {
+			return Err("Admin info can only be written to a flashdisk.".into());
+		}
+
+		admin_info.check_allocations(self.flashdisk_size()?)?;
+
+		let mut admin_info = admin_info;
+		admin_info.unknown_marker = ADMININFO_MARKER;
+		admin_info.update_checksum();
+
+		let _ = self.io.seek(FLASHDISK_ADMININFO_OFFSET)?;
+		let _ = self.io.write(&mut admin_info.to_be_bytes())?;
+		self.io.commit()?;
+
+		self.admin_info = admin_info;
+		self.admin_info_valid = true;
+
+		Ok(())
+	}
+
+	// Keeps the current allocations if they look sane, otherwise the admin info is regenerated for the disk size.
+	pub fn repair_admininfo(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+		let disk_size = self.flashdisk_size()?;
+
+		if self.admin_info.check_allocations(disk_size).is_ok() {
+			self.set_admininfo(self.admin_info)
+		} else {
+			let format = FlashdiskFormat::new(disk_size, FlashdiskAllocations::default_for_size(disk_size))?;
+
+			self.set_admininfo(format.admininfo())
+		}
+	}
+
+	pub fn get_flashdisk_allocations(&self) -> FlashdiskAllocations {
+		FlashdiskAllocations::from_admininfo(&self.admin_info)
+	}
+
+	// Lays the areas out again from the edited sizes. The data in each area is moved to where the area now starts so the boot code still finds the build.
+	pub fn set_flashdisk_allocations(&mut self, allocations: FlashdiskAllocations) -> Result<(), Box<dyn std::error::Error>> {
+		let format = FlashdiskFormat::new(self.flashdisk_size()?, allocations)?;
+		let new_admin_info = format.admininfo();
+
+		if self.admin_info_valid {
+			if self.build_count > 0 && new_admin_info.browser_alloc_bytes < self.admin_info.browser_size {
+				return Err("The browser allocation is too small for the build on the flashdisk.".into());
+			}
+
+			self.move_flashdisk_areas(&new_admin_info)?;
+		}
+
+		self.set_admininfo(new_admin_info)?;
+
+		self.load_buildinfo(false)
+	}
+
+	// Everything is read before anything is written since an area can land on top of where another one was. Areas that grow are padded as erased flash.
+	fn move_flashdisk_areas(&mut self, new_admin_info: &DiskAdminInfo) -> Result<(), Box<dyn std::error::Error>> {
+		let old_areas = self.admin_info.get_areas();
+		let new_areas = new_admin_info.get_areas();
+		let mut moved_areas: Vec<(u64, Vec<u8>)> = vec![];
+
+		for ((_, old_block, old_blocks), (_, new_block, new_blocks)) in old_areas.iter().zip(new_areas.iter()) {
+			if (old_block == new_block && old_blocks == new_blocks) || *new_blocks == 0 {
+				continue;
+			}
+
+			let mut area_data = vec![0xff as u8; (new_blocks * WEBTV_BLOCK_SIZE) as usize];
+			let copy_size = (old_blocks.min(new_blocks) * WEBTV_BLOCK_SIZE) as usize;
+
+			if copy_size > 0 {
+				let _ = self.io.seek(FLASHDISK_BUILD_HEADER_OFFSET + (old_block * WEBTV_BLOCK_SIZE))?;
+				let _ = self.io.read(&mut area_data[..copy_size])?;
+			}
+
+			moved_areas.push((FLASHDISK_BUILD_HEADER_OFFSET + (new_block * WEBTV_BLOCK_SIZE), area_data));
+		}
+
+		for (area_offset, area_data) in moved_areas.iter_mut() {
+			let _ = self.io.seek(*area_offset)?;
+			let _ = self.io.write(area_data)?;
+		}
+
+		self.io.commit()?;
+
+		Ok(())
+	}
+
 	pub fn set_selected_build_index(&mut self, selected_index: u8) -> Result<(), Box<dyn std::error::Error>> {
 		if self.layout == BuildMetaLayout::LC2DiskLayout {
 			let _ = self.io.seek(LC2_BUILD_SELECT_OFFSET)?;
-			let _ = self.io.write(&mut [selected_index, 0, 0, 0]);
-			let _ = self.io.commit();
+			let _ = self.io.write(&mut [selected_index, 0, 0, 0])?;
+			self.io.commit()?;
 		} else if self.layout == BuildMetaLayout::UTVDiskLayout {
 			let _ = self.io.seek(UTV_BUILD_SELECT_OFFSET)?;
-			let _ = self.io.write(&mut [selected_index, 0, 0, 0]);
-			let _ = self.io.commit();
+			let _ = self.io.write(&mut [selected_index, 0, 0, 0])?;
+			self.io.commit()?;
 		}
 
 		Ok(())
@@ -372,6 +638,7 @@ impl BuildMeta {
 			build_count: 0,
 			selected_build_index: 0,
 			admin_info: BuildMeta::default_admin_info(),
+			admin_info_valid: false,
 			build_info: [BuildMeta::default_buildinfo(); 2],
 			io: build_io,
 		}
@@ -406,10 +673,35 @@ impl BuildMeta {
 		let mut flashdisk_check = [0x00; 0x8c];
 		let _ = self.io.read(&mut flashdisk_check).unwrap_or(0);
 		self.admin_info = DiskAdminInfo::from_bytes(flashdisk_check);
+		self.admin_info_valid = self.admin_info.is_valid();
 
 		Ok(())
 	}
 
+	// How much of the image looks right when read with this collation.
+	// A partition table or admin info is a better sign than the single instruction the raw layout is found by.
+	fn collation_score(&self) -> u8 {
+		let mut score = match self.layout {
+			BuildMetaLayout::UnknownLayout => 0,
+			BuildMetaLayout::RawLayout => 1,
+			_ => 2
+		};
+
+		if self.layout == BuildMetaLayout::FlashdiskLayout && self.admin_info_valid {
+			score += 1;
+		}
+
+		if self.build_count > 0 {
+			let build_header = self.build_info[self.selected_build_index as usize].build_header;
+
+			if build_header.starts_with_branch() {
+				score += 1;
+			}
+		}
+
+		score
+	}
+
 	fn get_layout(&mut self) -> Result<BuildMetaLayout, Box<dyn std::error::Error>> {
 		let file_size = self.io.len().unwrap_or(0);
 		
@@ -418,8 +710,10 @@ impl BuildMeta {
 		if file_size > FLASHDISK_ADMININFO_OFFSET {
 			let _ = self.get_admininfo();
 
-			if self.admin_info.primary_nv_alloc_bytes == FLASHDISK_NVRAM_SIZE_CHECK_VALUE
-			&& self.admin_info.secondary_nv_alloc_bytes == FLASHDISK_NVRAM_SIZE_CHECK_VALUE {
+			// A corrupt admin info is still detected as a flashdisk so it can be reported and repaired.
+			if self.admin_info.unknown_marker == ADMININFO_MARKER
+			|| (self.admin_info.primary_nv_alloc_bytes == FLASHDISK_NVRAM_SIZE_CHECK_VALUE
+			&& self.admin_info.secondary_nv_alloc_bytes == FLASHDISK_NVRAM_SIZE_CHECK_VALUE) {
 				return Ok(BuildMetaLayout::FlashdiskLayout);
 			}
 		}
@@ -644,4 +938,192 @@ impl BuildMeta {
 
 		Ok(checksum)
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::buildio::get_test_file_path;
+
+	// Branches past the header. The classic builds use other offsets and have their base address filled in for them.
+	const TEST_BRANCH_INSTRUCTION: u32 = 0x10000012;
+
+	fn get_test_build_header(base_address: u32, romfs_address: u32, build_flags: u32, build_size: u64) -> Vec<u8> {
+		let mut build_header = vec![0x00; 0x40];
+
+		build_header[0x00..0x04].copy_from_slice(&TEST_BRANCH_INSTRUCTION.to_be_bytes());
+		build_header[0x0c..0x10].copy_from_slice(&((build_size / 4) as u32).to_be_bytes());
+		build_header[0x24..0x28].copy_from_slice(&romfs_address.to_be_bytes());
+		build_header[0x30..0x34].copy_from_slice(&base_address.to_be_bytes());
+		build_header[0x34..0x38].copy_from_slice(&build_flags.to_be_bytes());
+
+		build_header
+	}
+
+	// Data is written in the box's order and the collation swaps it on the way to the file.
+	fn create_test_image(file_name: &str, collation: BuildIODataCollation, file_size: u64, writes: Vec<(u64, Vec<u8>)>) -> String {
+		let file_path = get_test_file_path(file_name);
+
+		std::fs::File::create(&file_path).unwrap().set_len(file_size).unwrap();
+
+		let mut io = DiskIO::open(file_path.clone(), Some(collation)).unwrap();
+		for (offset, mut data) in writes {
+			io.seek(offset).unwrap();
+			io.write(&mut data).unwrap();
+		}
+		io.commit().unwrap();
+
+		file_path
+	}
+
+	fn create_test_build(file_name: &str, collation: BuildIODataCollation, base_address: u32, romfs_address: u32, build_flags: u32, build_size: u64) -> String {
+		create_test_image(file_name, collation, build_size, vec![
+			(RAW_BUILD_OFFSET0, get_test_build_header(base_address, romfs_address, build_flags, build_size))
+		])
+	}
+
+	// An LC2 disk with one partition and a build in the first slot.
+	fn create_test_partition_image(file_name: &str, collation: BuildIODataCollation) -> String {
+		let mut partition_table = vec![0x00; 0x10];
+		partition_table[0x04..0x08].copy_from_slice(&0x00000001u32.to_be_bytes());
+		partition_table[0x08..0x0c].copy_from_slice(&PARTITION_TABLE_MAGIC.to_be_bytes());
+
+		create_test_image(file_name, collation, 0x01600000, vec![
+			(LC2_PARTITION_TABLE_OFFSET, partition_table),
+			(LC2_BUILD_OFFSET0, get_test_build_header(0x80400000, 0x80800000, 0x00, 0x200000))
+		])
+	}
+
+	#[test]
+	fn detect_collation_finds_each_build_order() {
+		for collation in PROBE_COLLATIONS {
+			let file_path = create_test_build(&format!("collation_build_{:?}", collation), collation, 0x80400000, 0x80800000, 0x00, 0x1000);
+
+			for preferred_collation in PROBE_COLLATIONS {
+				assert_eq!(BuildMeta::detect_collation(file_path.clone(), preferred_collation), collation, "preferred {:?}", preferred_collation);
+			}
+
+			let build_meta = BuildMeta::open_disk(file_path.clone(), Some(collation), false).unwrap();
+			assert_eq!(build_meta.layout, BuildMetaLayout::RawLayout);
+			assert_eq!(build_meta.build_info[0].build_header.build_base_address, 0x80400000);
+
+			let _ = std::fs::remove_file(&file_path);
+		}
+	}
+
+	#[test]
+	fn detect_collation_finds_each_partition_image_order() {
+		for collation in PROBE_COLLATIONS {
+			let file_path = create_test_partition_image(&format!("collation_disk_{:?}", collation), collation);
+
+			for preferred_collation in PROBE_COLLATIONS {
+				assert_eq!(BuildMeta::detect_collation(file_path.clone(), preferred_collation), collation, "preferred {:?}", preferred_collation);
+			}
+
+			let build_meta = BuildMeta::open_disk(file_path.clone(), Some(collation), false).unwrap();
+			assert_eq!(build_meta.layout, BuildMetaLayout::LC2DiskLayout);
+			assert_eq!(build_meta.build_info[0].build_header.build_base_address, 0x80400000);
+
+			let _ = std::fs::remove_file(&file_path);
+		}
+	}
+
+	#[test]
+	fn detect_collation_prefers_the_preferred_collation_on_a_tie() {
+		// The first instruction reads the same forwards and backwards so raw and 32-bit swapped both make sense.
+		let file_path = get_test_file_path("collation_tie");
+		let mut build_data = get_test_build_header(0x80400000, 0x80800000, 0x00, 0x1000);
+		build_data[0x00..0x04].copy_from_slice(&[0x10, 0x00, 0x00, 0x10]);
+		build_data.resize(0x1000, 0x00);
+		std::fs::write(&file_path, &build_data).unwrap();
+
+		assert_eq!(BuildMeta::detect_collation(file_path.clone(), BuildIODataCollation::Raw), BuildIODataCollation::Raw);
+		assert_eq!(BuildMeta::detect_collation(file_path.clone(), BuildIODataCollation::ByteSwapped32), BuildIODataCollation::ByteSwapped32);
+		assert_eq!(BuildMeta::detect_collation(file_path.clone(), BuildIODataCollation::ByteSwapped16), BuildIODataCollation::Raw);
+
+		// Nothing makes sense so the preferred collation is kept.
+		std::fs::write(&file_path, vec![0x00; 0x1000]).unwrap();
+		assert_eq!(BuildMeta::detect_collation(file_path.clone(), BuildIODataCollation::ByteSwapped1632), BuildIODataCollation::ByteSwapped1632);
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+
+	fn get_test_address_ranges() -> BuildAddressRanges {
+		BuildAddressRanges {
+			bootrom: vec![(0x9fc00000, 0x9fc00000)],
+			flash_approm: vec![(0x9f000000, 0x9f000000), (0x9fe00000, 0x9fe00000)],
+			disk_approm: vec![(0x80300000, 0x84400000)],
+			flashdisk_approm: vec![(0x80300000, 0x84400000), (0x88000000, 0x88000000)]
+		}
+	}
+
+	fn classify_test_build(file_name: &str, base_address: u32, romfs_address: u32, build_flags: u32, build_size: u64) -> BuildClassification {
+		let file_path = create_test_build(file_name, BuildIODataCollation::Raw, base_address, romfs_address, build_flags, build_size);
+		let classification = BuildMeta::classify_build(file_path.clone(), &get_test_address_ranges()).unwrap();
+
+		let _ = std::fs::remove_file(&file_path);
+
+		classification
+	}
+
+	#[test]
+	fn classify_build_uses_the_profile_address_ranges() {
+		assert_eq!(classify_test_build("classify_bootrom", 0x9fc00000, 0x9fc80000, 0x00, 0x1000).kind, BuildKind::BootROM);
+		assert_eq!(classify_test_build("classify_flash", 0x9f000000, 0x9f100000, 0x00, 0x1000).kind, BuildKind::FlashAppROM);
+		assert_eq!(classify_test_build("classify_flash_bfe", 0x9fe00000, 0x9ff00000, 0x00, 0x1000).kind, BuildKind::FlashAppROM);
+		// Disk and flashdisk boxes share this range so the build is classed as a disk build.
+		assert_eq!(classify_test_build("classify_disk", 0x80400000, 0x80800000, 0x00, 0x1000).kind, BuildKind::DiskAppROM);
+		assert_eq!(classify_test_build("classify_flashdisk", 0x88000000, 0x88800000, 0x00, 0x1000).kind, BuildKind::FlashdiskAppROM);
+	}
+
+	#[test]
+	fn classify_build_falls_back_to_the_romfs_address() {
+		assert_eq!(classify_test_build("classify_classic_bootrom", 0x00000000, 0x9fc10000, 0x00, 0x1000).kind, BuildKind::ClassicBootROM);
+		assert_eq!(classify_test_build("classify_romfs_flash", 0x00000000, 0x9f100000, 0x00, 0x1000).kind, BuildKind::FlashAppROM);
+		assert_eq!(classify_test_build("classify_romfs_disk", 0x00000000, 0x80800000, 0x00, 0x1000).kind, BuildKind::DiskAppROM);
+		assert_eq!(classify_test_build("classify_romfs_unknown", 0x00000000, 0x12345678, 0x00, 0x1000).kind, BuildKind::UnknownBuild);
+	}
+
+	#[test]
+	fn classify_build_sends_oversize_builds_to_disk() {
+		assert_eq!(classify_test_build("classify_oversize", 0x00000000, 0x12345678, 0x00, FLASH_APPROM_SIZE_MAX).kind, BuildKind::UnknownBuild);
+
+		let classification = classify_test_build("classify_oversize", 0x00000000, 0x12345678, 0x00, FLASH_APPROM_SIZE_MAX + 0x200);
+		assert_eq!(classification.kind, BuildKind::DiskAppROM);
+		assert_eq!(classification.file_size, FLASH_APPROM_SIZE_MAX + 0x200);
+	}
+
+	#[test]
+	fn classify_build_reads_the_build_flags() {
+		// WinCE builds are picked out before the address is looked at.
+		assert_eq!(classify_test_build("classify_wince", 0x80400000, 0x80800000, BuildFlag::WinCE, 0x1000).kind, BuildKind::WinCEBuild);
+
+		let classification = classify_test_build("classify_satellite", 0x80400000, 0x80800000, BuildFlag::Satellite, 0x1000);
+		assert_eq!(classification.kind, BuildKind::DiskAppROM);
+		assert!(classification.satellite);
+	}
+
+	#[test]
+	fn classify_build_needs_a_branch() {
+		let file_path = get_test_file_path("classify_no_branch");
+		let mut build_data = get_test_build_header(0x9fc00000, 0x9fc80000, 0x00, 0x1000);
+		build_data[0x00..0x04].copy_from_slice(&0x27bdffe0u32.to_be_bytes());
+		build_data.resize(0x1000, 0x00);
+		std::fs::write(&file_path, &build_data).unwrap();
+
+		assert_eq!(BuildMeta::classify_build(file_path.clone(), &get_test_address_ranges()).unwrap().kind, BuildKind::UnknownBuild);
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+
+	#[test]
+	fn classify_build_reads_swapped_builds() {
+		let file_path = create_test_build("classify_swapped", BuildIODataCollation::ByteSwapped16, 0x9fc00000, 0x9fc80000, 0x00, 0x1000);
+
+		let classification = BuildMeta::classify_build(file_path.clone(), &get_test_address_ranges()).unwrap();
+		assert_eq!(classification.kind, BuildKind::BootROM);
+		assert_eq!(classification.collation, BuildIODataCollation::ByteSwapped16);
+		assert_eq!(classification.build_header.build_base_address, 0x9fc00000);
+
+		let _ = std::fs::remove_file(&file_path);
+	}
+}
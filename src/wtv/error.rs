@@ -0,0 +1,60 @@
+use std::io::ErrorKind;
+
+// Failures the launcher can explain. Each message says what went wrong and what can be done about it.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LauncherError {
+	FileMissing { file_path: String },
+	PermissionDenied { file_path: String },
+	WrongSize { file_path: String, expected_size: u64, found_size: u64 },
+	WrongLayout { file_path: String, expected_layout: String },
+	ChecksumMismatch { checksum_name: String, expected_checksum: u32, found_checksum: u32 },
+	// File errors that don't fit the above. The message is what the OS gave.
+	FileError { file_path: String, message: String }
+}
+
+impl LauncherError {
+	// Puts the file path on an error from opening or writing a file so the UI can say which file it was.
+	pub fn from_io_error(file_path: &String, e: &std::io::Error) -> LauncherError {
+		match e.kind() {
+			ErrorKind::NotFound => LauncherError::FileMissing { file_path: file_path.clone() },
+			ErrorKind::PermissionDenied => LauncherError::PermissionDenied { file_path: file_path.clone() },
+			_ => LauncherError::FileError { file_path: file_path.clone(), message: e.to_string() }
+		}
+	}
+
+	// Same as from_io_error but for errors that have already been boxed. Anything that isn't a file error is passed through.
+	pub fn with_file_path(file_path: &String, e: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+		match e.downcast_ref::<std::io::Error>() {
+			Some(io_error) => Box::new(LauncherError::from_io_error(file_path, io_error)),
+			_ => e
+		}
+	}
+}
+
+impl std::fmt::Display for LauncherError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			LauncherError::FileMissing { file_path } => {
+				write!(f, "Couldn't find '{}'. Check the file is still there or choose it again.", file_path)
+			},
+			LauncherError::PermissionDenied { file_path } => {
+				write!(f, "Not allowed to access '{}'. Check the file isn't read-only or in use by MAME.", file_path)
+			},
+			LauncherError::WrongSize { file_path, expected_size, found_size } => {
+				write!(f, "'{}' is 0x{:x} bytes but 0x{:x} bytes were expected. Is this the right file for this box?", file_path, found_size, expected_size)
+			},
+			LauncherError::WrongLayout { file_path, expected_layout } => {
+				write!(f, "'{}' doesn't look like {}. Is this the right image for this box?", file_path, expected_layout)
+			},
+			LauncherError::ChecksumMismatch { checksum_name, expected_checksum, found_checksum } => {
+				write!(f, "The {} checksum is 0x{:08x} but should be 0x{:08x}. The image may be corrupt.", checksum_name, found_checksum, expected_checksum)
+			},
+			LauncherError::FileError { file_path, message } => {
+				write!(f, "Problem with '{}': {}", file_path, message)
+			}
+		}
+	}
+}
+
+impl std::error::Error for LauncherError { }
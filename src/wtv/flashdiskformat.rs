@@ -0,0 +1,473 @@
+// By: Eric MacDonald (eMac)
+
+use packbytes::ToBytes;
+
+use super::buildmeta::DiskAdminInfo;
+
+const WEBTV_BLOCK_SIZE: u64 = 0x00000200;
+
+// The first 4 blocks hold the partition sector, an unused block, the FAT16 BPB and the admin info.
+// Block numbers in the admin info are relative to the end of these blocks.
+pub const FLASHDISK_PARTITION_SECTOR_OFFSET: u64 = 0x00000000;
+pub const FLASHDISK_BPB_OFFSET: u64 = 0x00000400;
+pub const FLASHDISK_ADMININFO_OFFSET: u64 = 0x00000600;
+pub const FLASHDISK_DATA_OFFSET: u64 = 0x00000800;
+
+const PARTITION_HIDDEN_SECTORS: u32 = 0x00000002;
+const PARTITION_HEADS: u32 = 0x00000010;
+const PARTITION_SECTORS_PER_TRACK: u32 = 0x00000002;
+const PARTITION_ENTRY_OFFSET: usize = 0x1be;
+const PARTITION_ACTIVE_FLAG: u8 = 0x80;
+const PARTITION_TYPE_FAT16: u8 = 0x04;
+
+const FAT_BOOT_JUMP: [u8; 3] = [0xe9, 0x54, 0x76];
+const FAT_OEM_NAME: [u8; 8] = [b'W', b'e', b'b', b'T', b'V', 0x00, 0x00, 0x00];
+const FAT_SECTORS_PER_CLUSTER: u32 = 0x00000004;
+const FAT_RESERVED_SECTORS: u32 = 0x00000001;
+const FAT_COUNT: u32 = 0x00000001;
+const FAT_ROOT_ENTRIES: u32 = 0x000000f0;
+const FAT_MEDIA_DESCRIPTOR: u8 = 0xf8;
+const FAT_DRIVE_NUMBER: u8 = 0x80;
+const FAT_EXTENDED_BOOT_SIGNATURE: u8 = 0x29;
+const FAT_VOLUME_LABEL: [u8; 11] = [0x20; 11];
+const FAT_TYPE_LABEL: [u8; 8] = [b'F', b'A', b'T', b'1', b'6', 0x20, 0x20, 0x20];
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+
+pub const ADMININFO_CHECKSUM_OFFSET: usize = 0x84;
+pub const ADMININFO_MARKER: u32 = 0xf7838254;
+
+const DEFAULT_PRIMARY_NV_BYTES: u32 = 0x00004000;
+const DEFAULT_SECONDARY_NV_BYTES: u32 = 0x00004000;
+const DEFAULT_SELECT_BYTES: u32 = 0x00000800;
+const DEFAULT_BROWSER1_BYTES: u32 = 0x00018000;
+const DEFAULT_BROWSER_BYTES: u32 = 0x00680000;
+const DEFAULT_BROWSER_SIZE: u32 = 0x00300000;
+const BROWSER_ALLOC_ALIGNMENT: u32 = 0x00010000;
+
+// How much space each area on the flashdisk gets. These are rounded up to a WebTV block.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlashdiskAllocations {
+	pub primary_nv_bytes: u32,
+	pub secondary_nv_bytes: u32,
+	pub diag_bytes: u32,
+	pub tier3_bytes: u32,
+	pub select_bytes: u32,
+	pub browser1_bytes: u32,
+	pub browser_bytes: u32,
+	pub browser_size: u32
+}
+
+impl FlashdiskAllocations {
+	// These are the allocations used on the 16MB flashdisks (0x7d1e sectors) I've seen. Smaller chips use default_for_size.
+	pub fn default() -> FlashdiskAllocations {
+		FlashdiskAllocations {
+			primary_nv_bytes: DEFAULT_PRIMARY_NV_BYTES,
+			secondary_nv_bytes: DEFAULT_SECONDARY_NV_BYTES,
+			diag_bytes: 0x00000000,
+			tier3_bytes: 0x00000000,
+			select_bytes: DEFAULT_SELECT_BYTES,
+			browser1_bytes: DEFAULT_BROWSER1_BYTES,
+			browser_bytes: DEFAULT_BROWSER_BYTES,
+			browser_size: DEFAULT_BROWSER_SIZE
+		}
+	}
+
+	// Shrinks the browser area on smaller chips so the default allocations fit.
+	pub fn default_for_size(disk_size: u64) -> FlashdiskAllocations {
+		let mut allocations = FlashdiskAllocations::default();
+
+		let browser0_offset = FLASHDISK_DATA_OFFSET + (allocations.browser0_block() as u64 * WEBTV_BLOCK_SIZE);
+		let available_bytes = disk_size.saturating_sub(browser0_offset).min(u32::MAX as u64) as u32;
+
+		if allocations.browser_bytes > available_bytes {
+			allocations.browser_bytes = available_bytes & !(BROWSER_ALLOC_ALIGNMENT - 1);
+		}
+		allocations.browser_size = allocations.browser_size.min(allocations.browser_bytes);
+
+		allocations
+	}
+
+	// Reads the sizes back out of an admin info. browser1 doesn't have its own size so it's what's between it and browser0.
+	pub fn from_admininfo(admin_info: &DiskAdminInfo) -> FlashdiskAllocations {
+		FlashdiskAllocations {
+			primary_nv_bytes: admin_info.primary_nv_alloc_bytes,
+			secondary_nv_bytes: admin_info.secondary_nv_alloc_bytes,
+			diag_bytes: admin_info.diag_alloc_bytes,
+			tier3_bytes: admin_info.tier3_alloc_bytes,
+			select_bytes: admin_info.unknown2,
+			browser1_bytes: (admin_info.browser0_block.saturating_sub(admin_info.browser1_block) as u64 * WEBTV_BLOCK_SIZE).min(u32::MAX as u64) as u32,
+			browser_bytes: admin_info.browser_alloc_bytes,
+			browser_size: admin_info.browser_size
+		}
+	}
+
+	fn to_blocks(bytes: u32) -> u32 {
+		((bytes as u64 + (WEBTV_BLOCK_SIZE - 1)) / WEBTV_BLOCK_SIZE) as u32
+	}
+
+	pub fn primary_nv_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.primary_nv_bytes)
+	}
+
+	pub fn secondary_nv_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.secondary_nv_bytes)
+	}
+
+	pub fn diag_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.diag_bytes)
+	}
+
+	pub fn tier3_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.tier3_bytes)
+	}
+
+	pub fn select_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.select_bytes)
+	}
+
+	pub fn browser1_blocks(&self) -> u32 {
+		FlashdiskAllocations::to_blocks(self.browser1_bytes)
+	}
+
+	// The areas are laid out in this order: primary NV, secondary NV, diag, tier3, select blocks, browser1 then browser0.
+	pub fn diag_block(&self) -> u32 {
+		self.primary_nv_blocks() + self.secondary_nv_blocks()
+	}
+
+	pub fn tier3_block(&self) -> u32 {
+		self.diag_block() + self.diag_blocks()
+	}
+
+	pub fn select_block(&self) -> u32 {
+		self.tier3_block() + self.tier3_blocks()
+	}
+
+	pub fn browser1_block(&self) -> u32 {
+		self.select_block() + self.select_blocks()
+	}
+
+	pub fn browser0_block(&self) -> u32 {
+		self.browser1_block() + self.browser1_blocks()
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone)]
+pub struct FlashdiskFormat {
+	pub disk_size: u64,
+	pub total_sectors: u32,
+	pub sectors_per_fat: u32,
+	pub allocations: FlashdiskAllocations
+}
+
+impl FlashdiskFormat {
+	// disk_size is the size of the logical disk (what's seen after the NFTL translation).
+	pub fn new(disk_size: u64, allocations: FlashdiskAllocations) -> Result<FlashdiskFormat, Box<dyn std::error::Error>> {
+		let sectors_per_cylinder = PARTITION_HEADS * PARTITION_SECTORS_PER_TRACK;
+		let disk_sectors = (disk_size / WEBTV_BLOCK_SIZE).min(u32::MAX as u64) as u32;
+		let cylinder_sectors = (disk_sectors / sectors_per_cylinder) * sectors_per_cylinder;
+
+		if cylinder_sectors <= PARTITION_HIDDEN_SECTORS {
+			return Err("Flashdisk is too small to partition.".into());
+		}
+
+		let browser_end = FLASHDISK_DATA_OFFSET
+			+ (allocations.browser0_block() as u64 * WEBTV_BLOCK_SIZE)
+			+ allocations.browser_bytes as u64;
+
+		if browser_end > disk_size {
+			return Err("Flashdisk allocations don't fit on this disk.".into());
+		} else if allocations.browser_size > allocations.browser_bytes {
+			return Err("Browser size is larger than its allocation.".into());
+		}
+
+		let total_sectors = cylinder_sectors - PARTITION_HIDDEN_SECTORS;
+
+		Ok(FlashdiskFormat {
+			disk_size: disk_size,
+			total_sectors: total_sectors,
+			sectors_per_fat: FlashdiskFormat::calculate_sectors_per_fat(total_sectors),
+			allocations: allocations
+		})
+	}
+
+	pub fn build_offset(&self) -> u64 {
+		FLASHDISK_DATA_OFFSET + (self.allocations.browser0_block() as u64 * WEBTV_BLOCK_SIZE)
+	}
+
+	fn calculate_sectors_per_fat(total_sectors: u32) -> u32 {
+		let root_dir_sectors = ((FAT_ROOT_ENTRIES * 0x20) + (WEBTV_BLOCK_SIZE as u32 - 1)) / WEBTV_BLOCK_SIZE as u32;
+
+		// The FAT size depends on the cluster count which depends on the FAT size so we go until it settles.
+		let mut sectors_per_fat = 1;
+		loop {
+			let data_sectors = total_sectors.saturating_sub(FAT_RESERVED_SECTORS + root_dir_sectors + (FAT_COUNT * sectors_per_fat));
+			let cluster_count = data_sectors / FAT_SECTORS_PER_CLUSTER;
+			let needed_sectors_per_fat = (((cluster_count + 2) * 2) + (WEBTV_BLOCK_SIZE as u32 - 1)) / WEBTV_BLOCK_SIZE as u32;
+
+			if needed_sectors_per_fat <= sectors_per_fat {
+				break;
+			}
+
+			sectors_per_fat = needed_sectors_per_fat;
+		}
+
+		sectors_per_fat
+	}
+
+	fn lba_to_chs(lba: u32) -> [u8; 3] {
+		let cylinder = (lba / (PARTITION_HEADS * PARTITION_SECTORS_PER_TRACK)).min(0x3ff);
+		let head = (lba / PARTITION_SECTORS_PER_TRACK) % PARTITION_HEADS;
+		let sector = (lba % PARTITION_SECTORS_PER_TRACK) + 1;
+
+		[
+			head as u8,
+			(((cylinder >> 2) & 0xc0) as u8) | ((sector & 0x3f) as u8),
+			(cylinder & 0xff) as u8
+		]
+	}
+
+	pub fn partition_sector(&self) -> [u8; WEBTV_BLOCK_SIZE as usize] {
+		let mut block = [0x00; WEBTV_BLOCK_SIZE as usize];
+
+		let start_lba = PARTITION_HIDDEN_SECTORS;
+		let end_lba = PARTITION_HIDDEN_SECTORS + self.total_sectors - 1;
+
+		let entry = &mut block[PARTITION_ENTRY_OFFSET..(PARTITION_ENTRY_OFFSET + 0x10)];
+		entry[0x00] = PARTITION_ACTIVE_FLAG;
+		entry[0x01..0x04].copy_from_slice(&FlashdiskFormat::lba_to_chs(start_lba));
+		entry[0x04] = PARTITION_TYPE_FAT16;
+		entry[0x05..0x08].copy_from_slice(&FlashdiskFormat::lba_to_chs(end_lba));
+		entry[0x08..0x0c].copy_from_slice(&start_lba.to_le_bytes());
+		entry[0x0c..0x10].copy_from_slice(&self.total_sectors.to_le_bytes());
+
+		block[0x1fe..0x200].copy_from_slice(&BOOT_SIGNATURE);
+
+		block
+	}
+
+	pub fn bpb_sector(&self) -> [u8; WEBTV_BLOCK_SIZE as usize] {
+		let mut block = [0x00; WEBTV_BLOCK_SIZE as usize];
+
+		let total_sectors16 = match self.total_sectors > 0xffff {
+			true => 0x0000,
+			false => self.total_sectors as u16
+		};
+
+		block[0x00..0x03].copy_from_slice(&FAT_BOOT_JUMP);
+		block[0x03..0x0b].copy_from_slice(&FAT_OEM_NAME);
+		block[0x0b..0x0d].copy_from_slice(&(WEBTV_BLOCK_SIZE as u16).to_le_bytes());
+		block[0x0d] = FAT_SECTORS_PER_CLUSTER as u8;
+		block[0x0e..0x10].copy_from_slice(&(FAT_RESERVED_SECTORS as u16).to_le_bytes());
+		block[0x10] = FAT_COUNT as u8;
+		block[0x11..0x13].copy_from_slice(&(FAT_ROOT_ENTRIES as u16).to_le_bytes());
+		block[0x13..0x15].copy_from_slice(&total_sectors16.to_le_bytes());
+		block[0x15] = FAT_MEDIA_DESCRIPTOR;
+		block[0x16..0x18].copy_from_slice(&(self.sectors_per_fat as u16).to_le_bytes());
+		block[0x18..0x1a].copy_from_slice(&(PARTITION_SECTORS_PER_TRACK as u16).to_le_bytes());
+		block[0x1a..0x1c].copy_from_slice(&(PARTITION_HEADS as u16).to_le_bytes());
+		block[0x1c..0x20].copy_from_slice(&PARTITION_HIDDEN_SECTORS.to_le_bytes());
+		block[0x20..0x24].copy_from_slice(&self.total_sectors.to_le_bytes());
+		block[0x24] = FAT_DRIVE_NUMBER;
+		block[0x26] = FAT_EXTENDED_BOOT_SIGNATURE;
+		block[0x2b..0x36].copy_from_slice(&FAT_VOLUME_LABEL);
+		block[0x36..0x3e].copy_from_slice(&FAT_TYPE_LABEL);
+
+		block[0x1fe..0x200].copy_from_slice(&BOOT_SIGNATURE);
+
+		block
+	}
+
+	pub fn admininfo(&self) -> DiskAdminInfo {
+		let allocations = self.allocations;
+
+		let mut admin_info = DiskAdminInfo {
+			primary_nv_alloc_bytes: allocations.primary_nv_bytes,
+			secondary_nv_alloc_bytes: allocations.secondary_nv_bytes,
+			browser_alloc_bytes: allocations.browser_bytes,
+			select_alloc_bytes: 0x00000000,
+			diag_alloc_bytes: allocations.diag_bytes,
+			tier3_alloc_bytes: allocations.tier3_bytes,
+			unknown1: 0x00000000,
+			unknown2: allocations.select_bytes,
+			primary_nv_size: allocations.primary_nv_blocks(),
+			secondary_nv_size: allocations.secondary_nv_blocks(),
+			browser_size: allocations.browser_size,
+			unknown3: 0x00000000,
+			unknown4: 0x00000000,
+			diag_size: allocations.diag_blocks(),
+			tier3_size: allocations.tier3_blocks(),
+			unknown5: allocations.select_blocks(),
+			unknown6: 0x00000000,
+			primary_nv0_block: 0x00000000,
+			unknown7: allocations.primary_nv_blocks(),
+			secondary_nv0_block: allocations.primary_nv_blocks(),
+			diag_block: allocations.diag_block(),
+			browser0_block: allocations.browser0_block(),
+			browser1_block: allocations.browser1_block(),
+			browser_select_block: allocations.select_block(),
+			primary_nv1_block: 0x00000000,
+			unknown8: allocations.select_block(),
+			secondary_nv1_block: allocations.primary_nv_blocks(),
+			primary_nv_select_block: allocations.select_block(),
+			secondary_nv_select_block: allocations.select_block(),
+			tier3_block: allocations.tier3_block(),
+			unknown9: allocations.select_block(),
+			unknown10: allocations.browser1_block(),
+			unknown11: 0x00000000,
+			checksum: 0x00000000,
+			unknown_marker: ADMININFO_MARKER
+		};
+
+		admin_info.update_checksum();
+
+		admin_info
+	}
+
+	pub fn admininfo_sector(&self) -> [u8; WEBTV_BLOCK_SIZE as usize] {
+		let mut block = [0x00; WEBTV_BLOCK_SIZE as usize];
+
+		let admin_info = self.admininfo().to_be_bytes();
+
+		block[0..admin_info.len()].copy_from_slice(&admin_info);
+
+		block
+	}
+
+	// The admin info checksum is a sum of every byte before the checksum.
+	pub fn calculate_admininfo_checksum(admininfo: &[u8]) -> u32 {
+		admininfo[0..ADMININFO_CHECKSUM_OFFSET.min(admininfo.len())]
+			.iter()
+			.fold(0x00000000 as u32, |checksum, b| checksum.wrapping_add(*b as u32))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::wtv::buildio::flashdiskio::FlashdiskIO;
+
+	// The start of the partition sector, BPB and admin info blocks that used to be hardcoded for the 16MB chip. The rest of each block is zero apart from the boot signatures.
+	const MDOC_2810_0016_PARTITION_ENTRY: [u8; 0x10] = [
+		0x80, 0x01, 0x01, 0x00, 0x04, 0x0F, 0xC2, 0xE8, 0x02, 0x00, 0x00, 0x00, 0x1E, 0x7D, 0x00, 0x00
+	];
+	const MDOC_2810_0016_BPB: [u8; 0x40] = [
+		0xE9, 0x54, 0x76, 0x57, 0x65, 0x62, 0x54, 0x56, 0x00, 0x00, 0x00, 0x00, 0x02, 0x04, 0x01, 0x00,
+		0x01, 0xF0, 0x00, 0x1E, 0x7D, 0xF8, 0x20, 0x00, 0x02, 0x00, 0x10, 0x00, 0x02, 0x00, 0x00, 0x00,
+		0x1E, 0x7D, 0x00, 0x00, 0x80, 0x00, 0x29, 0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x20, 0x20, 0x20,
+		0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x46, 0x41, 0x54, 0x31, 0x36, 0x20, 0x20, 0x20, 0x00, 0x00
+	];
+	const MDOC_2810_0016_ADMININFO: [u8; 0x90] = [
+		0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00,
+		0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x20, 0x00, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x20,
+		0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x44, 0x00, 0x00, 0x00, 0x40,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x40,
+		0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x44,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x11, 0xF7, 0x83, 0x82, 0x54, 0x00, 0x00, 0x00, 0x00
+	];
+
+	fn get_expected_block(offset: usize, data: &[u8], boot_signature: bool) -> [u8; WEBTV_BLOCK_SIZE as usize] {
+		let mut block = [0x00; WEBTV_BLOCK_SIZE as usize];
+
+		block[offset..(offset + data.len())].copy_from_slice(data);
+		if boot_signature {
+			block[0x1fe..0x200].copy_from_slice(&BOOT_SIGNATURE);
+		}
+
+		block
+	}
+
+	fn get_default_format(total_usr_size: u64) -> FlashdiskFormat {
+		let disk_size = FlashdiskIO::calculate_formatted_size(total_usr_size);
+
+		FlashdiskFormat::new(disk_size, FlashdiskAllocations::default_for_size(disk_size)).unwrap()
+	}
+
+	// Every area has to end up inside the disk and the admin info has to check out.
+	fn check_format_fits(format: &FlashdiskFormat) {
+		let allocations = format.allocations;
+
+		assert_eq!(allocations.browser_bytes & (BROWSER_ALLOC_ALIGNMENT - 1), 0);
+		assert!(allocations.browser_size <= allocations.browser_bytes);
+		assert!(format.build_offset() + allocations.browser_bytes as u64 <= format.disk_size);
+		assert!((PARTITION_HIDDEN_SECTORS + format.total_sectors) as u64 * WEBTV_BLOCK_SIZE <= format.disk_size);
+		let admininfo = format.admininfo_sector();
+		assert_eq!(FlashdiskFormat::calculate_admininfo_checksum(&admininfo).to_be_bytes(), admininfo[ADMININFO_CHECKSUM_OFFSET..(ADMININFO_CHECKSUM_OFFSET + 4)]);
+	}
+
+	#[test]
+	fn mdoc_2810_0016_matches_the_old_blocks() {
+		let format = get_default_format(0x1000000);
+
+		assert_eq!(format.allocations, FlashdiskAllocations::default());
+		assert_eq!(format.total_sectors, 0x7d1e);
+		assert_eq!(format.build_offset(), 0x21000);
+
+		assert_eq!(format.partition_sector(), get_expected_block(PARTITION_ENTRY_OFFSET, &MDOC_2810_0016_PARTITION_ENTRY, true));
+		assert_eq!(format.bpb_sector(), get_expected_block(0x00, &MDOC_2810_0016_BPB, true));
+		assert_eq!(format.admininfo_sector(), get_expected_block(0x00, &MDOC_2810_0016_ADMININFO, false));
+	}
+
+	#[test]
+	fn mdoc_2810_0008_keeps_the_default_allocations() {
+		let format = get_default_format(0x800000);
+
+		assert_eq!(format.allocations, FlashdiskAllocations::default());
+		check_format_fits(&format);
+	}
+
+	#[test]
+	fn mdoc_2810_0004_shrinks_the_browser() {
+		let format = get_default_format(0x400000);
+
+		assert_eq!(format.allocations.browser_bytes, 0x003c0000);
+		assert_eq!(format.allocations.browser_size, DEFAULT_BROWSER_SIZE);
+		check_format_fits(&format);
+	}
+
+	#[test]
+	fn mdoc_2810_0002_shrinks_the_browser() {
+		let format = get_default_format(0x200000);
+
+		assert_eq!(format.allocations.browser_bytes, 0x001d0000);
+		assert_eq!(format.allocations.browser_size, 0x001d0000);
+		check_format_fits(&format);
+	}
+
+	#[test]
+	fn allocations_read_back_from_the_admininfo() {
+		let format = get_default_format(0x400000);
+
+		assert_eq!(FlashdiskAllocations::from_admininfo(&format.admininfo()), format.allocations);
+		assert!(format.admininfo().check_allocations(format.disk_size).is_ok());
+	}
+
+	#[test]
+	fn overlapping_admininfo_is_refused() {
+		let format = get_default_format(0x1000000);
+
+		let mut admin_info = format.admininfo();
+		admin_info.secondary_nv0_block = admin_info.primary_nv0_block + 1;
+		assert!(admin_info.check_allocations(format.disk_size).is_err());
+
+		let mut admin_info = format.admininfo();
+		admin_info.browser_alloc_bytes = format.disk_size as u32;
+		assert!(admin_info.check_allocations(format.disk_size).is_err());
+
+		let mut admin_info = format.admininfo();
+		admin_info.browser_size = admin_info.browser_alloc_bytes + 1;
+		assert!(admin_info.check_allocations(format.disk_size).is_err());
+	}
+
+	#[test]
+	fn allocations_that_dont_fit_are_rejected() {
+		let disk_size = FlashdiskIO::calculate_formatted_size(0x200000);
+
+		assert!(FlashdiskFormat::new(disk_size, FlashdiskAllocations::default()).is_err());
+	}
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::Read;
+
+const HASH_READ_BUFFER_SIZE: usize = 0x10000;
+
+// The hashes MAME lists for each ROM in `-listxml`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomHash {
+	pub size: u64,
+	pub crc32: u32,
+	pub sha1: [u8; 20]
+}
+
+impl RomHash {
+	pub fn from_file(file_path: &String) -> Result<RomHash, Box<dyn std::error::Error>> {
+		let mut f = File::open(file_path)?;
+
+		let mut crc32 = crc32fast::Hasher::new();
+		let mut sha1 = sha1_smol::Sha1::new();
+		let mut size = 0;
+
+		let mut buf = vec![0x00; HASH_READ_BUFFER_SIZE];
+		loop {
+			let read_size = f.read(&mut buf)?;
+
+			if read_size == 0 {
+				break;
+			}
+
+			crc32.update(&buf[0..read_size]);
+			sha1.update(&buf[0..read_size]);
+			size += read_size as u64;
+		}
+
+		Ok(RomHash {
+			size: size,
+			crc32: crc32.finalize(),
+			sha1: sha1.digest().bytes()
+		})
+	}
+
+	// Formatted the same way MAME formats it in `-listxml`.
+	pub fn crc32_string(&self) -> String {
+		format!("{:08x}", self.crc32)
+	}
+
+	pub fn sha1_string(&self) -> String {
+		hex::encode(self.sha1)
+	}
+
+	// Hashes MAME gives us are compared as strings so upper case and missing hashes don't trip us up.
+	// Only the hashes MAME has are checked.
+	pub fn matches(&self, expected_crc32: &Option<String>, expected_sha1: &Option<String>) -> bool {
+		match expected_crc32 {
+			Some(crc32) => {
+				if crc32.to_lowercase() != self.crc32_string() {
+					return false;
+				}
+			},
+			_ => { }
+		};
+
+		match expected_sha1 {
+			Some(sha1) => {
+				if sha1.to_lowercase() != self.sha1_string() {
+					return false;
+				}
+			},
+			_ => { }
+		};
+
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::buildio::get_test_file_path;
+
+	fn hash_data(file_name: &str, data: &[u8]) -> RomHash {
+		let file_path = get_test_file_path(file_name);
+		std::fs::write(&file_path, data).unwrap();
+
+		let rom_hash = RomHash::from_file(&file_path).unwrap();
+
+		let _ = std::fs::remove_file(&file_path);
+
+		rom_hash
+	}
+
+	#[test]
+	fn empty_input() {
+		let rom_hash = hash_data("hash_empty", &[]);
+
+		assert_eq!(rom_hash.size, 0);
+		assert_eq!(rom_hash.crc32_string(), "00000000");
+		assert_eq!(rom_hash.sha1_string(), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+	}
+
+	#[test]
+	fn known_answers() {
+		let rom_hash = hash_data("hash_check", b"123456789");
+		assert_eq!(rom_hash.crc32, 0xcbf43926);
+
+		let rom_hash = hash_data("hash_abc", b"abc");
+		assert_eq!(rom_hash.crc32_string(), "352441c2");
+		assert_eq!(rom_hash.sha1_string(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+	}
+
+	// 55 bytes is the most that leaves room for the size in the last block. 56 and 64 push the size into another block.
+	#[test]
+	fn block_boundaries() {
+		let expected_hashes = [
+			(55, "aadfe34e", "c1c8bbdc22796e28c0e15163d20899b65621d65a"),
+			(56, "79790d37", "c2db330f6083854c99d4b5bfb6e8f29f201be699"),
+			(64, "89b46555", "0098ba824b5c16427bd7a1122a5a442a25ec644d"),
+			(65, "f33faf5d", "11655326c708d70319be2610e8a57d9a5b959d3b")
+		];
+
+		for (size, crc32, sha1) in expected_hashes.iter() {
+			let rom_hash = hash_data("hash_boundary", &vec![b'a'; *size]);
+
+			assert_eq!(rom_hash.size, *size as u64);
+			assert_eq!(rom_hash.crc32_string(), *crc32);
+			assert_eq!(rom_hash.sha1_string(), *sha1);
+		}
+	}
+
+	#[test]
+	fn larger_than_the_read_buffer() {
+		let data: Vec<u8> = (0..(HASH_READ_BUFFER_SIZE * 2)).map(|index| (index & 0xff) as u8).collect();
+
+		let rom_hash = hash_data("hash_large", &data);
+
+		assert_eq!(rom_hash.size, data.len() as u64);
+		assert_eq!(rom_hash.crc32_string(), "205fbff3");
+		assert_eq!(rom_hash.sha1_string(), "f826028ed472b1fadeddbf54fc1912a095d28795");
+	}
+}